@@ -0,0 +1,316 @@
+//! HTTP management API.
+//!
+//! | Method | Path | |
+//! |---|---|---|
+//! | `GET` | `/health` | Liveness; always 200 |
+//! | `GET` | `/ready` | 200 once the timer loop is running, 503 before and while shutting down |
+//! | `GET` | `/metrics` | Prometheus metrics, see [`crate::metrics`] |
+//! | `GET` `POST` | `/tasks` | List tasks / create one from a [`TaskSpec`] |
+//! | `GET` `PUT` `DELETE` | `/tasks/:id` | Fetch / replace / remove a task; file-defined tasks can't be replaced or removed |
+//! | `POST` | `/tasks/:id/enable`, `/tasks/:id/disable` | Resume / pause its schedule |
+//! | `POST` | `/tasks/:id/run` | Start a run now |
+//! | `GET` | `/tasks/:id/executions` | The task's executions |
+//! | `GET` | `/tasks/:id/stats` | Success rate, duration percentiles and last failure of the task's executions |
+//! | `GET` | `/tasks/:id/workflow-runs` | Workflow runs the task started |
+//! | `GET` | `/executions` | Executions, filtered by `task_id`, `status`, `workflow_run_id`, `since`, `until` |
+//! | `GET` | `/executions/stats` | Stats over executions, with the same filters |
+//! | `GET` | `/executions/:id` | One execution |
+//! | `GET` | `/workflow-runs/:id` | One workflow run |
+//! | `POST` | `/hooks/*path` | Start a run of every enabled task with a [`Webhook`](crate::Schedule::Webhook) schedule on `path`; 404 if there are none |
+//!
+//! A webhook run's event is `{"source": "webhook", "path", "headers", "query", "body"}`,
+//! where `body` is the request body as JSON if it parses, as a string otherwise and
+//! null when empty.
+//!
+//! Execution lists are newest first and paginated with `limit` (default 50, at most
+//! 1000) and `offset`. Errors are returned as `{"error": "..."}`. On a follower of a
+//! [cluster](crate::cluster), changes are refused with 503 and an error naming the leader.
+//!
+//! The API can create tasks that run shell commands, so anyone who can reach it can run
+//! code as the scheduler. Given a token, every route but `/health` and `/ready` requires
+//! `Authorization: Bearer <token>` and answers 401 without it; webhook senders have to
+//! send it too. Without one, only listen on a trusted interface such as loopback.
+
+use crate::{ChronoError, ExecutionFilter, Result, Scheduler, TaskSpec};
+use axum::extract::{Path, Query, State};
+use axum::body::Bytes;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 1000;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+struct ApiError(ChronoError);
+
+impl From<ChronoError> for ApiError {
+    fn from(err: ChronoError) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            ChronoError::TaskNotFound(_) => StatusCode::NOT_FOUND,
+            ChronoError::InvalidTask(_)
+            | ChronoError::InvalidCron(_)
+            | ChronoError::InvalidTimezone(_)
+            | ChronoError::PluginError(_)
+            | ChronoError::InvalidDefinition(_)
+            | ChronoError::TemplateError(_) => StatusCode::BAD_REQUEST,
+            ChronoError::ConsensusError(_) | ChronoError::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.0.to_string() }))).into_response()
+    }
+}
+
+/// Whether `headers` carry `Authorization: Bearer <token>`, compared in constant time.
+pub(crate) fn has_bearer_token(headers: &HeaderMap, token: &str) -> bool {
+    let given = headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn not_found(what: &str, id: Uuid) -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": format!("{} not found: {}", what, id) }))).into_response()
+}
+
+/// The API's routes. With a `token`, all but the health checks require it as a bearer
+/// token.
+pub fn router(scheduler: Arc<Scheduler>, token: Option<String>) -> Router {
+    let mut api = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/tasks", get(list_tasks).post(create_task))
+        .route("/tasks/:id", get(get_task).put(update_task).delete(delete_task))
+        .route("/tasks/:id/enable", post(enable_task))
+        .route("/tasks/:id/disable", post(disable_task))
+        .route("/tasks/:id/run", post(run_task))
+        .route("/tasks/:id/executions", get(task_executions))
+        .route("/tasks/:id/stats", get(task_stats))
+        .route("/tasks/:id/workflow-runs", get(task_workflow_runs))
+        .route("/executions", get(list_executions))
+        .route("/executions/stats", get(execution_stats))
+        .route("/executions/:id", get(get_execution))
+        .route("/workflow-runs/:id", get(get_workflow_run))
+        .route("/hooks/*path", post(webhook));
+    if let Some(token) = token {
+        api = api.route_layer(middleware::from_fn_with_state(Arc::new(token), authorize));
+    }
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .merge(api)
+        .with_state(scheduler)
+}
+
+/// Turns away requests that don't carry the API token.
+async fn authorize(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    if !has_bearer_token(request.headers(), &token) {
+        let error = json!({ "error": "missing or wrong API token" });
+        return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], Json(error)).into_response();
+    }
+    next.run(request).await
+}
+
+/// Serves the API on `addr` until the process exits, requiring `token` if given.
+pub async fn serve(scheduler: Arc<Scheduler>, addr: SocketAddr, token: Option<String>) -> Result<()> {
+    serve_router(router(scheduler, token), addr).await
+}
+
+/// Serves `app`, e.g. the API merged with a cluster node's routes, on `addr`.
+pub async fn serve_router(app: Router, addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await
+        .map_err(|e| ChronoError::NetworkError(format!("bind {}: {}", addr, e)))?;
+    axum::serve(listener, app).await
+        .map_err(|e| ChronoError::NetworkError(e.to_string()))
+}
+
+async fn health() -> Json<JsonValue> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn ready(State(scheduler): State<Arc<Scheduler>>) -> (StatusCode, Json<JsonValue>) {
+    if scheduler.is_running() {
+        (StatusCode::OK, Json(json!({ "status": "ready" })))
+    } else if scheduler.is_shutting_down() {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "status": "stopping" })))
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "status": "starting" })))
+    }
+}
+
+async fn metrics(State(scheduler): State<Arc<Scheduler>>) -> Response {
+    let body = scheduler.gather_metrics();
+    ([(header::CONTENT_TYPE, scheduler.metrics().content_type())], body).into_response()
+}
+
+async fn list_tasks(State(scheduler): State<Arc<Scheduler>>) -> Json<JsonValue> {
+    let mut tasks = scheduler.list_tasks();
+    tasks.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+    Json(json!(tasks))
+}
+
+async fn create_task(
+    State(scheduler): State<Arc<Scheduler>>,
+    Json(spec): Json<TaskSpec>,
+) -> ApiResult<(StatusCode, Json<JsonValue>)> {
+    let id = scheduler.add_task(spec.into_task()?)?;
+    Ok((StatusCode::CREATED, Json(json!(scheduler.get_task(&id)?))))
+}
+
+async fn get_task(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> ApiResult<Json<JsonValue>> {
+    Ok(Json(json!(scheduler.get_task(&id)?)))
+}
+
+async fn update_task(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(id): Path<Uuid>,
+    Json(spec): Json<TaskSpec>,
+) -> ApiResult<Json<JsonValue>> {
+    ensure_not_file_defined(&scheduler, &id)?;
+    let mut task = spec.into_task()?;
+    task.id = id;
+    Ok(Json(json!(scheduler.update_task(task)?)))
+}
+
+async fn delete_task(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> ApiResult<StatusCode> {
+    ensure_not_file_defined(&scheduler, &id)?;
+    scheduler.remove_task(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Tasks loaded from definition files would be reverted by the next reload, so they
+/// are changed by editing the file instead.
+fn ensure_not_file_defined(scheduler: &Scheduler, id: &Uuid) -> ApiResult<()> {
+    match scheduler.get_task(id)?.source {
+        Some(source) => Err(ChronoError::InvalidTask(format!("task is defined in {}; edit the file instead", source)).into()),
+        None => Ok(()),
+    }
+}
+
+async fn enable_task(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> ApiResult<Json<JsonValue>> {
+    Ok(Json(json!(scheduler.set_enabled(&id, true)?)))
+}
+
+async fn disable_task(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> ApiResult<Json<JsonValue>> {
+    Ok(Json(json!(scheduler.set_enabled(&id, false)?)))
+}
+
+async fn run_task(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<(StatusCode, Json<JsonValue>)> {
+    let execution_id = scheduler.trigger_now(&id)?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "execution_id": execution_id }))))
+}
+
+async fn task_executions(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(id): Path<Uuid>,
+    Query(mut filter): Query<ExecutionFilter>,
+) -> ApiResult<Json<JsonValue>> {
+    scheduler.get_task(&id)?;
+    filter.task_id = Some(id);
+    Ok(Json(execution_page(&scheduler, filter)))
+}
+
+async fn list_executions(
+    State(scheduler): State<Arc<Scheduler>>,
+    Query(filter): Query<ExecutionFilter>,
+) -> Json<JsonValue> {
+    Json(execution_page(&scheduler, filter))
+}
+
+fn execution_page(scheduler: &Scheduler, mut filter: ExecutionFilter) -> JsonValue {
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    filter.limit = Some(limit);
+    let page = scheduler.query_executions(&filter);
+    json!({
+        "items": page.items,
+        "total": page.total,
+        "offset": filter.offset,
+        "limit": limit,
+    })
+}
+
+async fn task_stats(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(id): Path<Uuid>,
+    Query(mut filter): Query<ExecutionFilter>,
+) -> ApiResult<Json<JsonValue>> {
+    scheduler.get_task(&id)?;
+    filter.task_id = Some(id);
+    Ok(Json(json!(scheduler.execution_stats(&filter))))
+}
+
+async fn execution_stats(
+    State(scheduler): State<Arc<Scheduler>>,
+    Query(filter): Query<ExecutionFilter>,
+) -> Json<JsonValue> {
+    Json(json!(scheduler.execution_stats(&filter)))
+}
+
+async fn get_execution(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> Response {
+    match scheduler.get_execution(&id) {
+        Some(execution) => Json(json!(execution)).into_response(),
+        None => not_found("Execution", id),
+    }
+}
+
+async fn task_workflow_runs(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<JsonValue>> {
+    scheduler.get_task(&id)?;
+    Ok(Json(json!(scheduler.list_workflow_runs(&id))))
+}
+
+async fn get_workflow_run(State(scheduler): State<Arc<Scheduler>>, Path(id): Path<Uuid>) -> Response {
+    match scheduler.get_workflow_run(&id) {
+        Some(run) => Json(json!(run)).into_response(),
+        None => not_found("Workflow run", id),
+    }
+}
+
+/// Headers kept out of webhook events, which are stored and shown to anyone who can read
+/// executions.
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+async fn webhook(
+    State(scheduler): State<Arc<Scheduler>>,
+    Path(path): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Response> {
+    let headers: serde_json::Map<String, JsonValue> = headers.iter()
+        .filter(|(name, _)| !CREDENTIAL_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), json!(value.to_str().ok()?))))
+        .collect();
+    let body = match serde_json::from_slice(&body) {
+        Ok(json) => json,
+        Err(_) if body.is_empty() => JsonValue::Null,
+        Err(_) => json!(String::from_utf8_lossy(&body)),
+    };
+    let event = json!({ "source": "webhook", "path": path, "headers": headers, "query": query, "body": body });
+    let execution_ids = scheduler.fire_webhook(&path, event)?;
+    if execution_ids.is_empty() {
+        let error = format!("no task listens on webhook '{}'", path);
+        return Ok((StatusCode::NOT_FOUND, Json(json!({ "error": error }))).into_response());
+    }
+    Ok((StatusCode::ACCEPTED, Json(json!({ "execution_ids": execution_ids }))).into_response())
+}
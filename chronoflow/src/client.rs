@@ -0,0 +1,106 @@
+//! Typed client for the HTTP API in [`crate::api`].
+
+use crate::{ChronoError, ExecutionFilter, ExecutionPage, ExecutionStats, Result, Task, TaskExecution, TaskSpec};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+pub struct ApiClient {
+    base_url: String,
+    http: Client,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    /// `base_url` is the server root, e.g. `http://127.0.0.1:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: Client::new(),
+            token: None,
+        }
+    }
+
+    /// Sends `token` as a bearer token, for a server started with one.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends `request` and decodes the JSON response, turning the server's
+    /// `{"error": ...}` bodies into errors.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = request.send().await
+            .map_err(|e| ChronoError::NetworkError(format!("{}: {}", self.base_url, e)))?;
+        let status = response.status();
+        let text = response.text().await
+            .map_err(|e| ChronoError::NetworkError(format!("{}: {}", self.base_url, e)))?;
+
+        if !status.is_success() {
+            // API errors are `{"error": ...}`; request rejections may be plain text.
+            let message = serde_json::from_str::<JsonValue>(&text).ok()
+                .and_then(|body| body.get("error").and_then(|e| e.as_str()).map(str::to_string))
+                .unwrap_or(text);
+            return Err(ChronoError::ApiError(status.as_u16(), message));
+        }
+        let text = if text.is_empty() { "null" } else { &text };
+        serde_json::from_str(text)
+            .map_err(|e| ChronoError::NetworkError(format!("unexpected response from {}: {}", self.base_url, e)))
+    }
+
+    pub async fn list_tasks(&self) -> Result<Vec<Task>> {
+        self.send(self.request(Method::GET, "/tasks")).await
+    }
+
+    pub async fn create_task(&self, spec: &TaskSpec) -> Result<Task> {
+        self.send(self.request(Method::POST, "/tasks").json(spec)).await
+    }
+
+    pub async fn get_task(&self, id: &Uuid) -> Result<Task> {
+        self.send(self.request(Method::GET, &format!("/tasks/{}", id))).await
+    }
+
+    pub async fn delete_task(&self, id: &Uuid) -> Result<()> {
+        self.send::<JsonValue>(self.request(Method::DELETE, &format!("/tasks/{}", id))).await.map(|_| ())
+    }
+
+    pub async fn set_enabled(&self, id: &Uuid, enabled: bool) -> Result<Task> {
+        let action = if enabled { "enable" } else { "disable" };
+        self.send(self.request(Method::POST, &format!("/tasks/{}/{}", id, action))).await
+    }
+
+    /// Starts a run now; returns the execution id.
+    pub async fn trigger(&self, id: &Uuid) -> Result<Uuid> {
+        let body: JsonValue = self.send(self.request(Method::POST, &format!("/tasks/{}/run", id))).await?;
+        body.get("execution_id")
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| ChronoError::NetworkError("response has no execution_id".into()))
+    }
+
+    pub async fn list_executions(&self, filter: &ExecutionFilter) -> Result<ExecutionPage> {
+        self.send(self.request(Method::GET, "/executions").query(filter)).await
+    }
+
+    /// Stats over the executions matching `filter`; its `offset` and `limit` are ignored.
+    pub async fn execution_stats(&self, filter: &ExecutionFilter) -> Result<ExecutionStats> {
+        self.send(self.request(Method::GET, "/executions/stats").query(filter)).await
+    }
+
+    pub async fn get_execution(&self, id: &Uuid) -> Result<TaskExecution> {
+        self.send(self.request(Method::GET, &format!("/executions/{}", id))).await
+    }
+}
@@ -0,0 +1,1486 @@
+//! Leader election and task replication across scheduler nodes, so that each due task
+//! fires on exactly one node.
+//!
+//! Nodes run a compact version of Raft over HTTP. The elected leader is the only node
+//! whose timer loop fires tasks. Every task change made on it is appended to a
+//! replicated log. A fire starts its runs only once the task's moved-on `next_run` is
+//! held by a majority, so a leader elected afterwards can't fire the same time again.
+//! Followers apply committed changes to their own scheduler and serve reads. They
+//! reject writes with [`ChronoError::ConsensusError`] naming the leader.
+//!
+//! Executions are replicated too, so any node can answer execution queries. Workflow
+//! runs stay on the node that ran them.
+//!
+//! With [`WorkerConfig`] set, the leader only decides when tasks are due. Each due run
+//! becomes a [`Job`] in a replicated queue and every node runs a worker pool that
+//! claims jobs through the log; the first committed claim wins, so a job runs once.
+//! Tasks are sharded over the live members with a [`HashRing`] on `Task::id`. A worker
+//! claims the jobs of the tasks it owns and steals any job left unclaimed for
+//! `steal_after`. Members are the nodes the leader has heard from within
+//! `member_timeout`; when one joins or leaves, ownership moves with the ring. A job
+//! claimed by a node that then dies is not run again.
+//!
+//! A node saves its term, vote and log to the wrapped storage backend with
+//! [`Storage::save_raft`] before it answers another node or counts its own entries
+//! towards a majority, and picks them up again when the scheduler loads its storage.
+//! Over [`NullStorage`](crate::NullStorage) a restarted node rejoins empty and catches
+//! up from the leader, so tasks only survive while a majority stays up. The wrapped
+//! backend also keeps each node's execution history.
+//!
+//! Nodes sign their requests to each other with [`ClusterConfig::secret`]; the `POST`
+//! routes below refuse requests without it with 401. Without a secret anyone who can
+//! reach a node's API can vote in elections and rewrite its log, so leave it unset
+//! only on a network that nothing else can reach.
+//!
+//! | Method | Path | |
+//! |---|---|---|
+//! | `POST` | `/cluster/vote` | Raft `RequestVote` |
+//! | `POST` | `/cluster/append` | Raft `AppendEntries` |
+//! | `POST` | `/cluster/snapshot` | Raft `InstallSnapshot` |
+//! | `POST` | `/cluster/propose` | Append a follower's command on the leader |
+//! | `GET` | `/cluster/status` | This node's [`ClusterStatus`] |
+
+use crate::api::has_bearer_token;
+use crate::shard::{HashRing, DEFAULT_VNODES};
+use crate::storage::{Snapshot, Storage};
+use crate::{ChronoError, Coordinator, ExecutionStatus, Job, Result, Scheduler, Task, TaskExecution, WorkflowRun};
+use async_trait::async_trait;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use tokio::runtime::RuntimeFlavor;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use tracing::{error, info, warn};
+
+// Most entries sent to a follower in one append request.
+const MAX_ENTRIES_PER_APPEND: usize = 256;
+
+/// Another node of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Peer {
+    pub id: String,
+    /// Root of the node's HTTP API, e.g. `http://10.0.0.2:8080`.
+    pub url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    /// Unique name of this node.
+    pub node_id: String,
+    /// Every other node; the cluster is this node plus its peers.
+    pub peers: Vec<Peer>,
+    /// How often the leader contacts each follower when there is nothing to send.
+    pub heartbeat_interval: Duration,
+    /// A follower that hears nothing from a leader for a random time in this range
+    /// starts an election.
+    pub election_timeout_min: Duration,
+    pub election_timeout_max: Duration,
+    /// How long [`Coordinator::sync`] waits for a majority before giving up.
+    pub commit_timeout: Duration,
+    /// Applied entries kept in the log before it is compacted into a snapshot.
+    pub max_log_entries: usize,
+    /// A peer the leader hasn't heard from for this long stops being a member and
+    /// its tasks move to other nodes.
+    pub member_timeout: Duration,
+    /// Spread runs over every node through the shared work queue; `None` runs
+    /// everything on the leader. Set it the same way on every node.
+    pub workers: Option<WorkerConfig>,
+    /// Shared by every node and sent as a bearer token with each request to a peer.
+    /// Requests to this node's Raft routes without it are refused.
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Runs this node executes at once.
+    pub concurrency: usize,
+    /// How long a job waits for its owner before any idle worker may take it.
+    pub steal_after: Duration,
+    /// Points per node on the hash ring.
+    pub vnodes: usize,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            steal_after: Duration::from_secs(2),
+            vnodes: DEFAULT_VNODES,
+        }
+    }
+}
+
+impl ClusterConfig {
+    pub fn new(node_id: impl Into<String>, peers: Vec<Peer>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            peers,
+            heartbeat_interval: Duration::from_millis(50),
+            election_timeout_min: Duration::from_millis(150),
+            election_timeout_max: Duration::from_millis(300),
+            commit_timeout: Duration::from_secs(5),
+            max_log_entries: 1000,
+            member_timeout: Duration::from_secs(1),
+            workers: None,
+            secret: None,
+        }
+    }
+}
+
+/// A change to the replicated state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    /// Appended by each new leader to commit what earlier leaders left behind.
+    Noop,
+    PutTask(Task),
+    RemoveTask(Uuid),
+    Execution(TaskExecution),
+    /// The nodes that currently share the work; appended by the leader.
+    Members(Vec<String>),
+    Enqueue(Job),
+    /// Takes a queued job for `node`, unless another claim got it first.
+    Claim { job_id: Uuid, node: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub term: u64,
+    /// Node whose command this is: the leader, or the follower that proposed it.
+    pub origin: String,
+    pub command: Command,
+}
+
+/// What the log builds up, and what a snapshot carries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateMachine {
+    pub tasks: HashMap<Uuid, Task>,
+    pub members: Vec<String>,
+    /// Jobs waiting to be claimed.
+    pub jobs: BTreeMap<Uuid, Job>,
+}
+
+impl StateMachine {
+    /// Applies a command. Returns the job and the node that won it for a successful
+    /// claim.
+    fn apply(&mut self, command: &Command) -> Option<(Job, String)> {
+        match command {
+            Command::PutTask(task) => {
+                self.tasks.insert(task.id, task.clone());
+            }
+            Command::RemoveTask(id) => {
+                self.tasks.remove(id);
+            }
+            Command::Members(members) => self.members = members.clone(),
+            Command::Enqueue(job) => {
+                self.jobs.insert(job.id, job.clone());
+            }
+            Command::Claim { job_id, node } => {
+                return self.jobs.remove(job_id).map(|job| (job, node.clone()));
+            }
+            Command::Noop | Command::Execution(_) => {}
+        }
+        None
+    }
+}
+
+/// A change to a node's Raft state, saved before the node acts on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RaftRecord {
+    /// The current term, and the candidate this node voted for in it.
+    Vote { term: u64, voted_for: Option<String> },
+    /// Log entries from `index` on, replacing any the log held from there.
+    Entries { index: u64, entries: Vec<Entry> },
+    /// The log up to `index` compacted into `state`.
+    Snapshot { index: u64, term: u64, state: StateMachine },
+}
+
+/// A node's saved Raft state, as rebuilt from its [`RaftRecord`]s.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RaftLog {
+    pub term: u64,
+    pub voted_for: Option<String>,
+    pub snapshot_index: u64,
+    pub snapshot_term: u64,
+    pub snapshot: StateMachine,
+    /// Entries after `snapshot_index`.
+    pub entries: Vec<Entry>,
+}
+
+impl RaftLog {
+    pub fn apply(&mut self, record: RaftRecord) {
+        match record {
+            RaftRecord::Vote { term, voted_for } => {
+                self.term = term;
+                self.voted_for = voted_for;
+            }
+            RaftRecord::Entries { index, mut entries } => {
+                let first = self.snapshot_index + 1;
+                if index < first {
+                    entries.drain(..((first - index) as usize).min(entries.len()));
+                }
+                self.entries.truncate((index.max(first) - first) as usize);
+                self.entries.extend(entries);
+            }
+            RaftRecord::Snapshot { index, term, state } => {
+                if index <= self.snapshot_index {
+                    return;
+                }
+                let drop_count = (index - self.snapshot_index) as usize;
+                if self.entries.get(drop_count - 1).is_some_and(|e| e.term == term) {
+                    self.entries.drain(..drop_count);
+                } else {
+                    self.entries.clear();
+                }
+                self.snapshot_index = index;
+                self.snapshot_term = term;
+                self.snapshot = state;
+            }
+        }
+    }
+
+    /// Records that rebuild this state, e.g. to rewrite a compacted log.
+    pub fn records(&self) -> Vec<RaftRecord> {
+        vec![
+            RaftRecord::Vote { term: self.term, voted_for: self.voted_for.clone() },
+            RaftRecord::Snapshot { index: self.snapshot_index, term: self.snapshot_term, state: self.snapshot.clone() },
+            RaftRecord::Entries { index: self.snapshot_index + 1, entries: self.entries.clone() },
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRequest {
+    pub term: u64,
+    pub candidate_id: String,
+    pub last_log_index: u64,
+    pub last_log_term: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteResponse {
+    pub term: u64,
+    pub granted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendRequest {
+    pub term: u64,
+    pub leader_id: String,
+    pub prev_log_index: u64,
+    pub prev_log_term: u64,
+    pub entries: Vec<Entry>,
+    pub leader_commit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendResponse {
+    pub term: u64,
+    pub success: bool,
+    /// The follower's last log index, so a leader can skip back over a gap in one step.
+    pub last_log_index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotRequest {
+    pub term: u64,
+    pub leader_id: String,
+    pub last_index: u64,
+    pub last_term: u64,
+    pub state: StateMachine,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposeRequest {
+    pub origin: String,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposeResponse {
+    /// Log index the command was committed at.
+    pub index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotResponse {
+    pub term: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterStatus {
+    pub node_id: String,
+    pub role: Role,
+    pub term: u64,
+    pub leader_id: Option<String>,
+    pub commit_index: u64,
+    pub last_log_index: u64,
+    /// Whether this node is a leader that has caught up and is firing tasks.
+    pub ready: bool,
+    pub members: Vec<String>,
+}
+
+struct RaftState {
+    role: Role,
+    term: u64,
+    voted_for: Option<String>,
+    leader_id: Option<String>,
+    /// Entries after `snapshot_index`; entry `i` is at `log[i - snapshot_index - 1]`.
+    log: Vec<Entry>,
+    snapshot_index: u64,
+    snapshot_term: u64,
+    snapshot: StateMachine,
+    commit_index: u64,
+    last_applied: u64,
+    next_index: HashMap<String, u64>,
+    match_index: HashMap<String, u64>,
+    /// When the leader last got a response from each peer.
+    last_contact: HashMap<String, Instant>,
+    /// Members this leader has appended but may not have applied yet.
+    proposed_members: Option<Vec<String>>,
+    election_deadline: Instant,
+    /// Index of this leader's `Noop`; once applied, the leader is ready.
+    noop_index: u64,
+    ready: bool,
+    /// The state as of `last_applied`.
+    machine: StateMachine,
+}
+
+impl RaftState {
+    fn last_index(&self) -> u64 {
+        self.snapshot_index + self.log.len() as u64
+    }
+
+    fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            return Some(self.snapshot_term);
+        }
+        if index < self.snapshot_index {
+            return None;
+        }
+        self.log.get((index - self.snapshot_index - 1) as usize).map(|e| e.term)
+    }
+
+    fn entry(&self, index: u64) -> &Entry {
+        &self.log[(index - self.snapshot_index - 1) as usize]
+    }
+
+    fn last_term(&self) -> u64 {
+        self.term_at(self.last_index()).unwrap_or(0)
+    }
+
+    fn is_ready_leader(&self) -> bool {
+        self.role == Role::Leader && self.ready
+    }
+
+    /// Moves to `term` as a follower. Returns whether this node was a ready leader,
+    /// whose scheduler then has to be reverted to the committed state.
+    fn become_follower(&mut self, term: u64) -> bool {
+        let was_ready = self.is_ready_leader();
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+        if self.role != Role::Follower {
+            self.leader_id = None;
+        }
+        self.role = Role::Follower;
+        self.ready = false;
+        was_ready
+    }
+}
+
+/// This node's side of the cluster. Create it with [`ClusterNode::new`], build the
+/// scheduler on [`ClusterNode::storage`] with the node as its coordinator, then
+/// [`attach`](ClusterNode::attach) the scheduler, serve [`ClusterNode::router`] next to
+/// the API and [`start`](ClusterNode::start) it.
+pub struct ClusterNode {
+    config: ClusterConfig,
+    state: Mutex<RaftState>,
+    /// Serializes applying entries to the scheduler with replacing its tasks wholesale.
+    apply_lock: Mutex<()>,
+    scheduler: OnceLock<Weak<Scheduler>>,
+    inner: Arc<dyn Storage>,
+    http: reqwest::Client,
+    /// Wakes the replication loops when entries are appended.
+    replicate: Notify,
+    /// Wakes the applier when the commit index moves.
+    apply: Notify,
+    /// Wakes `sync` callers when the commit index moves or leadership is lost.
+    committed: Notify,
+    /// Wakes proposers waiting for their entry to be applied.
+    applied: Notify,
+    /// Wakes the worker pool when jobs are queued or members change.
+    queued: Notify,
+    /// Owners of tasks among the current members.
+    ring: Mutex<HashRing>,
+    /// Jobs this node won, for its worker pool.
+    claimed: mpsc::UnboundedSender<Job>,
+    claimed_rx: Mutex<Option<mpsc::UnboundedReceiver<Job>>>,
+    /// Commands a follower sends on to the leader, in order.
+    forward: mpsc::UnboundedSender<Command>,
+    forward_rx: Mutex<Option<mpsc::UnboundedReceiver<Command>>>,
+    leadership: watch::Sender<bool>,
+    stop: CancellationToken,
+}
+
+impl ClusterNode {
+    /// `inner` is where this node keeps its execution history and workflow runs.
+    pub fn new(config: ClusterConfig, inner: Arc<dyn Storage>) -> Arc<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(config.election_timeout_max)
+            .build()
+            .expect("default http client config is valid");
+        let state = RaftState {
+            role: Role::Follower,
+            term: 0,
+            voted_for: None,
+            leader_id: None,
+            log: Vec::new(),
+            snapshot_index: 0,
+            snapshot_term: 0,
+            snapshot: StateMachine::default(),
+            commit_index: 0,
+            last_applied: 0,
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            last_contact: HashMap::new(),
+            proposed_members: None,
+            election_deadline: Instant::now() + random_timeout(&config),
+            noop_index: 0,
+            ready: false,
+            machine: StateMachine::default(),
+        };
+        let (claimed, claimed_rx) = mpsc::unbounded_channel();
+        let (forward, forward_rx) = mpsc::unbounded_channel();
+        Arc::new(Self {
+            config,
+            state: Mutex::new(state),
+            apply_lock: Mutex::new(()),
+            scheduler: OnceLock::new(),
+            inner,
+            http,
+            replicate: Notify::new(),
+            apply: Notify::new(),
+            committed: Notify::new(),
+            applied: Notify::new(),
+            queued: Notify::new(),
+            ring: Mutex::new(HashRing::default()),
+            claimed,
+            claimed_rx: Mutex::new(Some(claimed_rx)),
+            forward,
+            forward_rx: Mutex::new(Some(forward_rx)),
+            leadership: watch::Sender::new(false),
+            stop: CancellationToken::new(),
+        })
+    }
+
+    /// Storage for the node's scheduler. Task changes go through the replicated log and
+    /// fail unless this node leads, and the scheduler reports them done once a majority
+    /// holds them; everything else is written to the wrapped storage. Waiting for the
+    /// majority blocks the scheduler's caller, so the node needs tokio's multi-threaded
+    /// runtime.
+    pub fn storage(self: &Arc<Self>) -> Arc<dyn Storage> {
+        Arc::new(ClusterStorage { node: self.clone(), last_write: Mutex::new(None) })
+    }
+
+    /// Connects the scheduler that committed changes are applied to.
+    pub fn attach(&self, scheduler: &Arc<Scheduler>) {
+        let _ = self.scheduler.set(Arc::downgrade(scheduler));
+    }
+
+    pub fn router(self: &Arc<Self>) -> Router {
+        let rpc = Router::new()
+            .route("/cluster/vote", post(vote))
+            .route("/cluster/append", post(append_entries))
+            .route("/cluster/snapshot", post(install_snapshot))
+            .route("/cluster/propose", post(propose))
+            .route_layer(middleware::from_fn_with_state(self.clone(), authorize));
+        Router::new()
+            .route("/cluster/status", get(status))
+            .merge(rpc)
+            .with_state(self.clone())
+    }
+
+    /// Spawns the election timer, the applier, one replication loop per peer and, if
+    /// configured, the worker pool.
+    pub fn start(self: &Arc<Self>) {
+        if self.config.secret.is_none() {
+            warn!("No cluster secret set; anyone who can reach this node can take part in the cluster");
+        }
+        tokio::spawn(self.clone().election_loop());
+        tokio::spawn(self.clone().apply_loop());
+        for peer in &self.config.peers {
+            tokio::spawn(self.clone().replication_loop(peer.clone()));
+        }
+        if let Some(forward) = self.forward_rx.lock().unwrap().take() {
+            tokio::spawn(self.clone().forward_loop(forward));
+        }
+        if let Some(workers) = self.config.workers.clone() {
+            if let Some(claimed) = self.claimed_rx.lock().unwrap().take() {
+                tokio::spawn(self.clone().worker_loop(workers, claimed));
+            }
+        }
+    }
+
+    /// Leaves the cluster: stops the background loops, steps down and stops answering
+    /// other nodes.
+    pub fn stop(&self) {
+        self.stop.cancel();
+        let was_ready = self.state.lock().unwrap().become_follower(0);
+        self.lost_leadership(was_ready);
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        let state = self.state.lock().unwrap();
+        ClusterStatus {
+            node_id: self.config.node_id.clone(),
+            role: state.role,
+            term: state.term,
+            leader_id: state.leader_id.clone(),
+            commit_index: state.commit_index,
+            last_log_index: state.last_index(),
+            ready: state.is_ready_leader(),
+            members: state.machine.members.clone(),
+        }
+    }
+
+    /// The member that owns `task_id`, once the leader has published members.
+    pub fn owner(&self, task_id: &Uuid) -> Option<String> {
+        self.ring.lock().unwrap().owner(task_id).map(str::to_string)
+    }
+
+    fn scheduler(&self) -> Option<Arc<Scheduler>> {
+        self.scheduler.get().and_then(Weak::upgrade)
+    }
+
+    fn majority(&self) -> usize {
+        let size = self.config.peers.len() + 1;
+        size / 2 + 1
+    }
+
+    fn not_leader(&self, state: &RaftState) -> ChronoError {
+        let leader = state.leader_id.as_ref().filter(|id| **id != self.config.node_id);
+        match leader {
+            Some(id) => match self.config.peers.iter().find(|p| p.id == *id) {
+                Some(peer) => ChronoError::ConsensusError(format!("not the leader; leader is {} ({})", id, peer.url)),
+                None => ChronoError::ConsensusError(format!("not the leader; leader is {}", id)),
+            },
+            None => ChronoError::ConsensusError("no leader elected yet".into()),
+        }
+    }
+
+    fn save(&self, record: RaftRecord) -> Result<()> {
+        self.inner.save_raft(&record)
+    }
+
+    fn save_vote(&self, state: &RaftState) -> Result<()> {
+        self.save(RaftRecord::Vote { term: state.term, voted_for: state.voted_for.clone() })
+    }
+
+    /// Steps down on seeing a later term in a response. Failing to save the term only
+    /// means a restarted node starts from the term it last voted in, so it is reported
+    /// rather than returned.
+    fn step_down(&self, state: &mut RaftState, term: u64) -> bool {
+        let was_ready = state.become_follower(term);
+        if let Err(e) = self.save_vote(state) {
+            error!("Failed to save term {}: {}", state.term, e);
+        }
+        was_ready
+    }
+
+    /// Picks up the Raft state saved before a restart, before the node starts.
+    fn restore(&self, saved: RaftLog) {
+        let mut state = self.state.lock().unwrap();
+        state.term = saved.term;
+        state.voted_for = saved.voted_for;
+        state.log = saved.entries;
+        state.snapshot_index = saved.snapshot_index;
+        state.snapshot_term = saved.snapshot_term;
+        state.commit_index = saved.snapshot_index;
+        state.last_applied = saved.snapshot_index;
+        state.machine = saved.snapshot.clone();
+        state.snapshot = saved.snapshot;
+        let members = state.machine.members.clone();
+        drop(state);
+        self.rebuild_ring(&members);
+    }
+
+    /// Appends a command to the log as leader. Returns its index and term.
+    fn append(&self, command: Command) -> Result<(u64, u64)> {
+        self.append_from(self.config.node_id.clone(), command)
+    }
+
+    fn append_from(&self, origin: String, command: Command) -> Result<(u64, u64)> {
+        let mut state = self.state.lock().unwrap();
+        if !state.is_ready_leader() {
+            return Err(self.not_leader(&state));
+        }
+        let entry = Entry { term: state.term, origin, command };
+        self.save(RaftRecord::Entries { index: state.last_index() + 1, entries: vec![entry.clone()] })?;
+        state.log.push(entry);
+        let appended = (state.last_index(), state.term);
+        self.advance_commit(&mut state);
+        drop(state);
+        self.replicate.notify_waiters();
+        Ok(appended)
+    }
+
+    /// Gets a command committed from any node: appended here when leading, otherwise
+    /// sent to the leader. Returns its log index.
+    async fn submit(&self, command: Command) -> Result<u64> {
+        let leader = {
+            let state = self.state.lock().unwrap();
+            if state.is_ready_leader() {
+                None
+            } else {
+                let peer = state.leader_id.as_ref()
+                    .and_then(|id| self.config.peers.iter().find(|p| p.id == *id))
+                    .cloned();
+                Some(peer.ok_or_else(|| self.not_leader(&state))?)
+            }
+        };
+        match leader {
+            None => {
+                let (index, term) = self.append(command)?;
+                self.wait_committed(index, term).await?;
+                Ok(index)
+            }
+            Some(peer) => {
+                let request = ProposeRequest { origin: self.config.node_id.clone(), command };
+                let response: ProposeResponse = self.call(&peer, "propose", &request).await
+                    .map_err(|e| ChronoError::ConsensusError(format!("proposal not accepted: {}", e)))?;
+                Ok(response.index)
+            }
+        }
+    }
+
+    /// Like `submit`, then waits for this node to apply the command.
+    async fn propose(&self, command: Command) -> Result<()> {
+        let index = self.submit(command).await?;
+        let deadline = Instant::now() + self.config.commit_timeout;
+        loop {
+            let applied = self.applied.notified();
+            tokio::pin!(applied);
+            applied.as_mut().enable();
+            if self.state.lock().unwrap().last_applied >= index {
+                return Ok(());
+            }
+            if tokio::time::timeout_at(deadline, applied).await.is_err() {
+                return Err(ChronoError::Timeout(self.config.commit_timeout));
+            }
+        }
+    }
+
+    /// Waits until the entry at `index`, appended by this node as leader in `term`,
+    /// is committed.
+    async fn wait_committed(&self, index: u64, term: u64) -> Result<()> {
+        let deadline = Instant::now() + self.config.commit_timeout;
+        loop {
+            let committed = self.committed.notified();
+            tokio::pin!(committed);
+            committed.as_mut().enable();
+            {
+                let state = self.state.lock().unwrap();
+                let lost = || ChronoError::ConsensusError("lost leadership before changes were replicated".into());
+                if state.commit_index >= index {
+                    // A later leader may have replaced the entry before it committed.
+                    return match state.term_at(index) {
+                        Some(held) if held != term => Err(lost()),
+                        _ => Ok(()),
+                    };
+                }
+                if state.role != Role::Leader || state.term != term {
+                    return Err(lost());
+                }
+            }
+            if tokio::time::timeout_at(deadline, committed).await.is_err() {
+                return Err(ChronoError::ConsensusError(format!(
+                    "changes not replicated to a majority within {:?}", self.config.commit_timeout
+                )));
+            }
+        }
+    }
+
+    /// [`wait_committed`](Self::wait_committed) for synchronous callers. Replication
+    /// has to go on meanwhile, so this can't wait on a current-thread runtime.
+    fn block_until_committed(&self, index: u64, term: u64) -> Result<()> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => Err(ChronoError::ConsensusError(
+                "waiting for replication needs tokio's multi-threaded runtime".into(),
+            )),
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(self.wait_committed(index, term))),
+            Err(_) => tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .map_err(|e| ChronoError::ConsensusError(e.to_string()))?
+                .block_on(self.wait_committed(index, term)),
+        }
+    }
+
+    /// Commits the highest entry of the current term held by a majority.
+    fn advance_commit(&self, state: &mut RaftState) {
+        let mut index = state.last_index();
+        while index > state.commit_index {
+            if state.term_at(index) == Some(state.term) {
+                let holders = 1 + state.match_index.values().filter(|m| **m >= index).count();
+                if holders >= self.majority() {
+                    state.commit_index = index;
+                    self.apply.notify_one();
+                    self.committed.notify_waiters();
+                    return;
+                }
+            }
+            index -= 1;
+        }
+    }
+
+    /// Reverts the scheduler to the committed tasks after this node stops leading,
+    /// dropping changes that may never commit.
+    fn lost_leadership(&self, was_ready: bool) {
+        if !was_ready {
+            return;
+        }
+        warn!("Node {} is no longer the cluster leader", self.config.node_id);
+        self.leadership.send_replace(false);
+        self.committed.notify_waiters();
+        let _apply = self.apply_lock.lock().unwrap();
+        let tasks: Vec<Task> = self.state.lock().unwrap().machine.tasks.values().cloned().collect();
+        if let Some(scheduler) = self.scheduler() {
+            scheduler.replace_tasks(tasks);
+        }
+    }
+
+    async fn election_loop(self: Arc<Self>) {
+        let tick = self.config.heartbeat_interval / 2;
+        loop {
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = tokio::time::sleep(tick) => {}
+            }
+            let due = {
+                let state = self.state.lock().unwrap();
+                state.role != Role::Leader && Instant::now() >= state.election_deadline
+            };
+            if due {
+                self.clone().run_election().await;
+            }
+            self.update_members();
+        }
+    }
+
+    async fn run_election(self: Arc<Self>) {
+        let request = {
+            let mut state = self.state.lock().unwrap();
+            state.term += 1;
+            state.role = Role::Candidate;
+            state.voted_for = Some(self.config.node_id.clone());
+            state.leader_id = None;
+            state.election_deadline = Instant::now() + random_timeout(&self.config);
+            if let Err(e) = self.save_vote(&state) {
+                error!("Failed to save term {}, not standing for election: {}", state.term, e);
+                state.role = Role::Follower;
+                return;
+            }
+            VoteRequest {
+                term: state.term,
+                candidate_id: self.config.node_id.clone(),
+                last_log_index: state.last_index(),
+                last_log_term: state.last_term(),
+            }
+        };
+
+        let mut votes = 1;
+        if votes >= self.majority() {
+            self.become_leader(request.term);
+            return;
+        }
+        let mut pending = tokio::task::JoinSet::new();
+        for peer in self.config.peers.clone() {
+            let node = self.clone();
+            let request = request.clone();
+            pending.spawn(async move { node.call::<_, VoteResponse>(&peer, "vote", &request).await });
+        }
+        while let Some(response) = pending.join_next().await {
+            let Ok(Ok(response)) = response else { continue };
+            if response.term > request.term {
+                let was_ready = self.step_down(&mut self.state.lock().unwrap(), response.term);
+                self.lost_leadership(was_ready);
+                return;
+            }
+            if response.granted {
+                votes += 1;
+                if votes >= self.majority() {
+                    self.become_leader(request.term);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn become_leader(&self, term: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.role != Role::Candidate || state.term != term {
+            return;
+        }
+        info!("Node {} elected cluster leader for term {}", self.config.node_id, term);
+        state.role = Role::Leader;
+        state.leader_id = Some(self.config.node_id.clone());
+        let next = state.last_index() + 1;
+        let now = Instant::now();
+        for peer in &self.config.peers {
+            state.next_index.insert(peer.id.clone(), next);
+            state.match_index.insert(peer.id.clone(), 0);
+            // Peers count as members until they fail to answer this leader.
+            state.last_contact.insert(peer.id.clone(), now);
+        }
+        state.proposed_members = None;
+        let noop = Entry { term, origin: self.config.node_id.clone(), command: Command::Noop };
+        if let Err(e) = self.save(RaftRecord::Entries { index: state.last_index() + 1, entries: vec![noop.clone()] }) {
+            error!("Failed to save the log, stepping down: {}", e);
+            state.become_follower(term);
+            return;
+        }
+        state.log.push(noop);
+        state.noop_index = state.last_index();
+        self.advance_commit(&mut state);
+        drop(state);
+        self.replicate.notify_waiters();
+    }
+
+    /// As ready leader, publishes the members when peers start or stop answering.
+    fn update_members(&self) {
+        let members = {
+            let state = self.state.lock().unwrap();
+            if !state.is_ready_leader() {
+                return;
+            }
+            let now = Instant::now();
+            let mut members: Vec<String> = self.config.peers.iter()
+                .filter(|p| state.last_contact.get(&p.id).is_some_and(|at| now - *at <= self.config.member_timeout))
+                .map(|p| p.id.clone())
+                .chain(std::iter::once(self.config.node_id.clone()))
+                .collect();
+            members.sort();
+            let current = state.proposed_members.as_ref().unwrap_or(&state.machine.members);
+            if members == *current {
+                return;
+            }
+            members
+        };
+        info!("Cluster members: {}", members.join(", "));
+        if self.append(Command::Members(members.clone())).is_ok() {
+            self.state.lock().unwrap().proposed_members = Some(members);
+        }
+    }
+
+    async fn replication_loop(self: Arc<Self>, peer: Peer) {
+        loop {
+            if self.stop.is_cancelled() {
+                return;
+            }
+            let wakeup = self.replicate.notified();
+            tokio::pin!(wakeup);
+            wakeup.as_mut().enable();
+
+            let sent = self.replicate_to(&peer).await;
+            if sent == Some(true) {
+                // More entries are waiting for this peer.
+                continue;
+            }
+            let pause = self.config.heartbeat_interval;
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = &mut wakeup, if sent.is_some() => {}
+                _ = tokio::time::sleep(pause) => {}
+            }
+        }
+    }
+
+    /// Sends the peer whatever it is missing, or a heartbeat. Returns `None` if this node
+    /// isn't leading or the peer couldn't be reached, otherwise whether the peer is
+    /// still behind.
+    async fn replicate_to(&self, peer: &Peer) -> Option<bool> {
+        enum Outgoing {
+            Append(AppendRequest),
+            Snapshot(SnapshotRequest),
+        }
+        let outgoing = {
+            let state = self.state.lock().unwrap();
+            if state.role != Role::Leader {
+                return None;
+            }
+            let next = state.next_index.get(&peer.id).copied().unwrap_or(1).max(1);
+            if next <= state.snapshot_index {
+                Outgoing::Snapshot(SnapshotRequest {
+                    term: state.term,
+                    leader_id: self.config.node_id.clone(),
+                    last_index: state.snapshot_index,
+                    last_term: state.snapshot_term,
+                    state: state.snapshot.clone(),
+                })
+            } else {
+                let prev_log_index = next - 1;
+                let end = state.last_index().min(prev_log_index + MAX_ENTRIES_PER_APPEND as u64);
+                Outgoing::Append(AppendRequest {
+                    term: state.term,
+                    leader_id: self.config.node_id.clone(),
+                    prev_log_index,
+                    prev_log_term: state.term_at(prev_log_index).unwrap_or(0),
+                    entries: (next..=end).map(|i| state.entry(i).clone()).collect(),
+                    leader_commit: state.commit_index,
+                })
+            }
+        };
+
+        match outgoing {
+            Outgoing::Append(request) => {
+                let response: AppendResponse = self.call(peer, "append", &request).await.ok()?;
+                let mut state = self.state.lock().unwrap();
+                state.last_contact.insert(peer.id.clone(), Instant::now());
+                if response.term > state.term {
+                    let was_ready = self.step_down(&mut state, response.term);
+                    drop(state);
+                    self.lost_leadership(was_ready);
+                    return None;
+                }
+                if state.role != Role::Leader || state.term != request.term {
+                    return None;
+                }
+                if response.success {
+                    let matched = request.prev_log_index + request.entries.len() as u64;
+                    let current = state.match_index.entry(peer.id.clone()).or_insert(0);
+                    *current = (*current).max(matched);
+                    state.next_index.insert(peer.id.clone(), matched + 1);
+                    self.advance_commit(&mut state);
+                    Some(matched < state.last_index())
+                } else {
+                    let next = (request.prev_log_index).min(response.last_log_index + 1).max(1);
+                    state.next_index.insert(peer.id.clone(), next);
+                    Some(true)
+                }
+            }
+            Outgoing::Snapshot(request) => {
+                let response: SnapshotResponse = self.call(peer, "snapshot", &request).await.ok()?;
+                let mut state = self.state.lock().unwrap();
+                state.last_contact.insert(peer.id.clone(), Instant::now());
+                if response.term > state.term {
+                    let was_ready = self.step_down(&mut state, response.term);
+                    drop(state);
+                    self.lost_leadership(was_ready);
+                    return None;
+                }
+                if state.role != Role::Leader || state.term != request.term {
+                    return None;
+                }
+                let current = state.match_index.entry(peer.id.clone()).or_insert(0);
+                *current = (*current).max(request.last_index);
+                state.next_index.insert(peer.id.clone(), request.last_index + 1);
+                Some(true)
+            }
+        }
+    }
+
+    async fn call<Req: Serialize, Resp: DeserializeOwned>(&self, peer: &Peer, rpc: &str, request: &Req) -> Result<Resp> {
+        let url = format!("{}/cluster/{}", peer.url.trim_end_matches('/'), rpc);
+        let mut post = self.http.post(&url).json(request);
+        if let Some(secret) = &self.config.secret {
+            post = post.bearer_auth(secret);
+        }
+        let response = post.send().await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ChronoError::NetworkError(format!("{}: {}", url, e)))?;
+        response.json().await
+            .map_err(|e| ChronoError::NetworkError(format!("{}: {}", url, e)))
+    }
+
+    async fn apply_loop(self: Arc<Self>) {
+        loop {
+            self.apply_committed();
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = self.apply.notified() => {}
+            }
+        }
+    }
+
+    /// Applies committed entries in order, then compacts the log if it has grown long.
+    fn apply_committed(&self) {
+        let _apply = self.apply_lock.lock().unwrap();
+        let scheduler = self.scheduler();
+        loop {
+            let (entry, local, won) = {
+                let mut state = self.state.lock().unwrap();
+                if state.last_applied >= state.commit_index {
+                    break;
+                }
+                state.last_applied += 1;
+                let entry = state.entry(state.last_applied).clone();
+                let won = state.machine.apply(&entry.command);
+                // A node already holds its own executions, and a leader's scheduler
+                // holds the task changes it made this term.
+                let local = entry.origin == self.config.node_id
+                    && (matches!(entry.command, Command::Execution(_))
+                        || (state.role == Role::Leader && entry.term == state.term));
+                (entry, local, won)
+            };
+            match entry.command {
+                Command::Noop => {}
+                Command::PutTask(task) if !local => {
+                    if let Some(scheduler) = &scheduler {
+                        scheduler.apply_task(task);
+                    }
+                }
+                Command::RemoveTask(id) if !local => {
+                    if let Some(scheduler) = &scheduler {
+                        scheduler.apply_task_removal(&id);
+                    }
+                }
+                Command::Execution(execution) if !local => {
+                    if let Err(e) = self.inner.save_execution(&execution) {
+                        error!("Failed to persist execution {}: {}", execution.id, e);
+                    }
+                    if let Some(scheduler) = &scheduler {
+                        scheduler.apply_execution(execution);
+                    }
+                }
+                Command::Members(members) => {
+                    self.rebuild_ring(&members);
+                    self.queued.notify_waiters();
+                }
+                Command::Enqueue(_) => self.queued.notify_waiters(),
+                Command::Claim { .. } => {
+                    if let Some((job, node)) = won {
+                        if node == self.config.node_id {
+                            let _ = self.claimed.send(job);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.role == Role::Leader && !state.ready && state.last_applied >= state.noop_index {
+            // Everything earlier leaders committed is applied; take over from here.
+            let tasks: Vec<Task> = state.machine.tasks.values().cloned().collect();
+            state.ready = true;
+            drop(state);
+            if let Some(scheduler) = &scheduler {
+                scheduler.replace_tasks(tasks);
+            }
+            self.leadership.send_replace(true);
+            state = self.state.lock().unwrap();
+        }
+
+        if (state.last_applied - state.snapshot_index) as usize > self.config.max_log_entries {
+            let compact_to = state.last_applied;
+            let term = state.term_at(compact_to).unwrap_or(0);
+            let record = RaftRecord::Snapshot { index: compact_to, term, state: state.machine.clone() };
+            match self.save(record) {
+                Ok(()) => {
+                    let drop_count = (compact_to - state.snapshot_index) as usize;
+                    state.log.drain(..drop_count);
+                    state.snapshot_index = compact_to;
+                    state.snapshot_term = term;
+                    state.snapshot = state.machine.clone();
+                }
+                Err(e) => error!("Failed to save a snapshot of the log: {}", e),
+            }
+        }
+        drop(state);
+        self.applied.notify_waiters();
+    }
+
+    fn rebuild_ring(&self, members: &[String]) {
+        let vnodes = self.config.workers.as_ref().map_or(DEFAULT_VNODES, |w| w.vnodes);
+        *self.ring.lock().unwrap() = HashRing::new(members, vnodes);
+    }
+
+    /// Sends commands from `ClusterStorage` on a follower to the leader, one at a time
+    /// so that updates to an execution arrive in order.
+    async fn forward_loop(self: Arc<Self>, mut commands: mpsc::UnboundedReceiver<Command>) {
+        loop {
+            let command = tokio::select! {
+                _ = self.stop.cancelled() => return,
+                command = commands.recv() => match command {
+                    Some(command) => command,
+                    None => return,
+                },
+            };
+            if let Err(e) = self.submit(command).await {
+                error!("Failed to send update to the cluster leader: {}", e);
+            }
+        }
+    }
+
+    /// Claims jobs while fewer than `concurrency` of the runs it started are still
+    /// going, and starts the ones it wins.
+    async fn worker_loop(self: Arc<Self>, workers: WorkerConfig, mut claimed: mpsc::UnboundedReceiver<Job>) {
+        let mut running: Vec<Uuid> = Vec::new();
+        loop {
+            if self.stop.is_cancelled() {
+                return;
+            }
+            let queued = self.queued.notified();
+            tokio::pin!(queued);
+            queued.as_mut().enable();
+
+            let Some(scheduler) = self.scheduler() else { return };
+            while let Ok(job) = claimed.try_recv() {
+                match scheduler.run_job(&job) {
+                    Ok(execution_id) => running.push(execution_id),
+                    Err(e) => error!("Failed to start job {}: {}", job.id, e),
+                }
+            }
+            running.retain(|id| {
+                scheduler.get_execution(id)
+                    .is_some_and(|e| matches!(e.status, ExecutionStatus::Queued | ExecutionStatus::Running))
+            });
+            drop(scheduler);
+
+            if running.len() < workers.concurrency {
+                if let Some(job) = self.next_job(&workers) {
+                    let claim = Command::Claim { job_id: job.id, node: self.config.node_id.clone() };
+                    match self.propose(claim).await {
+                        Ok(()) => continue,
+                        // Most likely mid-election; try again shortly.
+                        Err(_) => tokio::time::sleep(self.config.heartbeat_interval).await,
+                    }
+                }
+            }
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = &mut queued => {}
+                _ = tokio::time::sleep(self.config.heartbeat_interval * 2) => {}
+            }
+        }
+    }
+
+    /// The oldest queued job of a task this node owns, or else the oldest job that
+    /// has waited long enough to be stolen.
+    fn next_job(&self, workers: &WorkerConfig) -> Option<Job> {
+        let state = self.state.lock().unwrap();
+        let ring = self.ring.lock().unwrap();
+        let steal_after = chrono::Duration::from_std(workers.steal_after).unwrap_or_default();
+        let now = Utc::now();
+        let oldest = |jobs: &mut dyn Iterator<Item = &Job>| jobs.min_by_key(|j| (j.enqueued_at, j.id)).cloned();
+        oldest(&mut state.machine.jobs.values().filter(|j| ring.owner(&j.task_id) == Some(self.config.node_id.as_str())))
+            .or_else(|| oldest(&mut state.machine.jobs.values().filter(|j| now - j.enqueued_at >= steal_after)))
+    }
+
+    fn handle_vote(&self, request: VoteRequest) -> Result<VoteResponse> {
+        let mut state = self.state.lock().unwrap();
+        let before = (state.term, state.voted_for.clone());
+        let mut was_ready = false;
+        if request.term > state.term {
+            was_ready = state.become_follower(request.term);
+        }
+        let up_to_date = (request.last_log_term, request.last_log_index) >= (state.last_term(), state.last_index());
+        let granted = request.term == state.term
+            && up_to_date
+            && state.voted_for.as_ref().is_none_or(|id| *id == request.candidate_id);
+        if granted {
+            state.voted_for = Some(request.candidate_id);
+            state.election_deadline = Instant::now() + random_timeout(&self.config);
+        }
+        // Forgetting a vote across a restart could elect two leaders in one term.
+        let saved = if (state.term, &state.voted_for) != (before.0, &before.1) {
+            self.save_vote(&state)
+        } else {
+            Ok(())
+        };
+        let response = VoteResponse { term: state.term, granted };
+        drop(state);
+        self.lost_leadership(was_ready);
+        saved.map(|()| response)
+    }
+
+    fn handle_append(&self, request: AppendRequest) -> Result<AppendResponse> {
+        let mut state = self.state.lock().unwrap();
+        if request.term < state.term {
+            return Ok(AppendResponse { term: state.term, success: false, last_log_index: state.last_index() });
+        }
+        let term = state.term;
+        let was_ready = state.become_follower(request.term);
+        state.leader_id = Some(request.leader_id.clone());
+        state.election_deadline = Instant::now() + random_timeout(&self.config);
+
+        let saved = if state.term != term { self.save_vote(&state) } else { Ok(()) };
+        let response = saved.and_then(|()| self.accept_entries(&mut state, request));
+        drop(state);
+        self.lost_leadership(was_ready);
+        response
+    }
+
+    /// Adds the leader's entries if they follow on from this node's log. They are
+    /// saved before the leader is told this node holds them.
+    fn accept_entries(&self, state: &mut RaftState, request: AppendRequest) -> Result<AppendResponse> {
+        if request.prev_log_index > state.last_index() {
+            return Ok(AppendResponse { term: state.term, success: false, last_log_index: state.last_index() });
+        }
+        if state.term_at(request.prev_log_index).is_some_and(|t| t != request.prev_log_term) {
+            // Everything from the conflicting entry on will be resent.
+            let hint = request.prev_log_index.saturating_sub(1);
+            return Ok(AppendResponse { term: state.term, success: false, last_log_index: hint });
+        }
+        let last = request.prev_log_index + request.entries.len() as u64;
+        // Entries already held are kept; from the first new or conflicting one on, the
+        // leader's replace the log.
+        let first_new = request.entries.iter().zip(request.prev_log_index + 1..)
+            .position(|(entry, index)| index > state.snapshot_index && state.term_at(index) != Some(entry.term));
+        if let Some(skip) = first_new {
+            let index = request.prev_log_index + 1 + skip as u64;
+            let entries = request.entries[skip..].to_vec();
+            self.save(RaftRecord::Entries { index, entries: entries.clone() })?;
+            state.log.truncate((index - state.snapshot_index - 1) as usize);
+            state.log.extend(entries);
+        }
+        if request.leader_commit > state.commit_index {
+            state.commit_index = state.commit_index.max(request.leader_commit.min(last));
+            self.apply.notify_one();
+        }
+        Ok(AppendResponse { term: state.term, success: true, last_log_index: state.last_index() })
+    }
+
+    fn handle_snapshot(&self, request: SnapshotRequest) -> Result<SnapshotResponse> {
+        let (was_ready, saved) = {
+            let mut state = self.state.lock().unwrap();
+            if request.term < state.term {
+                return Ok(SnapshotResponse { term: state.term });
+            }
+            let term = state.term;
+            let was_ready = state.become_follower(request.term);
+            state.leader_id = Some(request.leader_id.clone());
+            state.election_deadline = Instant::now() + random_timeout(&self.config);
+            (was_ready, if state.term != term { self.save_vote(&state) } else { Ok(()) })
+        };
+        self.lost_leadership(was_ready);
+        saved?;
+
+        let _apply = self.apply_lock.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        if request.term != state.term || request.last_index <= state.snapshot_index {
+            return Ok(SnapshotResponse { term: state.term });
+        }
+        self.save(RaftRecord::Snapshot {
+            index: request.last_index,
+            term: request.last_term,
+            state: request.state.clone(),
+        })?;
+        if state.term_at(request.last_index) == Some(request.last_term) {
+            let drop_count = (request.last_index - state.snapshot_index) as usize;
+            state.log.drain(..drop_count);
+        } else {
+            state.log.clear();
+        }
+        state.snapshot_index = request.last_index;
+        state.snapshot_term = request.last_term;
+        state.snapshot = request.state.clone();
+        state.commit_index = state.commit_index.max(request.last_index);
+        if state.last_applied < request.last_index {
+            state.last_applied = request.last_index;
+            state.machine = request.state;
+            let tasks: Vec<Task> = state.machine.tasks.values().cloned().collect();
+            let members = state.machine.members.clone();
+            drop(state);
+            if let Some(scheduler) = self.scheduler() {
+                scheduler.replace_tasks(tasks);
+            }
+            self.rebuild_ring(&members);
+            self.apply.notify_one();
+            self.queued.notify_waiters();
+            state = self.state.lock().unwrap();
+        }
+        Ok(SnapshotResponse { term: state.term })
+    }
+}
+
+#[async_trait]
+impl Coordinator for ClusterNode {
+    fn is_leader(&self) -> bool {
+        self.state.lock().unwrap().is_ready_leader()
+    }
+
+    async fn leadership_changed(&self) {
+        let mut leadership = self.leadership.subscribe();
+        let _ = leadership.changed().await;
+    }
+
+    async fn sync(&self) -> Result<()> {
+        let (target, term) = {
+            let state = self.state.lock().unwrap();
+            if !state.is_ready_leader() {
+                return Err(self.not_leader(&state));
+            }
+            (state.last_index(), state.term)
+        };
+        self.wait_committed(target, term).await
+    }
+
+    fn dispatch(&self, job: &Job) -> Result<bool> {
+        if self.config.workers.is_none() {
+            return Ok(false);
+        }
+        self.append(Command::Enqueue(job.clone()))?;
+        Ok(true)
+    }
+}
+
+fn random_timeout(config: &ClusterConfig) -> Duration {
+    let min = config.election_timeout_min.as_millis() as u64;
+    let max = (config.election_timeout_max.as_millis() as u64).max(min);
+    Duration::from_millis(rand::thread_rng().gen_range(min..=max))
+}
+
+/// Storage handed to a clustered scheduler; see [`ClusterNode::storage`].
+struct ClusterStorage {
+    node: Arc<ClusterNode>,
+    /// Index and term of the latest task change, for `flush` to wait on.
+    last_write: Mutex<Option<(u64, u64)>>,
+}
+
+impl ClusterStorage {
+    fn write(&self, command: Command) -> Result<()> {
+        let appended = self.node.append(command)?;
+        *self.last_write.lock().unwrap() = Some(appended);
+        Ok(())
+    }
+}
+
+impl Storage for ClusterStorage {
+    fn save_task(&self, task: &Task) -> Result<()> {
+        self.write(Command::PutTask(task.clone()))
+    }
+
+    fn delete_task(&self, id: &Uuid) -> Result<()> {
+        self.write(Command::RemoveTask(*id))
+    }
+
+    /// Waits for the latest task change to be committed.
+    fn flush(&self) -> Result<()> {
+        let last_write = *self.last_write.lock().unwrap();
+        match last_write {
+            Some((index, term)) => self.node.block_until_committed(index, term),
+            None => Ok(()),
+        }
+    }
+
+    fn save_execution(&self, execution: &TaskExecution) -> Result<()> {
+        self.node.inner.save_execution(execution)?;
+        // Other nodes only miss a copy if this fails; the run itself is unaffected.
+        let command = Command::Execution(execution.clone());
+        if self.node.is_leader() {
+            let _ = self.node.append(command);
+        } else {
+            let _ = self.node.forward.send(command);
+        }
+        Ok(())
+    }
+
+    /// Each node applies its own retention policy to its copy of the history.
+    fn delete_executions(&self, ids: &[Uuid]) -> Result<()> {
+        self.node.inner.delete_executions(ids)
+    }
+
+    fn save_workflow_run(&self, run: &WorkflowRun) -> Result<()> {
+        self.node.inner.save_workflow_run(run)
+    }
+
+    /// Tasks come from the cluster, not from the node's own storage: the node picks up
+    /// its saved Raft state here, and the scheduler starts from the tasks in its
+    /// snapshot. Later entries are applied once a leader commits them again.
+    fn load(&self) -> Result<Snapshot> {
+        let mut snapshot = self.node.inner.load()?;
+        let tasks = match snapshot.raft.take() {
+            Some(raft) => {
+                let tasks = raft.snapshot.tasks.values().cloned().collect();
+                self.node.restore(raft);
+                tasks
+            }
+            None => Vec::new(),
+        };
+        Ok(Snapshot { tasks, ..snapshot })
+    }
+}
+
+/// Turns away requests to the Raft routes that don't carry the cluster secret.
+async fn authorize(State(node): State<Arc<ClusterNode>>, request: Request, next: Next) -> Response {
+    match &node.config.secret {
+        Some(secret) if !has_bearer_token(request.headers(), secret) => {
+            (StatusCode::UNAUTHORIZED, "missing or wrong cluster secret").into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+fn unavailable() -> Response {
+    (StatusCode::SERVICE_UNAVAILABLE, "node has left the cluster").into_response()
+}
+
+/// Answers an RPC, or fails it if this node couldn't save what the answer vouches for.
+fn reply<T: Serialize>(response: Result<T>) -> Response {
+    match response {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn vote(State(node): State<Arc<ClusterNode>>, Json(request): Json<VoteRequest>) -> Response {
+    if node.stop.is_cancelled() {
+        return unavailable();
+    }
+    reply(node.handle_vote(request))
+}
+
+async fn append_entries(State(node): State<Arc<ClusterNode>>, Json(request): Json<AppendRequest>) -> Response {
+    if node.stop.is_cancelled() {
+        return unavailable();
+    }
+    reply(node.handle_append(request))
+}
+
+async fn install_snapshot(State(node): State<Arc<ClusterNode>>, Json(request): Json<SnapshotRequest>) -> Response {
+    if node.stop.is_cancelled() {
+        return unavailable();
+    }
+    reply(node.handle_snapshot(request))
+}
+
+async fn propose(State(node): State<Arc<ClusterNode>>, Json(request): Json<ProposeRequest>) -> Response {
+    if node.stop.is_cancelled() {
+        return unavailable();
+    }
+    let committed = match node.append_from(request.origin, request.command) {
+        Ok((index, term)) => node.wait_committed(index, term).await.map(|_| index),
+        Err(e) => Err(e),
+    };
+    match committed {
+        Ok(index) => Json(ProposeResponse { index }).into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    }
+}
+
+async fn status(State(node): State<Arc<ClusterNode>>) -> Json<ClusterStatus> {
+    Json(node.status())
+}
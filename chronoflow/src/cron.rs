@@ -0,0 +1,349 @@
+use crate::{ChronoError, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+// Upper bound for the day-by-day search; long enough to find e.g. a leap-day Monday.
+const MAX_SEARCH_DAYS: u32 = 366 * 28;
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+// Masks with every day of the month (1-31) and every day of the week (0-6) set.
+const ALL_DAYS_OF_MONTH: u64 = 0xffff_fffe;
+const ALL_DAYS_OF_WEEK: u64 = 0x7f;
+
+/// A parsed cron expression.
+///
+/// Accepts the classic 5-field form (`min hour dom month dow`) and a 6-field form with a
+/// leading seconds field, plus the `@yearly`, `@monthly`, `@weekly`, `@daily` and `@hourly`
+/// macros. Fields support lists, ranges, steps and month/day names; day-of-month also
+/// accepts `L`, `LW` and `15W`, day-of-week accepts `5L` (last Friday) and `1#2`
+/// (second Monday). When both day fields are restricted a day matches if either does; a
+/// field that covers its whole range, like `*`, `?`, `1-31` or `*/1`, is unrestricted.
+///
+/// Fields are matched against wall-clock time in the zone of the instant passed to
+/// [`CronExpr::next_after_in`]. A fire time that falls into a DST gap fires at the first
+/// instant after the gap, and a fire time that occurs twice fires only the first time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    source: String,
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+    last_day_of_month: bool,
+    last_weekday_of_month: bool,
+    nearest_weekdays: Vec<u32>,
+    last_days_of_week: u64,
+    nth_days_of_week: Vec<(u32, u32)>,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let source = expr.trim();
+        let expanded = match source.to_ascii_lowercase().as_str() {
+            "@yearly" | "@annually" => "0 0 0 1 1 *",
+            "@monthly" => "0 0 0 1 * *",
+            "@weekly" => "0 0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 0 * * *",
+            "@hourly" => "0 0 * * * *",
+            other if other.starts_with('@') => {
+                return Err(invalid(source, format!("unknown macro '{}'", other)));
+            }
+            _ => source,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let (sec, rest) = match fields.len() {
+            5 => ("0", &fields[..]),
+            6 => (fields[0], &fields[1..]),
+            n => {
+                return Err(invalid(source, format!("expected 5 or 6 fields, found {}", n)));
+            }
+        };
+
+        let mut cron = CronExpr {
+            source: source.to_string(),
+            seconds: parse_field(sec, 0, 59, None).map_err(|e| invalid(source, format!("seconds: {}", e)))?,
+            minutes: parse_field(rest[0], 0, 59, None).map_err(|e| invalid(source, format!("minutes: {}", e)))?,
+            hours: parse_field(rest[1], 0, 23, None).map_err(|e| invalid(source, format!("hours: {}", e)))?,
+            days_of_month: 0,
+            months: parse_field(rest[3], 1, 12, Some(&MONTH_NAMES))
+                .map_err(|e| invalid(source, format!("month: {}", e)))?,
+            days_of_week: 0,
+            dom_restricted: false,
+            dow_restricted: false,
+            last_day_of_month: false,
+            last_weekday_of_month: false,
+            nearest_weekdays: Vec::new(),
+            last_days_of_week: 0,
+            nth_days_of_week: Vec::new(),
+        };
+        cron.parse_days_of_month(rest[2])
+            .map_err(|e| invalid(source, format!("day of month: {}", e)))?;
+        cron.parse_days_of_week(rest[4])
+            .map_err(|e| invalid(source, format!("day of week: {}", e)))?;
+        cron.dom_restricted = cron.days_of_month != ALL_DAYS_OF_MONTH;
+        cron.dow_restricted = cron.days_of_week != ALL_DAYS_OF_WEEK;
+        Ok(cron)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the first fire time strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.next_after_in(&after)
+    }
+
+    /// Returns the first fire time strictly after `after`, evaluated in `after`'s zone.
+    pub fn next_after_in<Z: TimeZone>(&self, after: &DateTime<Z>) -> Option<DateTime<Z>> {
+        let tz = after.timezone();
+        let mut cursor = after.naive_local();
+        loop {
+            let candidate = self.next_naive_after(cursor)?;
+            let resolved = match tz.from_local_datetime(&candidate) {
+                LocalResult::Single(t) => t,
+                LocalResult::Ambiguous(earliest, _) => earliest,
+                LocalResult::None => end_of_gap(&tz, candidate)?,
+            };
+            // Wall-clock times repeated by a fallback resolve to their first occurrence,
+            // which may already be behind us.
+            if resolved > *after {
+                return Some(resolved);
+            }
+            cursor = candidate;
+        }
+    }
+
+    /// Returns the upcoming fire times after `after`, in order.
+    pub fn upcoming(&self, after: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        std::iter::successors(self.next_after(after), move |prev| self.next_after(*prev))
+    }
+
+    pub(crate) fn next_naive_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.with_nanosecond(0)? + Duration::seconds(1);
+        let mut date = start.date();
+        let mut floor = Some(start.time());
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if !has(self.months, date.month()) {
+                date = first_of_next_month(date)?;
+                floor = None;
+                continue;
+            }
+            if self.matches_day(date) {
+                if let Some(time) = self.first_time_from(floor.unwrap_or(NaiveTime::MIN)) {
+                    return Some(date.and_time(time));
+                }
+            }
+            date = date.succ_opt()?;
+            floor = None;
+        }
+        None
+    }
+
+    fn first_time_from(&self, from: NaiveTime) -> Option<NaiveTime> {
+        for hour in from.hour()..24 {
+            if !has(self.hours, hour) {
+                continue;
+            }
+            let same_hour = hour == from.hour();
+            let minute_start = if same_hour { from.minute() } else { 0 };
+            for minute in minute_start..60 {
+                if !has(self.minutes, minute) {
+                    continue;
+                }
+                let second_start = if same_hour && minute == from.minute() { from.second() } else { 0 };
+                if let Some(second) = (second_start..60).find(|s| has(self.seconds, *s)) {
+                    return NaiveTime::from_hms_opt(hour, minute, second);
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => self.dom_matches(date) || self.dow_matches(date),
+            (true, false) => self.dom_matches(date),
+            (false, true) => self.dow_matches(date),
+            (false, false) => true,
+        }
+    }
+
+    fn dom_matches(&self, date: NaiveDate) -> bool {
+        let day = date.day();
+        let last = days_in_month(date.year(), date.month());
+        has(self.days_of_month, day)
+            || (self.last_day_of_month && day == last)
+            || (self.last_weekday_of_month && nearest_weekday(date.year(), date.month(), last) == Some(day))
+            || self
+                .nearest_weekdays
+                .iter()
+                .any(|target| nearest_weekday(date.year(), date.month(), *target) == Some(day))
+    }
+
+    fn dow_matches(&self, date: NaiveDate) -> bool {
+        let dow = date.weekday().num_days_from_sunday();
+        let is_last = date.day() + 7 > days_in_month(date.year(), date.month());
+        let nth = (date.day() - 1) / 7 + 1;
+        has(self.days_of_week, dow)
+            || (is_last && has(self.last_days_of_week, dow))
+            || self.nth_days_of_week.iter().any(|(d, n)| *d == dow && *n == nth)
+    }
+
+    fn parse_days_of_month(&mut self, field: &str) -> std::result::Result<(), String> {
+        for item in field.split(',') {
+            let upper = item.to_ascii_uppercase();
+            if upper == "L" {
+                self.last_day_of_month = true;
+            } else if upper == "LW" {
+                self.last_weekday_of_month = true;
+            } else if let Some(day) = upper.strip_suffix('W') {
+                let day = parse_number(day, 1, 31, None)?;
+                self.nearest_weekdays.push(day);
+            } else {
+                self.days_of_month |= parse_item(item, 1, 31, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_days_of_week(&mut self, field: &str) -> std::result::Result<(), String> {
+        for item in field.split(',') {
+            let upper = item.to_ascii_uppercase();
+            if let Some(day) = upper.strip_suffix('L').filter(|d| !d.is_empty()) {
+                let day = parse_number(day, 0, 7, Some(&DAY_NAMES))? % 7;
+                self.last_days_of_week |= 1 << day;
+            } else if let Some((day, nth)) = upper.split_once('#') {
+                let day = parse_number(day, 0, 7, Some(&DAY_NAMES))? % 7;
+                let nth = parse_number(nth, 1, 5, None)?;
+                self.nth_days_of_week.push((day, nth));
+            } else {
+                let mask = parse_item(item, 0, 7, Some(&DAY_NAMES))?;
+                // 7 is an alias for Sunday.
+                self.days_of_week |= (mask & 0x7f) | ((mask >> 7) & 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = ChronoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn invalid(expr: &str, reason: String) -> ChronoError {
+    ChronoError::InvalidCron(format!("'{}': {}", expr, reason))
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: Option<&[&str]>) -> std::result::Result<u64, String> {
+    field
+        .split(',')
+        .try_fold(0u64, |mask, item| Ok(mask | parse_item(item, min, max, names)?))
+}
+
+fn parse_item(item: &str, min: u32, max: u32, names: Option<&[&str]>) -> std::result::Result<u64, String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => {
+            let step: u32 = step.parse().map_err(|_| format!("invalid step '{}'", step))?;
+            if step == 0 {
+                return Err("step must be greater than zero".to_string());
+            }
+            (range, Some(step))
+        }
+        None => (item, None),
+    };
+
+    let (start, end) = if range == "*" || range == "?" {
+        (min, max)
+    } else if let Some((a, b)) = range.split_once('-') {
+        (parse_number(a, min, max, names)?, parse_number(b, min, max, names)?)
+    } else {
+        let start = parse_number(range, min, max, names)?;
+        // "5/15" means "every 15 starting at 5".
+        (start, if step.is_some() { max } else { start })
+    };
+    if start > end {
+        return Err(format!("range '{}' is reversed", range));
+    }
+
+    let step = step.unwrap_or(1) as usize;
+    Ok((start..=end).step_by(step).fold(0, |mask, v| mask | (1 << v)))
+}
+
+fn parse_number(value: &str, min: u32, max: u32, names: Option<&[&str]>) -> std::result::Result<u32, String> {
+    let upper = value.to_ascii_uppercase();
+    let parsed = match names.and_then(|names| names.iter().position(|n| *n == upper)) {
+        // Month names are 1-based, day names 0-based, matching each field's minimum.
+        Some(index) => index as u32 + min,
+        None => value.parse().map_err(|_| format!("invalid value '{}'", value))?,
+    };
+    if parsed < min || parsed > max {
+        return Err(format!("value {} out of range {}-{}", parsed, min, max));
+    }
+    Ok(parsed)
+}
+
+/// The first instant after the DST gap containing `local`.
+fn end_of_gap<Z: TimeZone>(tz: &Z, local: NaiveDateTime) -> Option<DateTime<Z>> {
+    let mut probe = local.with_second(0)?.with_nanosecond(0)?;
+    for _ in 0..24 * 60 {
+        probe += Duration::minutes(1);
+        if let Some(t) = tz.from_local_datetime(&probe).earliest() {
+            return Some(t);
+        }
+    }
+    None
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDate> {
+    let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let first = NaiveDate::from_ymd_opt(year, month, 1).expect("valid month");
+    first_of_next_month(first)
+        .map(|next| next.signed_duration_since(first).num_days() as u32)
+        .unwrap_or(31)
+}
+
+/// The weekday closest to `target` without leaving the month, as used by `W`.
+fn nearest_weekday(year: i32, month: u32, target: u32) -> Option<u32> {
+    let last = days_in_month(year, month);
+    if target > last {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year, month, target)?;
+    Some(match date.weekday().num_days_from_sunday() {
+        6 if target == 1 => target + 2,
+        6 => target - 1,
+        0 if target == last => target - 2,
+        0 => target + 1,
+        _ => target,
+    })
+}
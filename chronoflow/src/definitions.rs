@@ -0,0 +1,538 @@
+//! Task definitions kept in a directory of YAML (`.yaml`, `.yml`) or TOML (`.toml`)
+//! files, so schedules can live in version control.
+//!
+//! Each file holds a `tasks` list:
+//!
+//! ```yaml
+//! tasks:
+//!   - name: fetch-prices
+//!     cron: "*/15 * * * *"
+//!     timezone: Europe/Berlin
+//!     plugin: http_request
+//!     config:
+//!       url: https://example.com/prices
+//!   - name: report
+//!     plugin: shell
+//!     config:
+//!       argv: [./report.sh, "{{ upstream.fetch-prices.output.body }}"]
+//!     depends_on: [fetch-prices]
+//!     notifications:
+//!       - on: Failure
+//!         notifier: smtp
+//!         config: { from: chronoflow@example.com, to: [ops@example.com] }
+//!       - on: { SlaMiss: { seconds: 600 } }
+//!         notifier: file
+//!         config: { path: /var/log/chronoflow-alerts.jsonl }
+//! ```
+//!
+//! A task has at most one trigger: `cron`, `every` (seconds), `at` (RFC 3339), `webhook`
+//! (a path under `/hooks/`), `watch` (a directory, optionally narrowed by `glob`) or
+//! `topic`. With none it only runs when triggered or by its dependencies. `depends_on`
+//! entries are task names, or `{task, condition}` to trigger on something other than
+//! success. Other keys mirror [`TaskSpec`](crate::TaskSpec).
+//!
+//! Task ids are derived from names, so a task keeps its id (and its execution history)
+//! across restarts and edits. Names must be unique across the directory.
+
+use crate::{
+    ChronoError, Dependency, MisfirePolicy, NotificationRule, OverlapPolicy, PluginConfig, Result, RetryPolicy, Schedule,
+    Scheduler, Task, TriggerCondition,
+};
+use chrono::{DateTime, Utc};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+use tracing::{error, info};
+
+/// Namespace for the name-derived ids of file-defined tasks.
+const DEFINITION_NAMESPACE: Uuid = Uuid::from_u128(0x5d1f_0c3e_8a4b_4f6e_9c2d_7b1a_3e5f_8d90);
+
+/// How long to wait for a burst of file events to settle before reloading.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[derive(Deserialize)]
+struct DefinitionFile {
+    #[serde(default)]
+    tasks: Vec<TaskDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskDefinition {
+    name: String,
+    #[serde(default)]
+    cron: Option<String>,
+    #[serde(default)]
+    every: Option<u64>,
+    #[serde(default)]
+    at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    webhook: Option<String>,
+    #[serde(default)]
+    watch: Option<String>,
+    #[serde(default)]
+    glob: Option<String>,
+    #[serde(default)]
+    topic: Option<String>,
+    plugin: String,
+    #[serde(default)]
+    wasm: Option<String>,
+    #[serde(default = "empty_config")]
+    config: JsonValue,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    misfire_policy: MisfirePolicy,
+    #[serde(default)]
+    overlap_policy: OverlapPolicy,
+    #[serde(default)]
+    retry: Option<RetryPolicy>,
+    #[serde(default)]
+    timeout_seconds: Option<u64>,
+    #[serde(default)]
+    depends_on: Vec<DependencyDefinition>,
+    #[serde(default)]
+    notifications: Vec<NotificationRule>,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencyDefinition {
+    OnSuccess(String),
+    Full {
+        task: String,
+        #[serde(default)]
+        condition: TriggerCondition,
+    },
+}
+
+fn empty_config() -> JsonValue {
+    JsonValue::Object(Default::default())
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// A task built from a definition file, with where it was defined.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub task: Task,
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+impl Definition {
+    fn error(&self, reason: impl std::fmt::Display) -> ChronoError {
+        ChronoError::InvalidDefinition(format!(
+            "{}:{}: task '{}': {}", self.file.display(), self.line, self.task.name, reason
+        ))
+    }
+}
+
+/// What [`sync`] changed, by task name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The id a definition named `name` is registered under.
+pub fn definition_id(name: &str) -> Uuid {
+    Uuid::new_v5(&DEFINITION_NAMESPACE, name.as_bytes())
+}
+
+/// Parses every definition file in `dir`, in file name order. All problems found are
+/// reported together, one `file:line: reason` per line.
+pub fn load_dir(dir: &Path) -> Result<Vec<Definition>> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| ChronoError::InvalidDefinition(format!("{}: {}", dir.display(), e)))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_definition_file(path))
+        .collect();
+    files.sort();
+
+    let mut definitions = Vec::new();
+    let mut errors = Vec::new();
+    for file in &files {
+        match load_file(file) {
+            Ok(loaded) => definitions.extend(loaded),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut seen: HashMap<String, &Definition> = HashMap::new();
+    for definition in &definitions {
+        if let Some(first) = seen.insert(definition.task.name.clone(), definition) {
+            errors.push(definition.error(format!(
+                "already defined at {}:{}", first.file.display(), first.line
+            )));
+        }
+    }
+    for definition in &definitions {
+        for dep in &definition.task.depends_on {
+            if !definitions.iter().any(|d| d.task.id == dep.task_id) {
+                errors.push(definition.error(format!("depends on an undefined task {}", dep.task_id)));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(definitions)
+    } else {
+        let messages: Vec<String> = errors.into_iter()
+            .map(|e| match e {
+                ChronoError::InvalidDefinition(message) => message,
+                other => other.to_string(),
+            })
+            .collect();
+        Err(ChronoError::InvalidDefinition(messages.join("\n")))
+    }
+}
+
+fn is_definition_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml" | "toml"))
+}
+
+fn load_file(path: &Path) -> Result<Vec<Definition>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| ChronoError::InvalidDefinition(format!("{}: {}", path.display(), e)))?;
+    let at_line = |line: usize, reason: &dyn std::fmt::Display| {
+        ChronoError::InvalidDefinition(format!("{}:{}: {}", path.display(), line, reason))
+    };
+
+    let file: DefinitionFile = if path.extension().is_some_and(|e| e == "toml") {
+        toml::from_str(&text).map_err(|e| {
+            let line = e.span().map_or(1, |span| line_at(&text, span.start));
+            at_line(line, &e.message())
+        })?
+    } else if text.trim().is_empty() {
+        DefinitionFile { tasks: Vec::new() }
+    } else {
+        serde_yaml::from_str(&text).map_err(|e| {
+            let line = e.location().map_or(1, |l| l.line());
+            at_line(line, &e)
+        })?
+    };
+
+    file.tasks.into_iter()
+        .map(|definition| {
+            let line = line_of_name(&text, &definition.name).unwrap_or(1);
+            let name = definition.name.clone();
+            let task = build_task(definition, path)
+                .map_err(|e| at_line(line, &format!("task '{}': {}", name, e)))?;
+            Ok(Definition { task, file: path.to_path_buf(), line })
+        })
+        .collect()
+}
+
+fn build_task(definition: TaskDefinition, path: &Path) -> Result<Task> {
+    if definition.glob.is_some() && definition.watch.is_none() {
+        return Err(ChronoError::InvalidTask("glob requires watch".into()));
+    }
+    let mut triggers = Vec::new();
+    triggers.extend(definition.cron.map(Schedule::Cron));
+    triggers.extend(definition.every.map(|seconds| Schedule::Interval { seconds }));
+    triggers.extend(definition.at.map(|at| Schedule::Once { at }));
+    triggers.extend(definition.webhook.map(|path| Schedule::Webhook { path }));
+    triggers.extend(definition.watch.map(|dir| Schedule::FileWatch { dir, glob: definition.glob }));
+    triggers.extend(definition.topic.map(|name| Schedule::Topic { name }));
+    if triggers.len() > 1 {
+        return Err(ChronoError::InvalidTask(
+            "only one of cron, every, at, webhook, watch and topic may be set".into(),
+        ));
+    }
+    let schedule = triggers.pop().unwrap_or(Schedule::Manual);
+    let plugin = PluginConfig {
+        name: definition.plugin,
+        wasm_path: definition.wasm.unwrap_or_default(),
+        config: definition.config,
+    };
+
+    let mut task = Task::new(definition.name, schedule, plugin)?
+        .with_misfire_policy(definition.misfire_policy)
+        .with_overlap_policy(definition.overlap_policy);
+    task.id = definition_id(&task.name);
+    if let Some(timezone) = &definition.timezone {
+        task = task.with_timezone(timezone)?;
+    }
+    if let Some(retry) = definition.retry {
+        task = task.with_retry(retry)?;
+    }
+    task.timeout_seconds = definition.timeout_seconds;
+    task.depends_on = definition.depends_on.into_iter()
+        .map(|dep| match dep {
+            DependencyDefinition::OnSuccess(name) => Dependency {
+                task_id: definition_id(&name),
+                condition: TriggerCondition::OnSuccess,
+            },
+            DependencyDefinition::Full { task, condition } => Dependency { task_id: definition_id(&task), condition },
+        })
+        .collect();
+    task.notifications = definition.notifications;
+    task.enabled = definition.enabled;
+    task.source = Some(path.display().to_string());
+    task.validate()?;
+    Ok(task)
+}
+
+/// 1-based line containing byte `offset`.
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// 1-based line of the `name` key (YAML) or assignment (TOML) holding `name`.
+fn line_of_name(text: &str, name: &str) -> Option<usize> {
+    text.lines().position(|line| {
+        let line = line.trim_start().trim_start_matches('-').trim_start();
+        line.strip_prefix("name")
+            .map(|rest| rest.trim_start())
+            .and_then(|rest| rest.strip_prefix(':').or_else(|| rest.strip_prefix('=')))
+            .is_some_and(|value| value.trim().trim_matches(|c| c == '"' || c == '\'') == name)
+    }).map(|index| index + 1)
+}
+
+/// Makes the scheduler's file-defined tasks match `definitions`: new ones are added,
+/// changed ones updated and ones no longer defined removed. Tasks created through the
+/// API are left alone, and executions already running carry on with the settings they
+/// started with.
+///
+/// Every definition is validated, and every removal checked, before anything changes,
+/// so a bad file leaves the registered tasks as they were. Should a change still fail
+/// to apply, e.g. because storage does, the changes made before it are undone.
+pub fn sync(scheduler: &Scheduler, definitions: &[Definition]) -> Result<SyncReport> {
+    // Upstream tasks first, so dependencies are registered before their dependents.
+    let ordered = dependency_order(definitions)?;
+    for definition in definitions {
+        scheduler.validate_task(&definition.task).map_err(|e| definition.error(e))?;
+    }
+
+    let tasks = scheduler.list_tasks();
+    let registered: HashMap<Uuid, &Task> = tasks.iter()
+        .filter(|t| t.source.is_some())
+        .map(|t| (t.id, t))
+        .collect();
+    let defined: HashMap<Uuid, &Definition> = definitions.iter().map(|d| (d.task.id, d)).collect();
+    let stale: Vec<&Task> = registered.values().copied().filter(|t| !defined.contains_key(&t.id)).collect();
+    check_removals(&tasks, &defined, &stale)?;
+
+    let mut applied = Vec::new();
+    let result = apply(scheduler, &ordered, &registered, stale, &mut applied);
+    if result.is_err() {
+        undo(scheduler, applied);
+    }
+    result
+}
+
+/// A change [`sync`] made, with what it takes to undo it.
+enum Applied {
+    Added(Uuid),
+    Updated(Task),
+    Removed(Task),
+}
+
+/// Fails if a task that stays registered depends on one about to be removed.
+fn check_removals(tasks: &[Task], defined: &HashMap<Uuid, &Definition>, stale: &[&Task]) -> Result<()> {
+    let stale_ids: HashSet<Uuid> = stale.iter().map(|t| t.id).collect();
+    let mut blocked = Vec::new();
+    for task in tasks.iter().filter(|t| !stale_ids.contains(&t.id)) {
+        let depends_on = defined.get(&task.id).map_or(&task.depends_on, |d| &d.task.depends_on);
+        for dep in depends_on.iter().filter(|dep| stale_ids.contains(&dep.task_id)) {
+            let upstream = stale.iter().find(|t| t.id == dep.task_id).map_or("", |t| t.name.as_str());
+            blocked.push(format!("'{}' (a dependency of '{}')", upstream, task.name));
+        }
+    }
+    if blocked.is_empty() {
+        Ok(())
+    } else {
+        Err(ChronoError::InvalidDefinition(format!(
+            "can't remove {} while other tasks depend on them", blocked.join(", ")
+        )))
+    }
+}
+
+fn apply(
+    scheduler: &Scheduler,
+    ordered: &[&Definition],
+    registered: &HashMap<Uuid, &Task>,
+    mut stale: Vec<&Task>,
+    applied: &mut Vec<Applied>,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    for definition in ordered {
+        let task = &definition.task;
+        match registered.get(&task.id) {
+            None => {
+                scheduler.add_task(task.clone()).map_err(|e| definition.error(e))?;
+                applied.push(Applied::Added(task.id));
+                report.added.push(task.name.clone());
+            }
+            Some(current) if !same_definition(current, task) => {
+                scheduler.update_task(task.clone()).map_err(|e| definition.error(e))?;
+                applied.push(Applied::Updated((*current).clone()));
+                report.updated.push(task.name.clone());
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Dependents first, since a task can't be removed while others depend on it.
+    while !stale.is_empty() {
+        let before = stale.len();
+        let mut failed = None;
+        stale.retain(|task| match scheduler.remove_task(&task.id) {
+            Ok(()) => {
+                applied.push(Applied::Removed((*task).clone()));
+                report.removed.push(task.name.clone());
+                false
+            }
+            Err(e) => {
+                failed = Some(e);
+                true
+            }
+        });
+        if let Some(e) = failed.filter(|_| stale.len() == before) {
+            return Err(e);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Reverts `applied`, newest first. Failures are logged; there is nothing better to do
+/// with them while already handling one.
+fn undo(scheduler: &Scheduler, applied: Vec<Applied>) {
+    for change in applied.into_iter().rev() {
+        let result = match change {
+            Applied::Added(id) => scheduler.remove_task(&id),
+            Applied::Updated(previous) => scheduler.update_task(previous).map(|_| ()),
+            Applied::Removed(previous) => scheduler.add_task(previous).map(|_| ()),
+        };
+        if let Err(e) = result {
+            error!("Failed to undo a partial task definition sync: {}", e);
+        }
+    }
+}
+
+fn same_definition(current: &Task, new: &Task) -> bool {
+    current.name == new.name
+        && current.schedule == new.schedule
+        && current.plugin == new.plugin
+        && current.timezone == new.timezone
+        && current.misfire_policy == new.misfire_policy
+        && current.overlap_policy == new.overlap_policy
+        && current.retry == new.retry
+        && current.timeout_seconds == new.timeout_seconds
+        && current.depends_on == new.depends_on
+        && current.notifications == new.notifications
+        && current.source == new.source
+        && current.enabled == new.enabled
+}
+
+/// Orders definitions so each comes after the definitions it depends on.
+fn dependency_order(definitions: &[Definition]) -> Result<Vec<&Definition>> {
+    let by_id: HashMap<Uuid, &Definition> = definitions.iter().map(|d| (d.task.id, d)).collect();
+    let mut ordered = Vec::with_capacity(definitions.len());
+    let mut done = HashSet::new();
+    let mut visiting = HashSet::new();
+
+    fn visit<'d>(
+        definition: &'d Definition,
+        by_id: &HashMap<Uuid, &'d Definition>,
+        done: &mut HashSet<Uuid>,
+        visiting: &mut HashSet<Uuid>,
+        ordered: &mut Vec<&'d Definition>,
+    ) -> Result<()> {
+        if done.contains(&definition.task.id) {
+            return Ok(());
+        }
+        if !visiting.insert(definition.task.id) {
+            return Err(definition.error("dependency cycle"));
+        }
+        for dep in &definition.task.depends_on {
+            if let Some(upstream) = by_id.get(&dep.task_id) {
+                visit(upstream, by_id, done, visiting, ordered)?;
+            }
+        }
+        visiting.remove(&definition.task.id);
+        done.insert(definition.task.id);
+        ordered.push(definition);
+        Ok(())
+    }
+
+    for definition in definitions {
+        visit(definition, &by_id, &mut done, &mut visiting, &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+/// Loads `dir` and syncs the scheduler with it.
+pub fn load_and_sync(scheduler: &Scheduler, dir: &Path) -> Result<SyncReport> {
+    sync(scheduler, &load_dir(dir)?)
+}
+
+/// Keeps the scheduler in sync with `dir` until dropped.
+pub struct DefinitionWatcher {
+    _watcher: RecommendedWatcher,
+    reloader: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for DefinitionWatcher {
+    fn drop(&mut self) {
+        self.reloader.abort();
+    }
+}
+
+/// Watches `dir` and re-syncs the scheduler whenever a definition file changes. A
+/// reload that fails is reported and leaves the registered tasks untouched.
+pub fn watch(scheduler: Arc<Scheduler>, dir: PathBuf) -> Result<DefinitionWatcher> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event.paths.iter().any(|p| is_definition_file(p)) {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .map_err(|e| ChronoError::InvalidDefinition(format!("{}: {}", dir.display(), e)))?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| ChronoError::InvalidDefinition(format!("{}: {}", dir.display(), e)))?;
+
+    let reloader = tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            // Editors often write a file in several steps; wait for them to finish.
+            tokio::time::sleep(RELOAD_DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+            // In a cluster the leader applies definitions for every node.
+            if !scheduler.is_leader() {
+                continue;
+            }
+
+            match load_and_sync(&scheduler, &dir) {
+                Ok(report) if report.is_empty() => {}
+                Ok(report) => info!(
+                    "Reloaded task definitions: {} added, {} updated, {} removed",
+                    report.added.len(), report.updated.len(), report.removed.len()
+                ),
+                Err(e) => error!("Failed to reload task definitions: {}", e),
+            }
+        }
+    });
+
+    Ok(DefinitionWatcher { _watcher: watcher, reloader })
+}
@@ -1,3 +1,4 @@
+use crate::PluginOutput;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,18 +6,55 @@ pub enum ChronoError {
     #[error("Task not found: {0}")]
     TaskNotFound(String),
     
+    #[error("Invalid task: {0}")]
+    InvalidTask(String),
+    
     #[error("Invalid cron expression: {0}")]
     InvalidCron(String),
     
+    #[error("Invalid timezone: {0}")]
+    InvalidTimezone(String),
+    
     #[error("Plugin error: {0}")]
     PluginError(String),
     
+    /// A plugin failure that still produced output worth keeping, e.g. a process's
+    /// stderr and exit code.
+    #[error("Plugin error: {0}")]
+    PluginFailed(String, Box<PluginOutput>),
+    
+    /// A task definition file that failed to parse or validate, as `file:line: reason`.
+    #[error("Invalid task definition: {0}")]
+    InvalidDefinition(String),
+    
+    /// A plugin config placeholder that could not be rendered.
+    #[error("Template error: {0}")]
+    TemplateError(String),
+    
+    #[error("Execution timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    
+    /// A run stopped by the scheduler before it finished.
+    #[error("Execution cancelled: {0}")]
+    Cancelled(String),
+    
+    /// A run cut off by the scheduler shutting down.
+    #[error("Execution interrupted: {0}")]
+    Interrupted(String),
+    
+    #[error("Scheduler is shutting down")]
+    ShuttingDown,
+    
     #[error("Storage error: {0}")]
     StorageError(String),
     
     #[error("Network error: {0}")]
     NetworkError(String),
     
+    /// An error response from the management API.
+    #[error("Server returned {0}: {1}")]
+    ApiError(u16, String),
+    
     #[error("Consensus error: {0}")]
     ConsensusError(String),
 }
@@ -0,0 +1,171 @@
+//! Event triggers: tasks that run when something happens rather than at a time.
+//!
+//! - [`Schedule::Webhook`] tasks run when a request is posted to `/hooks/<path>` on the
+//!   [API](crate::api).
+//! - [`Schedule::Topic`] tasks run when an event is published to their topic with
+//!   [`Scheduler::publish`].
+//! - [`Schedule::FileWatch`] tasks run when a file under their directory changes, once
+//!   [`watch_files`] is running.
+//!
+//! The event is stored on the execution and available to the task's config as
+//! `{{ event.* }}`. A file event is `{"source": "file", "path", "relative_path",
+//! "kind", "dir"}`, where `kind` is `created`, `modified` or `removed`.
+
+use crate::{ChronoError, Result, Schedule, Scheduler, Task};
+use globset::Glob;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// How long to wait for a burst of file events to settle before firing.
+const FILE_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// How often the watched directories are matched against the registered tasks.
+const WATCH_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs [`Schedule::FileWatch`] tasks until dropped.
+pub struct FileTriggers {
+    watcher: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for FileTriggers {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Watches the directories of the scheduler's file-watch tasks and starts a run of a
+/// task for every file under its directory that changes and matches its glob. Tasks
+/// added, edited or removed later are picked up within a second; a directory that
+/// doesn't exist is reported and watched once it does. Changes to the same file in
+/// quick succession start one run.
+pub fn watch_files(scheduler: Arc<Scheduler>) -> Result<FileTriggers> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        for path in event.paths {
+            let kind = match event.kind {
+                EventKind::Create(_) => "created",
+                EventKind::Modify(ModifyKind::Name(_)) if path.exists() => "created",
+                EventKind::Modify(ModifyKind::Name(_)) => "removed",
+                EventKind::Modify(_) => "modified",
+                EventKind::Remove(_) => "removed",
+                _ => continue,
+            };
+            if !path.is_dir() {
+                let _ = tx.send((path, kind));
+            }
+        }
+    })
+    .map_err(|e| ChronoError::InvalidTask(format!("file watcher: {}", e)))?;
+
+    let watcher = tokio::spawn(async move {
+        let mut watched = WatchedDirs::default();
+        let mut sync = tokio::time::interval(WATCH_SYNC_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = sync.tick() => watched.sync(&mut watcher, &scheduler.list_tasks()),
+                Some((path, kind)) = rx.recv() => {
+                    // Writers often touch a file several times; report each file once.
+                    tokio::time::sleep(FILE_DEBOUNCE).await;
+                    let mut changes = HashMap::from([(path, kind)]);
+                    while let Ok((path, kind)) = rx.try_recv() {
+                        let seen = changes.entry(path).or_insert(kind);
+                        if kind == "removed" || *seen == "removed" {
+                            *seen = kind;
+                        }
+                    }
+                    // In a cluster only the leader runs tasks.
+                    if scheduler.is_leader() {
+                        fire(&scheduler, &changes);
+                    }
+                }
+            }
+        }
+    });
+    Ok(FileTriggers { watcher })
+}
+
+/// The directories being watched, by canonical path.
+#[derive(Default)]
+struct WatchedDirs {
+    dirs: HashSet<PathBuf>,
+    /// Configured directories that couldn't be watched, so each is reported once.
+    failed: HashSet<String>,
+}
+
+impl WatchedDirs {
+    fn sync(&mut self, watcher: &mut RecommendedWatcher, tasks: &[Task]) {
+        let mut wanted = HashSet::new();
+        for task in tasks.iter().filter(|t| t.enabled) {
+            let Schedule::FileWatch { dir, .. } = &task.schedule else {
+                continue;
+            };
+            let watched = std::fs::canonicalize(dir).map_err(|e| e.to_string()).and_then(|path| {
+                if !self.dirs.contains(&path) {
+                    watcher.watch(&path, RecursiveMode::Recursive).map_err(|e| e.to_string())?;
+                    self.dirs.insert(path.clone());
+                }
+                Ok(path)
+            });
+            match watched {
+                Ok(path) => {
+                    self.failed.remove(dir);
+                    wanted.insert(path);
+                }
+                Err(e) if self.failed.insert(dir.clone()) => {
+                    warn!(task_id = %task.id, "Can't watch {} for task {}: {}", dir, task.name, e);
+                }
+                Err(_) => {}
+            }
+        }
+        for path in self.dirs.difference(&wanted).cloned().collect::<Vec<_>>() {
+            let _ = watcher.unwatch(&path);
+            self.dirs.remove(&path);
+        }
+    }
+}
+
+fn fire(scheduler: &Scheduler, changes: &HashMap<PathBuf, &str>) {
+    for task in scheduler.list_tasks().into_iter().filter(|t| t.enabled) {
+        let Schedule::FileWatch { dir, glob } = &task.schedule else {
+            continue;
+        };
+        let Ok(root) = std::fs::canonicalize(dir) else {
+            continue;
+        };
+        let matcher = glob.as_deref().map(|g| Glob::new(g).map(|g| g.compile_matcher()));
+        for (path, kind) in changes {
+            let Some(relative) = relative_path(&root, path) else {
+                continue;
+            };
+            match &matcher {
+                Some(Ok(matcher)) if !matcher.is_match(relative) => continue,
+                // Rejected when the task was registered; nothing can match it.
+                Some(Err(_)) => continue,
+                _ => {}
+            }
+            let event = serde_json::json!({
+                "source": "file",
+                "path": path,
+                "relative_path": relative,
+                "kind": kind,
+                "dir": dir,
+            });
+            if let Err(e) = scheduler.trigger_event(&task.id, event) {
+                error!(task_id = %task.id, "Failed to run task {} for {}: {}", task.name, path.display(), e);
+            }
+        }
+    }
+}
+
+/// `path` relative to `root`, if it lies under it.
+fn relative_path<'a>(root: &Path, path: &'a Path) -> Option<&'a Path> {
+    path.strip_prefix(root).ok().filter(|p| !p.as_os_str().is_empty())
+}
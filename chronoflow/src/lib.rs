@@ -1,9 +1,33 @@
 pub mod types;
 pub mod error;
+pub mod cron;
+pub mod definitions;
+pub mod events;
+pub mod metrics;
+pub mod notifications;
+pub mod notifiers;
 pub mod plugin;
+pub mod plugins;
 pub mod scheduler;
+pub mod shard;
+pub mod api;
+pub mod client;
+pub mod cluster;
+pub mod storage;
+pub mod template;
+pub mod wasm;
+pub mod workflow;
 
 pub use types::*;
 pub use error::*;
+pub use cron::*;
 pub use plugin::*;
-pub use scheduler::*;
\ No newline at end of file
+pub use plugins::*;
+pub use scheduler::*;
+pub use storage::*;
+pub use metrics::Metrics;
+pub use notifications::{Notification, Notifier};
+pub use notifiers::*;
+pub use template::{DirSecrets, NoSecrets, SecretProvider, TemplateContext};
+pub use wasm::{WasmLimits, WasmRuntime};
+pub use workflow::{StepStatus, WorkflowRun, WorkflowStatus};
\ No newline at end of file
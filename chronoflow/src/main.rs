@@ -1,44 +1,675 @@
-mod error;
-mod types;
-mod plugin;
-mod scheduler;
-
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use plugin::PluginManager;
-use scheduler::Scheduler;
-use types::{Task, Schedule, PluginConfig};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+use chronoflow::client::ApiClient;
+use chronoflow::cluster::{ClusterConfig, ClusterNode, Peer, WorkerConfig};
+use chronoflow::{
+    api, definitions, events, ChronoError, Coordinator, CronExpr, Dependency, DirSecrets, ExecutionFilter, ExecutionStats, ExecutionStatus, FileStorage,
+    NullStorage, OverlapPolicy, PluginConfig, PluginManager, Result, RetentionPolicy, Schedule, Scheduler, SchedulerConfig, Storage, Task, TaskExecution, TaskSpec, TriggerCondition,
+};
+
+#[derive(Parser)]
+#[command(name = "chronoflow", version, about = "ChronoFlow - Distributed Task Scheduler")]
+struct Cli {
+    /// Scheduler API to talk to.
+    #[arg(long, global = true, env = "CHRONOFLOW_URL", default_value = "http://127.0.0.1:8080")]
+    server: String,
+
+    /// Token the API requires as `Authorization: Bearer <token>`; sent by the client
+    /// commands and required by `serve`.
+    #[arg(long, global = true, env = "CHRONOFLOW_API_TOKEN", hide_env_values = true)]
+    api_token: Option<String>,
+
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the scheduler and its HTTP API.
+    Serve(ServeArgs),
+    /// Manage tasks on a running scheduler.
+    #[command(subcommand)]
+    Task(TaskCommand),
+    /// Inspect executions on a running scheduler.
+    #[command(subcommand)]
+    Exec(ExecCommand),
+    /// Check that a cron expression parses and fires.
+    ValidateCron { expr: String },
+    /// List the next fire times of a cron expression.
+    NextRuns {
+        expr: String,
+        #[arg(short, default_value_t = 10)]
+        n: usize,
+        /// Zone to evaluate the expression in.
+        #[arg(long, default_value = "UTC")]
+        timezone: String,
+        /// Start from this RFC 3339 time instead of now.
+        #[arg(long)]
+        after: Option<DateTime<Utc>>,
+    },
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[arg(long, env = "CHRONOFLOW_ADDR", default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+    /// Persist tasks and executions to this file.
+    #[arg(long, env = "CHRONOFLOW_DATA")]
+    data: Option<PathBuf>,
+    /// Directory of files backing `{{ secrets.<name> }}` placeholders.
+    #[arg(long, env = "CHRONOFLOW_SECRETS_DIR")]
+    secrets_dir: Option<PathBuf>,
+    /// Load task definitions from YAML/TOML files here and reload them on change.
+    #[arg(long, env = "CHRONOFLOW_TASKS_DIR")]
+    tasks_dir: Option<PathBuf>,
+    /// Name of this node in a cluster; required with `--peer`.
+    #[arg(long, env = "CHRONOFLOW_NODE_ID", requires_all = ["peer", "cluster_secret"])]
+    node_id: Option<String>,
+    /// Secret shared by every cluster node, which they authenticate each other with.
+    #[arg(long, env = "CHRONOFLOW_CLUSTER_SECRET", hide_env_values = true, requires = "node_id")]
+    cluster_secret: Option<String>,
+    /// Another cluster node as `id=url`, e.g. `b=http://10.0.0.2:8080`; repeatable.
+    #[arg(long, value_parser = parse_peer, requires = "node_id")]
+    peer: Vec<Peer>,
+    /// Share runs across the cluster's work queue, running up to this many at once
+    /// on this node.
+    #[arg(long, requires = "node_id")]
+    workers: Option<usize>,
+    /// Keep at most this many finished executions per task.
+    #[arg(long, env = "CHRONOFLOW_KEEP_EXECUTIONS")]
+    keep_executions: Option<usize>,
+    /// Drop executions that finished more than this many seconds ago.
+    #[arg(long, env = "CHRONOFLOW_MAX_EXECUTION_AGE")]
+    max_execution_age: Option<u64>,
+    /// On Ctrl+C, seconds to let running tasks finish before interrupting them.
+    #[arg(long, env = "CHRONOFLOW_SHUTDOWN_TIMEOUT", default_value_t = 30)]
+    shutdown_timeout: u64,
+}
+
+fn parse_peer(value: &str) -> std::result::Result<Peer, String> {
+    match value.split_once('=') {
+        Some((id, url)) if !id.is_empty() && !url.is_empty() => Ok(Peer { id: id.to_string(), url: url.to_string() }),
+        _ => Err(format!("expected id=url, got '{}'", value)),
+    }
+}
+
+#[derive(Subcommand)]
+enum TaskCommand {
+    Add(Box<TaskAddArgs>),
+    List,
+    Rm { id: Uuid },
+    Enable { id: Uuid },
+    Disable { id: Uuid },
+    /// Start a run now.
+    Run { id: Uuid },
+}
+
+#[derive(Args)]
+struct TaskAddArgs {
+    /// JSON task spec; replaces all other options.
+    #[arg(long, conflicts_with_all = ["name", "cron", "every", "at", "manual", "webhook", "watch", "topic"])]
+    file: Option<PathBuf>,
+    #[arg(long, required_unless_present = "file")]
+    name: Option<String>,
+    #[arg(long, group = "schedule")]
+    cron: Option<String>,
+    /// Interval in seconds.
+    #[arg(long, group = "schedule")]
+    every: Option<u64>,
+    /// Run once at this RFC 3339 time.
+    #[arg(long, group = "schedule")]
+    at: Option<DateTime<Utc>>,
+    /// Only run when triggered or by upstream tasks.
+    #[arg(long, group = "schedule")]
+    manual: bool,
+    /// Run on every POST to /hooks/<PATH>.
+    #[arg(long, group = "schedule")]
+    webhook: Option<String>,
+    /// Run when a file in this directory changes.
+    #[arg(long, group = "schedule")]
+    watch: Option<String>,
+    /// Only react to files whose path relative to --watch matches this glob.
+    #[arg(long, requires = "watch")]
+    glob: Option<String>,
+    /// Run on every event published to this topic.
+    #[arg(long, group = "schedule")]
+    topic: Option<String>,
+    #[arg(long, default_value = "logger")]
+    plugin: String,
+    /// Plugin config as JSON.
+    #[arg(long, default_value = "{}")]
+    config: String,
+    #[arg(long)]
+    wasm: Option<String>,
+    #[arg(long)]
+    timezone: Option<String>,
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Run after this task succeeds; repeatable.
+    #[arg(long)]
+    depends_on: Vec<Uuid>,
+    /// Register the task paused.
+    #[arg(long)]
+    disabled: bool,
+    /// Notification rule as JSON, e.g.
+    /// '{"on": "Failure", "notifier": "file", "config": {"path": "alerts.jsonl"}}';
+    /// repeatable.
+    #[arg(long)]
+    notify: Vec<String>,
+    /// What to do when a run is due while the previous one is still going.
+    #[arg(long, value_enum, default_value_t = OverlapArg::Allow)]
+    overlap: OverlapArg,
+}
+
+#[derive(Subcommand)]
+enum ExecCommand {
+    List {
+        #[arg(long)]
+        task: Option<Uuid>,
+        #[arg(long, value_enum)]
+        status: Option<StatusArg>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Only executions started at or after this RFC 3339 time.
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        /// Only executions started before this RFC 3339 time.
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+    },
+    /// Success rate, durations and last failure of matching executions.
+    Stats {
+        #[arg(long)]
+        task: Option<Uuid>,
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+    },
+    Show { id: Uuid },
+    /// Print an execution's output and error.
+    Logs { id: Uuid },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OverlapArg {
+    Allow,
+    Skip,
+    Queue,
+    CancelPrevious,
+}
+
+impl From<OverlapArg> for OverlapPolicy {
+    fn from(policy: OverlapArg) -> Self {
+        match policy {
+            OverlapArg::Allow => OverlapPolicy::Allow,
+            OverlapArg::Skip => OverlapPolicy::Skip,
+            OverlapArg::Queue => OverlapPolicy::Queue,
+            OverlapArg::CancelPrevious => OverlapPolicy::CancelPrevious,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusArg {
+    Queued,
+    Running,
+    Success,
+    Failed,
+    Timeout,
+    Skipped,
+    Cancelled,
+    Interrupted,
+}
+
+impl From<StatusArg> for ExecutionStatus {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Queued => ExecutionStatus::Queued,
+            StatusArg::Running => ExecutionStatus::Running,
+            StatusArg::Success => ExecutionStatus::Success,
+            StatusArg::Failed => ExecutionStatus::Failed,
+            StatusArg::Timeout => ExecutionStatus::Timeout,
+            StatusArg::Skipped => ExecutionStatus::Skipped,
+            StatusArg::Cancelled => ExecutionStatus::Cancelled,
+            StatusArg::Interrupted => ExecutionStatus::Interrupted,
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut client = ApiClient::new(cli.server);
+    if let Some(token) = &cli.api_token {
+        client = client.with_token(token);
+    }
+    let output = cli.output;
+    match cli.command {
+        Command::Serve(args) => serve(args, cli.api_token).await,
+        Command::Task(command) => task_command(&client, output, command).await,
+        Command::Exec(command) => exec_command(&client, output, command).await,
+        Command::ValidateCron { expr } => {
+            Schedule::Cron(expr.clone()).validate()?;
+            if output == OutputFormat::Json {
+                print_json(&serde_json::json!({ "expression": expr, "valid": true }));
+            } else {
+                println!("✅ '{}' is valid", expr);
+            }
+            Ok(())
+        }
+        Command::NextRuns { expr, n, timezone, after } => {
+            let cron = CronExpr::parse(&expr)?;
+            let tz: Tz = timezone.parse().map_err(|_| ChronoError::InvalidTimezone(timezone.clone()))?;
+            let mut at = after.unwrap_or_else(Utc::now).with_timezone(&tz);
+            let mut runs = Vec::new();
+            while runs.len() < n {
+                let Some(next) = cron.next_after_in(&at) else { break };
+                runs.push(next);
+                at = next;
+            }
+            if output == OutputFormat::Json {
+                print_json(&runs.iter().map(|t| t.to_rfc3339()).collect::<Vec<_>>());
+            } else {
+                for run in runs {
+                    println!("{}", run.to_rfc3339());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn serve(args: ServeArgs, api_token: Option<String>) -> Result<()> {
+    // `RUST_LOG` picks what is logged, e.g. `chronoflow=debug`.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
     println!("🚀 ChronoFlow - Distributed Task Scheduler");
     println!("==========================================\n");
-    
+
     let plugin_manager = Arc::new(PluginManager::new());
-    let scheduler = Arc::new(Scheduler::new(Arc::clone(&plugin_manager)));
-    
-    // Add demo task
-    let demo_task = Task::new(
-        "Demo Logger Task".to_string(),
-        Schedule::Interval { seconds: 10 },
-        PluginConfig {
-            name: "logger".to_string(),
-            wasm_path: "".to_string(),
-            config: serde_json::json!({
-                "message": "Hello from ChronoFlow! Task is running..."
-            }),
-        },
-    );
-    
-    let task_id = scheduler.add_task(demo_task);
-    println!("✅ Added task with ID: {}", task_id);
-    
-    // Start scheduler
+    let retention = RetentionPolicy {
+        max_per_task: args.keep_executions,
+        max_age: args.max_execution_age.map(std::time::Duration::from_secs),
+    };
+    let mut scheduler = Scheduler::new(Arc::clone(&plugin_manager))
+        .with_config(SchedulerConfig { retention, ..SchedulerConfig::default() });
+    let mut storage: Arc<dyn Storage> = Arc::new(NullStorage);
+    if let Some(path) = &args.data {
+        storage = Arc::new(FileStorage::open(path)?);
+        println!("💾 Storing state in {}", path.display());
+    }
+    let node = args.node_id.map(|id| {
+        let mut config = ClusterConfig::new(id, args.peer.clone());
+        config.workers = args.workers.map(|concurrency| WorkerConfig { concurrency, ..WorkerConfig::default() });
+        config.secret = args.cluster_secret.clone();
+        ClusterNode::new(config, Arc::clone(&storage))
+    });
+    match &node {
+        Some(node) => {
+            scheduler = scheduler.with_storage(node.storage())?.with_coordinator(node.clone());
+            println!("🔗 Cluster node {} with {} peer(s)", node.status().node_id, args.peer.len());
+        }
+        None => scheduler = scheduler.with_storage(storage)?,
+    }
+    if let Some(dir) = &args.secrets_dir {
+        scheduler = scheduler.with_secrets(Arc::new(DirSecrets::new(dir)));
+    }
+    let scheduler = Arc::new(scheduler);
+    if let Some(node) = &node {
+        node.attach(&scheduler);
+        node.start();
+    }
+
+    let _watcher = match (&args.tasks_dir, &node) {
+        (Some(dir), Some(node)) => {
+            // Only the leader can change tasks; apply the files whenever this node takes over.
+            let (leader, node, dir_path) = (Arc::clone(&scheduler), Arc::clone(node), dir.clone());
+            tokio::spawn(async move {
+                loop {
+                    node.leadership_changed().await;
+                    if node.is_leader() {
+                        if let Err(e) = definitions::load_and_sync(&leader, &dir_path) {
+                            tracing::error!("Failed to load task definitions: {}", e);
+                        }
+                    }
+                }
+            });
+            println!("📄 Loading task definitions from {} when leading", dir.display());
+            Some(definitions::watch(Arc::clone(&scheduler), dir.clone())?)
+        }
+        (Some(dir), None) => {
+            let report = definitions::load_and_sync(&scheduler, dir)?;
+            println!(
+                "📄 Loaded task definitions from {} ({} added, {} updated, {} removed)",
+                dir.display(), report.added.len(), report.updated.len(), report.removed.len()
+            );
+            Some(definitions::watch(Arc::clone(&scheduler), dir.clone())?)
+        }
+        (None, _) => None,
+    };
+
     scheduler.start().await;
-    
-    println!("\n⏰ Scheduler started! Tasks will run every 10 seconds.");
+    println!("⏰ Scheduler started with {} task(s).", scheduler.list_tasks().len());
+    let _file_triggers = events::watch_files(Arc::clone(&scheduler))?;
+
+    if api_token.is_none() && !args.addr.ip().is_loopback() {
+        tracing::warn!("No API token set; anyone who can reach {} can manage tasks and run commands", args.addr);
+    }
+    let mut app = api::router(Arc::clone(&scheduler), api_token);
+    if let Some(node) = &node {
+        app = app.merge(node.router());
+    }
+    let server = tokio::spawn(api::serve_router(app, args.addr));
+    println!("🌐 API listening on http://{}", args.addr);
     println!("Press Ctrl+C to stop...\n");
-    
-    // Keep running
-    tokio::signal::ctrl_c().await.unwrap();
-    println!("\n👋 Shutting down ChronoFlow...");
-}
\ No newline at end of file
+
+    tokio::select! {
+        result = server => {
+            result.map_err(|e| ChronoError::NetworkError(e.to_string()))??;
+        }
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    println!("\n👋 Shutting down ChronoFlow, waiting up to {}s for running tasks...", args.shutdown_timeout);
+    let report = scheduler.shutdown_handle()
+        .shutdown(std::time::Duration::from_secs(args.shutdown_timeout))
+        .await;
+    println!("{} run(s) finished, {} interrupted", report.drained, report.interrupted);
+    if let Some(node) = &node {
+        node.stop();
+    }
+    Ok(())
+}
+
+async fn task_command(client: &ApiClient, output: OutputFormat, command: TaskCommand) -> Result<()> {
+    match command {
+        TaskCommand::Add(args) => {
+            let spec = task_spec(*args)?;
+            let task = client.create_task(&spec).await?;
+            print_tasks(output, &[task]);
+        }
+        TaskCommand::List => {
+            let tasks = client.list_tasks().await?;
+            print_tasks(output, &tasks);
+        }
+        TaskCommand::Rm { id } => {
+            client.delete_task(&id).await?;
+            if output == OutputFormat::Json {
+                print_json(&serde_json::json!({ "deleted": id }));
+            } else {
+                println!("Removed task {}", id);
+            }
+        }
+        TaskCommand::Enable { id } => print_tasks(output, &[client.set_enabled(&id, true).await?]),
+        TaskCommand::Disable { id } => print_tasks(output, &[client.set_enabled(&id, false).await?]),
+        TaskCommand::Run { id } => {
+            let execution_id = client.trigger(&id).await?;
+            if output == OutputFormat::Json {
+                print_json(&serde_json::json!({ "execution_id": execution_id }));
+            } else {
+                println!("Started execution {}", execution_id);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn task_spec(args: TaskAddArgs) -> Result<TaskSpec> {
+    if let Some(path) = &args.file {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ChronoError::InvalidTask(format!("{}: {}", path.display(), e)))?;
+        return serde_json::from_str(&text)
+            .map_err(|e| ChronoError::InvalidTask(format!("{}: {}", path.display(), e)));
+    }
+
+    let schedule = if let Some(expr) = args.cron {
+        Schedule::Cron(expr)
+    } else if let Some(seconds) = args.every {
+        Schedule::Interval { seconds }
+    } else if let Some(at) = args.at {
+        Schedule::Once { at }
+    } else if let Some(path) = args.webhook {
+        Schedule::Webhook { path }
+    } else if let Some(dir) = args.watch {
+        Schedule::FileWatch { dir, glob: args.glob }
+    } else if let Some(name) = args.topic {
+        Schedule::Topic { name }
+    } else if args.manual {
+        Schedule::Manual
+    } else {
+        return Err(ChronoError::InvalidTask(
+            "one of --cron, --every, --at, --webhook, --watch, --topic or --manual is required".into(),
+        ));
+    };
+    let config = serde_json::from_str(&args.config)
+        .map_err(|e| ChronoError::InvalidTask(format!("--config is not valid JSON: {}", e)))?;
+    let notifications = args.notify.iter()
+        .map(|rule| serde_json::from_str(rule)
+            .map_err(|e| ChronoError::InvalidTask(format!("--notify is not a valid rule: {}", e))))
+        .collect::<Result<_>>()?;
+    Ok(TaskSpec {
+        name: args.name.unwrap_or_default(),
+        schedule,
+        plugin: PluginConfig {
+            name: args.plugin,
+            wasm_path: args.wasm.unwrap_or_default(),
+            config,
+        },
+        timezone: args.timezone,
+        misfire_policy: Default::default(),
+        overlap_policy: args.overlap.into(),
+        retry: None,
+        timeout_seconds: args.timeout,
+        depends_on: args.depends_on.into_iter()
+            .map(|task_id| Dependency { task_id, condition: TriggerCondition::OnSuccess })
+            .collect(),
+        notifications,
+        enabled: !args.disabled,
+    })
+}
+
+async fn exec_command(client: &ApiClient, output: OutputFormat, command: ExecCommand) -> Result<()> {
+    match command {
+        ExecCommand::List { task, status, limit, offset, since, until } => {
+            let filter = ExecutionFilter {
+                task_id: task,
+                status: status.map(Into::into),
+                since,
+                until,
+                offset,
+                limit: Some(limit),
+                ..Default::default()
+            };
+            let page = client.list_executions(&filter).await?;
+            if output == OutputFormat::Json {
+                print_json(&page);
+            } else {
+                print_executions(&page.items);
+                println!("\n{}-{} of {}", (offset + 1).min(page.total), offset + page.items.len(), page.total);
+            }
+        }
+        ExecCommand::Stats { task, since, until } => {
+            let filter = ExecutionFilter { task_id: task, since, until, ..Default::default() };
+            let stats = client.execution_stats(&filter).await?;
+            if output == OutputFormat::Json {
+                print_json(&stats);
+            } else {
+                print_stats(&stats);
+            }
+        }
+        ExecCommand::Show { id } => {
+            let execution = client.get_execution(&id).await?;
+            if output == OutputFormat::Json {
+                print_json(&execution);
+            } else {
+                print_execution_details(&execution);
+            }
+        }
+        ExecCommand::Logs { id } => {
+            let execution = client.get_execution(&id).await?;
+            if output == OutputFormat::Json {
+                print_json(&serde_json::json!({ "logs": execution.logs, "output": execution.output, "error": execution.error }));
+            } else {
+                print_execution_logs(&execution);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+fn print_tasks(output: OutputFormat, tasks: &[Task]) {
+    if output == OutputFormat::Json {
+        print_json(tasks);
+        return;
+    }
+    let rows: Vec<Vec<String>> = tasks.iter()
+        .map(|t| vec![
+            t.id.to_string(),
+            t.name.clone(),
+            describe_schedule(&t.schedule),
+            if t.enabled { "yes".into() } else { "no".into() },
+            format_time(t.next_run),
+            format_time(t.last_run),
+        ])
+        .collect();
+    print_table(&["ID", "NAME", "SCHEDULE", "ENABLED", "NEXT RUN", "LAST RUN"], &rows);
+}
+
+fn print_executions(executions: &[TaskExecution]) {
+    let rows: Vec<Vec<String>> = executions.iter()
+        .map(|e| vec![
+            e.id.to_string(),
+            e.task_id.to_string(),
+            format!("{:?}", e.status),
+            format_time(Some(e.started_at)),
+            e.duration_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".into()),
+            e.attempt.to_string(),
+        ])
+        .collect();
+    print_table(&["ID", "TASK", "STATUS", "STARTED", "DURATION", "ATTEMPT"], &rows);
+}
+
+fn print_execution_details(e: &TaskExecution) {
+    let optional_id = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_else(|| "-".into());
+    let rows = vec![
+        vec!["ID".into(), e.id.to_string()],
+        vec!["TASK".into(), e.task_id.to_string()],
+        vec!["STATUS".into(), format!("{:?}", e.status)],
+        vec!["FAILURE".into(), e.failure_reason.map(|r| format!("{:?}", r)).unwrap_or_else(|| "-".into())],
+        vec!["SCHEDULED".into(), format_time(e.scheduled_at)],
+        vec!["STARTED".into(), format_time(Some(e.started_at))],
+        vec!["FINISHED".into(), format_time(e.finished_at)],
+        vec!["DURATION".into(), e.duration_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".into())],
+        vec!["ATTEMPT".into(), e.attempt.to_string()],
+        vec!["RETRY OF".into(), optional_id(e.retry_of)],
+        vec!["WORKFLOW RUN".into(), optional_id(e.workflow_run_id)],
+        vec!["MESSAGE".into(), e.output.as_ref().map(|o| o.message.clone()).unwrap_or_else(|| "-".into())],
+        vec!["ERROR".into(), e.error.clone().unwrap_or_else(|| "-".into())],
+    ];
+    for row in rows {
+        println!("{:<13} {}", row[0], row[1]);
+    }
+}
+
+fn print_stats(stats: &ExecutionStats) {
+    let ms = |value: Option<u64>| value.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".into());
+    let rows = vec![
+        vec!["TOTAL".into(), stats.total.to_string()],
+        vec!["SUCCEEDED".into(), stats.succeeded.to_string()],
+        vec!["FAILED".into(), stats.failed.to_string()],
+        vec!["SUCCESS RATE".into(), stats.success_rate.map(|r| format!("{:.1}%", r * 100.0)).unwrap_or_else(|| "-".into())],
+        vec!["P50".into(), ms(stats.p50_duration_ms)],
+        vec!["P95".into(), ms(stats.p95_duration_ms)],
+        vec!["LAST FAILURE".into(), stats.last_failure.as_ref()
+            .map(|e| format!("{} at {}", e.id, format_time(Some(e.started_at))))
+            .unwrap_or_else(|| "-".into())],
+    ];
+    for row in rows {
+        println!("{:<13} {}", row[0], row[1]);
+    }
+}
+
+fn print_execution_logs(e: &TaskExecution) {
+    for line in &e.logs {
+        println!("{} {:<5} {}", line.at.format("%Y-%m-%d %H:%M:%S%.3f"), format!("{:?}", line.level).to_uppercase(), line.message);
+    }
+    if let Some(output) = &e.output {
+        println!("{}", output.message);
+        // Process output from the shell plugin.
+        for stream in ["stdout", "stderr"] {
+            if let Some(text) = output.data.get(stream).and_then(|v| v.as_str()).filter(|t| !t.is_empty()) {
+                println!("--- {} ---\n{}", stream, text.trim_end());
+            }
+        }
+    }
+    if let Some(error) = &e.error {
+        eprintln!("error: {}", error);
+    }
+}
+
+fn describe_schedule(schedule: &Schedule) -> String {
+    match schedule {
+        Schedule::Cron(expr) => format!("cron {}", expr),
+        Schedule::Interval { seconds } => format!("every {}s", seconds),
+        Schedule::Once { at } => format!("once {}", at.to_rfc3339()),
+        Schedule::Manual => "manual".into(),
+        Schedule::Webhook { path } => format!("webhook /hooks/{}", path.trim_matches('/')),
+        Schedule::FileWatch { dir, glob: Some(glob) } => format!("watch {} ({})", dir, glob),
+        Schedule::FileWatch { dir, glob: None } => format!("watch {}", dir),
+        Schedule::Topic { name } => format!("topic {}", name),
+    }
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".into())
+}
+
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells.iter().zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(headers.to_vec()));
+    for row in rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
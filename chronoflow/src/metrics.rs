@@ -0,0 +1,163 @@
+//! Prometheus metrics for the scheduler, served by the API at `/metrics`.
+//!
+//! | Metric | Labels | |
+//! |---|---|---|
+//! | `chronoflow_executions_total` | `task_id`, `task`, `status` | Executions that reached a final status |
+//! | `chronoflow_execution_duration_seconds` | `task_id`, `task` | Histogram of attempt durations |
+//! | `chronoflow_schedule_lag_seconds` | `task_id`, `task` | Histogram of how long after its fire time a run started |
+//! | `chronoflow_scheduled_tasks` | | Enabled tasks with a pending fire time |
+//! | `chronoflow_queued_executions` | | Executions waiting for a previous run or a concurrency slot |
+//! | `chronoflow_running_executions` | | Executions whose plugin is running |
+//! | `chronoflow_plugin_errors_total` | `plugin` | Errors returned by plugins |
+//! | `chronoflow_notifications_total` | `notifier`, `outcome` | Notifications `sent`, `failed` or `deduplicated` |
+
+use crate::ExecutionStatus;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use std::time::Duration;
+use uuid::Uuid;
+
+const DURATION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+const LAG_BUCKETS: &[f64] = &[0.001, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0, 60.0, 300.0];
+
+/// The scheduler's metrics and the registry they are gathered from. A
+/// [`PluginManager`](crate::PluginManager) creates one, and the
+/// [`Scheduler`](crate::Scheduler) built on it records into the same registry.
+pub struct Metrics {
+    registry: Registry,
+    executions: IntCounterVec,
+    duration: HistogramVec,
+    lag: HistogramVec,
+    scheduled_tasks: IntGauge,
+    queued_executions: IntGauge,
+    running_executions: IntGauge,
+    plugin_errors: IntCounterVec,
+    notifications: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("chronoflow".into()), None).expect("namespace is valid");
+        let task = &["task_id", "task"];
+        let metrics = Self {
+            executions: IntCounterVec::new(
+                Opts::new("executions_total", "Executions that reached a final status"),
+                &["task_id", "task", "status"],
+            ).expect("metric is valid"),
+            duration: HistogramVec::new(
+                HistogramOpts::new("execution_duration_seconds", "Duration of each execution attempt")
+                    .buckets(DURATION_BUCKETS.to_vec()),
+                task,
+            ).expect("metric is valid"),
+            lag: HistogramVec::new(
+                HistogramOpts::new("schedule_lag_seconds", "Time between a run's fire time and its start")
+                    .buckets(LAG_BUCKETS.to_vec()),
+                task,
+            ).expect("metric is valid"),
+            scheduled_tasks: IntGauge::new("scheduled_tasks", "Enabled tasks with a pending fire time")
+                .expect("metric is valid"),
+            queued_executions: IntGauge::new("queued_executions", "Executions waiting to start")
+                .expect("metric is valid"),
+            running_executions: IntGauge::new("running_executions", "Executions whose plugin is running")
+                .expect("metric is valid"),
+            plugin_errors: IntCounterVec::new(
+                Opts::new("plugin_errors_total", "Errors returned by plugins"),
+                &["plugin"],
+            ).expect("metric is valid"),
+            notifications: IntCounterVec::new(
+                Opts::new("notifications_total", "Notifications by notifier and outcome"),
+                &["notifier", "outcome"],
+            ).expect("metric is valid"),
+            registry,
+        };
+        metrics.register_all();
+        metrics
+    }
+
+    fn register_all(&self) {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.executions.clone()),
+            Box::new(self.duration.clone()),
+            Box::new(self.lag.clone()),
+            Box::new(self.scheduled_tasks.clone()),
+            Box::new(self.queued_executions.clone()),
+            Box::new(self.running_executions.clone()),
+            Box::new(self.plugin_errors.clone()),
+            Box::new(self.notifications.clone()),
+        ];
+        for collector in collectors {
+            self.registry.register(collector).expect("metric names are unique");
+        }
+    }
+
+    /// The registry the metrics live in; other collectors can be added to it.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Everything in the registry, in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        // Writing to a Vec can't fail, and every metric has a valid name.
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    /// Content type of [`Metrics::encode`]'s output.
+    pub fn content_type(&self) -> &'static str {
+        prometheus::TEXT_FORMAT
+    }
+
+    /// Counts an execution that reached `status`, with how long it ran if it did.
+    pub fn record_execution(&self, task_id: &Uuid, task: &str, status: ExecutionStatus, duration: Option<Duration>) {
+        let id = task_id.to_string();
+        let status = format!("{:?}", status);
+        self.executions.with_label_values(&[&id, task, &status]).inc();
+        if let Some(duration) = duration {
+            self.duration.with_label_values(&[&id, task]).observe(duration.as_secs_f64());
+        }
+    }
+
+    /// Records that a run started `lag` after its fire time.
+    pub fn record_lag(&self, task_id: &Uuid, task: &str, lag: Duration) {
+        self.lag.with_label_values(&[&task_id.to_string(), task]).observe(lag.as_secs_f64());
+    }
+
+    pub fn record_plugin_error(&self, plugin: &str) {
+        self.plugin_errors.with_label_values(&[plugin]).inc();
+    }
+
+    /// Counts a notification that was `sent`, `failed` or `deduplicated`.
+    pub fn record_notification(&self, notifier: &str, outcome: &str) {
+        self.notifications.with_label_values(&[notifier, outcome]).inc();
+    }
+
+    /// Updates the queue gauges, e.g. right before they are gathered.
+    pub fn set_queue_depth(&self, scheduled_tasks: usize, queued: usize, running: usize) {
+        self.scheduled_tasks.set(scheduled_tasks as i64);
+        self.queued_executions.set(queued as i64);
+        self.running_executions.set(running as i64);
+    }
+
+    /// Drops the series of a removed task.
+    pub fn forget_task(&self, task_id: &Uuid, task: &str) {
+        let id = task_id.to_string();
+        for status in [
+            ExecutionStatus::Success,
+            ExecutionStatus::Failed,
+            ExecutionStatus::Timeout,
+            ExecutionStatus::Skipped,
+            ExecutionStatus::Cancelled,
+            ExecutionStatus::Interrupted,
+        ] {
+            let _ = self.executions.remove_label_values(&[&id, task, &format!("{:?}", status)]);
+        }
+        let _ = self.duration.remove_label_values(&[&id, task]);
+        let _ = self.lag.remove_label_values(&[&id, task]);
+    }
+}
@@ -0,0 +1,66 @@
+//! Notifications about task runs, sent according to each task's
+//! [`NotificationRule`](crate::NotificationRule)s.
+//!
+//! A rule names a [`Notifier`] registered with the [`PluginManager`](crate::PluginManager)
+//! and passes it a config. The built-in notifiers are `webhook`, `smtp` and `file`, see
+//! [`crate::notifiers`]. Placeholders in the config are rendered like a plugin config,
+//! with `task.*`, `notification.*`, `env.*` and `secrets.*` available.
+
+use crate::{ExecutionStatus, NotifyOn, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// What happened to a run, as handed to a [`Notifier`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub event: NotifyOn,
+    pub task_id: Uuid,
+    pub task: String,
+    /// The run's first execution; retries link back to it.
+    pub execution_id: Uuid,
+    /// The run's final status, or `Running` for an SLA miss.
+    pub status: ExecutionStatus,
+    pub error: Option<String>,
+    /// How long the run has taken so far.
+    pub duration_ms: u64,
+    pub at: DateTime<Utc>,
+}
+
+impl Notification {
+    /// A one-line description, e.g. for a subject line.
+    pub fn summary(&self) -> String {
+        match (self.event, &self.error) {
+            (NotifyOn::Success, _) => format!("Task {} succeeded", self.task),
+            (NotifyOn::Failure, Some(error)) => format!("Task {} failed: {}", self.task, error),
+            (NotifyOn::Failure, None) => format!("Task {} failed", self.task),
+            (NotifyOn::Recovery, _) => format!("Task {} recovered", self.task),
+            (NotifyOn::SlaMiss { seconds }, _) => {
+                format!("Task {} still running after {}s", self.task, seconds)
+            }
+        }
+    }
+
+    /// The notification as JSON, with its [`summary`](Self::summary) included.
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = serde_json::to_value(self).unwrap_or_default();
+        if let Some(object) = json.as_object_mut() {
+            object.insert("summary".into(), self.summary().into());
+        }
+        json
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Checks a rule's config when its task is registered.
+    fn validate(&self, _config: &JsonValue) -> Result<()> {
+        Ok(())
+    }
+
+    async fn notify(&self, notification: &Notification, config: &JsonValue) -> Result<()>;
+}
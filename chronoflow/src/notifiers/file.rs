@@ -0,0 +1,54 @@
+use crate::{ChronoError, Notification, Notifier, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tokio::io::AsyncWriteExt;
+
+/// Appends each notification to a file as one line of JSON, with a `summary` field
+/// added. The file is created if it doesn't exist.
+///
+/// Config: `path` (required).
+pub struct FileNotifier;
+
+#[derive(Deserialize)]
+struct FileConfig {
+    path: String,
+}
+
+impl FileConfig {
+    fn parse(config: &JsonValue) -> Result<Self> {
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| ChronoError::PluginError(format!("file: {}", e)))?;
+        if parsed.path.is_empty() {
+            return Err(ChronoError::PluginError("file: path is empty".into()));
+        }
+        Ok(parsed)
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn validate(&self, config: &JsonValue) -> Result<()> {
+        FileConfig::parse(config).map(|_| ())
+    }
+
+    async fn notify(&self, notification: &Notification, config: &JsonValue) -> Result<()> {
+        let config = FileConfig::parse(config)?;
+        let mut line = notification.to_json().to_string();
+        line.push('\n');
+        let io_error = |e: std::io::Error| ChronoError::PluginError(format!("file: {}: {}", config.path, e));
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .await
+            .map_err(io_error)?;
+        // One write per line, so concurrent notifications don't interleave.
+        file.write_all(line.as_bytes()).await.map_err(io_error)?;
+        file.flush().await.map_err(io_error)
+    }
+}
@@ -0,0 +1,9 @@
+//! Notifiers registered by `PluginManager::new`.
+
+mod file;
+mod smtp;
+mod webhook;
+
+pub use file::FileNotifier;
+pub use smtp::SmtpNotifier;
+pub use webhook::WebhookNotifier;
@@ -0,0 +1,167 @@
+use crate::{ChronoError, Notification, Notifier, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const DEFAULT_TIMEOUT_SECONDS: f64 = 30.0;
+
+/// Emails the notification over plain SMTP, without TLS or authentication, e.g.
+/// through a local relay or a test server.
+///
+/// Config: `from` and `to` (a list of addresses; both required), `host` (default
+/// `localhost`), `port` (default 25), `subject` (default the notification's summary)
+/// and `timeout_seconds` for the whole exchange.
+pub struct SmtpNotifier;
+
+#[derive(Deserialize)]
+struct SmtpConfig {
+    #[serde(default = "default_host")]
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    from: String,
+    to: Vec<String>,
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(default)]
+    timeout_seconds: Option<f64>,
+}
+
+fn default_host() -> String {
+    "localhost".into()
+}
+
+fn default_port() -> u16 {
+    25
+}
+
+impl SmtpConfig {
+    fn parse(config: &JsonValue) -> Result<Self> {
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| ChronoError::PluginError(format!("smtp: {}", e)))?;
+        if parsed.to.is_empty() {
+            return Err(ChronoError::PluginError("smtp: to is empty".into()));
+        }
+        for address in std::iter::once(&parsed.from).chain(&parsed.to) {
+            if address.is_empty() || address.contains(['\r', '\n', '<', '>']) {
+                return Err(ChronoError::PluginError(format!("smtp: invalid address '{}'", address)));
+            }
+        }
+        parsed.timeout()?;
+        Ok(parsed)
+    }
+
+    fn timeout(&self) -> Result<Duration> {
+        let seconds = self.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        Duration::try_from_secs_f64(seconds).ok().filter(|t| !t.is_zero()).ok_or_else(|| {
+            ChronoError::PluginError(format!("smtp: timeout_seconds must be positive, got {}", seconds))
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        "smtp"
+    }
+
+    fn validate(&self, config: &JsonValue) -> Result<()> {
+        SmtpConfig::parse(config).map(|_| ())
+    }
+
+    async fn notify(&self, notification: &Notification, config: &JsonValue) -> Result<()> {
+        let config = SmtpConfig::parse(config)?;
+        let timeout = config.timeout()?;
+        let message = message(&config, notification);
+        tokio::time::timeout(timeout, send(&config, &message))
+            .await
+            .map_err(|_| ChronoError::PluginError(format!("smtp: timed out after {:?}", timeout)))?
+    }
+}
+
+async fn send(config: &SmtpConfig, message: &str) -> Result<()> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port)).await
+        .map_err(|e| ChronoError::PluginError(format!("smtp: connect {}:{}: {}", config.host, config.port, e)))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut session = Session { reader: BufReader::new(reader) };
+
+    session.expect(2).await?;
+    session.command(&mut writer, "EHLO chronoflow", 2).await?;
+    session.command(&mut writer, &format!("MAIL FROM:<{}>", config.from), 2).await?;
+    for to in &config.to {
+        session.command(&mut writer, &format!("RCPT TO:<{}>", to), 2).await?;
+    }
+    session.command(&mut writer, "DATA", 3).await?;
+    session.command(&mut writer, &format!("{}\r\n.", message), 2).await?;
+    // The message is accepted; a server that hangs up without answering QUIT is fine.
+    let _ = session.command(&mut writer, "QUIT", 2).await;
+    Ok(())
+}
+
+struct Session<R> {
+    reader: BufReader<R>,
+}
+
+impl<R: tokio::io::AsyncRead + Unpin> Session<R> {
+    async fn command(&mut self, writer: &mut (impl AsyncWrite + Unpin), line: &str, class: u16) -> Result<()> {
+        writer.write_all(format!("{}\r\n", line).as_bytes()).await.map_err(smtp_io)?;
+        self.expect(class).await
+    }
+
+    /// Reads a possibly multi-line reply and checks its code is `class`xx.
+    async fn expect(&mut self, class: u16) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await.map_err(smtp_io)? == 0 {
+                return Err(ChronoError::PluginError("smtp: server closed the connection".into()));
+            }
+            let code: u16 = line.get(..3).and_then(|c| c.parse().ok())
+                .ok_or_else(|| ChronoError::PluginError(format!("smtp: malformed reply '{}'", line.trim_end())))?;
+            if code / 100 != class {
+                return Err(ChronoError::PluginError(format!("smtp: server replied '{}'", line.trim_end())));
+            }
+            // `250-...` continues the reply, `250 ...` ends it.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn smtp_io(err: std::io::Error) -> ChronoError {
+    ChronoError::PluginError(format!("smtp: {}", err))
+}
+
+/// The message headers and body, with CRLF line endings and dot-stuffing applied.
+fn message(config: &SmtpConfig, notification: &Notification) -> String {
+    let subject = config.subject.clone().unwrap_or_else(|| notification.summary());
+    let mut body = vec![
+        notification.summary(),
+        String::new(),
+        format!("Task: {} ({})", notification.task, notification.task_id),
+        format!("Execution: {}", notification.execution_id),
+        format!("Status: {:?}", notification.status),
+        format!("Duration: {} ms", notification.duration_ms),
+        format!("At: {}", notification.at.to_rfc3339()),
+    ];
+    if let Some(error) = &notification.error {
+        body.push(format!("Error: {}", error));
+    }
+
+    let headers = [
+        format!("From: <{}>", config.from),
+        format!("To: {}", config.to.iter().map(|to| format!("<{}>", to)).collect::<Vec<_>>().join(", ")),
+        format!("Subject: {}", subject.replace(['\r', '\n'], " ")),
+        format!("Date: {}", notification.at.to_rfc2822()),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+    ];
+    headers.into_iter()
+        .chain(std::iter::once(String::new()))
+        .chain(body.iter().flat_map(|text| text.split('\n').map(|line| line.trim_end_matches('\r').to_string())))
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
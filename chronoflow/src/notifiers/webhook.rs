@@ -0,0 +1,86 @@
+use crate::{ChronoError, Notification, Notifier, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECONDS: f64 = 10.0;
+
+/// POSTs the notification as JSON, with a `summary` field added.
+///
+/// Config: `url` (required), `headers` and `timeout_seconds`. Any status other than
+/// 2xx counts as a failed delivery.
+pub struct WebhookNotifier {
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct WebhookConfig {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    timeout_seconds: Option<f64>,
+}
+
+impl WebhookConfig {
+    fn parse(config: &JsonValue) -> Result<Self> {
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| ChronoError::PluginError(format!("webhook: {}", e)))?;
+        reqwest::Url::parse(&parsed.url)
+            .map_err(|e| ChronoError::PluginError(format!("webhook: invalid url '{}': {}", parsed.url, e)))?;
+        parsed.timeout()?;
+        Ok(parsed)
+    }
+
+    fn timeout(&self) -> Result<Duration> {
+        let seconds = self.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        Duration::try_from_secs_f64(seconds).ok().filter(|t| !t.is_zero()).ok_or_else(|| {
+            ChronoError::PluginError(format!("webhook: timeout_seconds must be positive, got {}", seconds))
+        })
+    }
+}
+
+impl WebhookNotifier {
+    pub fn new() -> Self {
+        Self { client: Client::new() }
+    }
+}
+
+impl Default for WebhookNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn validate(&self, config: &JsonValue) -> Result<()> {
+        WebhookConfig::parse(config).map(|_| ())
+    }
+
+    async fn notify(&self, notification: &Notification, config: &JsonValue) -> Result<()> {
+        let config = WebhookConfig::parse(config)?;
+        let mut request = self.client
+            .post(&config.url)
+            .timeout(config.timeout()?)
+            .json(&notification.to_json());
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await
+            .map_err(|e| ChronoError::PluginError(format!("webhook: POST {} failed: {}", config.url, e)))?;
+        if !response.status().is_success() {
+            return Err(ChronoError::PluginError(format!(
+                "webhook: POST {} returned status {}", config.url, response.status().as_u16()
+            )));
+        }
+        Ok(())
+    }
+}
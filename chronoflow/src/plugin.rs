@@ -1,48 +1,252 @@
-use crate::{ChronoError, Result};
+use crate::{ChronoError, LogLevel, LogLine, Metrics, NotificationRule, Notifier, PluginConfig, Result};
+use crate::wasm::{WasmLimits, WasmRuntime};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
+/// What a plugin knows about the run it is executing.
+#[derive(Debug, Clone)]
+pub struct PluginContext {
+    pub task_id: Uuid,
+    pub execution_id: Uuid,
+    pub attempt: u32,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Cancelled when the run times out or is otherwise abandoned. Plugins that hand
+    /// work to other tasks or processes should watch it and stop that work.
+    pub cancel: CancellationToken,
+    /// Where the plugin logs what it does; kept with the execution.
+    pub log: ExecutionLog,
+}
+
+// Lines kept per execution; later ones are counted but dropped.
+const MAX_LOG_LINES: usize = 1000;
+
+/// An execution's log. Lines are kept for the `TaskExecution` and also emitted as
+/// `tracing` events inside the run's span. Clones write to the same log.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionLog {
+    buffer: Arc<Mutex<LogBuffer>>,
+}
+
+#[derive(Debug, Default)]
+struct LogBuffer {
+    lines: Vec<LogLine>,
+    dropped: usize,
+}
+
+impl ExecutionLog {
+    pub fn debug(&self, message: impl Into<String>) {
+        self.write(LogLevel::Debug, message.into());
+    }
+    
+    pub fn info(&self, message: impl Into<String>) {
+        self.write(LogLevel::Info, message.into());
+    }
+    
+    pub fn warn(&self, message: impl Into<String>) {
+        self.write(LogLevel::Warn, message.into());
+    }
+    
+    pub fn error(&self, message: impl Into<String>) {
+        self.write(LogLevel::Error, message.into());
+    }
+    
+    pub fn write(&self, level: LogLevel, message: String) {
+        match level {
+            LogLevel::Debug => tracing::debug!(target: "chronoflow::plugin", "{}", message),
+            LogLevel::Info => tracing::info!(target: "chronoflow::plugin", "{}", message),
+            LogLevel::Warn => tracing::warn!(target: "chronoflow::plugin", "{}", message),
+            LogLevel::Error => tracing::error!(target: "chronoflow::plugin", "{}", message),
+        }
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.lines.len() < MAX_LOG_LINES {
+            buffer.lines.push(LogLine { at: Utc::now(), level, message });
+        } else {
+            buffer.dropped += 1;
+        }
+    }
+    
+    /// The lines written so far, ending with a note of how many were dropped, if any.
+    pub fn lines(&self) -> Vec<LogLine> {
+        let buffer = self.buffer.lock().unwrap();
+        let mut lines = buffer.lines.clone();
+        if buffer.dropped > 0 {
+            lines.push(LogLine {
+                at: Utc::now(),
+                level: LogLevel::Warn,
+                message: format!("{} more line(s) dropped", buffer.dropped),
+            });
+        }
+        lines
+    }
+}
+
+/// Result of a successful plugin run: a human-readable summary plus structured data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginOutput {
+    pub message: String,
+    #[serde(default)]
+    pub data: JsonValue,
+}
+
+impl PluginOutput {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), data: JsonValue::Null }
+    }
+    
+    pub fn with_data(mut self, data: JsonValue) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    fn name(&self) -> &str;
+    
+    /// JSON Schema describing the `config` object the plugin accepts.
+    fn config_schema(&self) -> JsonValue {
+        serde_json::json!({ "type": "object" })
+    }
+    
+    /// Checks a task's config when the task is registered, before it ever runs.
+    fn validate(&self, _config: &JsonValue) -> Result<()> {
+        Ok(())
+    }
+    
+    async fn execute(&self, ctx: PluginContext, config: &JsonValue) -> Result<PluginOutput>;
+}
+
+/// Registry of native plugins and notifiers, plus the runtime for WASM plugins. A
+/// `PluginConfig` with a non-empty `wasm_path` runs that module; otherwise `name`
+/// selects a native plugin.
 pub struct PluginManager {
-    plugins: HashMap<String, Box<dyn Fn(&JsonValue) -> Result<String> + Send + Sync>>,
+    plugins: RwLock<HashMap<String, Arc<dyn Plugin>>>,
+    notifiers: RwLock<HashMap<String, Arc<dyn Notifier>>>,
+    wasm: WasmRuntime,
+    metrics: Arc<Metrics>,
+}
+
+impl Default for PluginManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PluginManager {
     pub fn new() -> Self {
-        let mut manager = Self {
-            plugins: HashMap::new(),
+        let manager = Self {
+            plugins: RwLock::new(HashMap::new()),
+            notifiers: RwLock::new(HashMap::new()),
+            wasm: WasmRuntime::new(WasmLimits::default()).expect("default wasm engine config is valid"),
+            metrics: Arc::new(Metrics::new()),
         };
         manager.register_builtin_plugins();
         manager
     }
     
-    fn register_builtin_plugins(&mut self) {
-        // HTTP request plugin
-        self.plugins.insert(
-            "http_request".to_string(),
-            Box::new(|config: &JsonValue| {
-                let url = config.get("url")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| ChronoError::PluginError("url required".into()))?;
-                Ok(format!("HTTP request to {} completed", url))
-            })
-        );
-        
-        // Logger plugin
-        self.plugins.insert(
-            "logger".to_string(),
-            Box::new(|config: &JsonValue| {
-                let msg = config.get("message")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("No message");
-                println!("[PLUGIN LOG] {}", msg);
-                Ok(format!("Logged: {}", msg))
-            })
-        );
-    }
-    
-    pub fn execute_plugin(&self, name: &str, config: &JsonValue) -> Result<String> {
-        let plugin = self.plugins.get(name)
-            .ok_or_else(|| ChronoError::PluginError(format!("Plugin {} not found", name)))?;
-        plugin(config)
-    }
-}
\ No newline at end of file
+    fn register_builtin_plugins(&self) {
+        self.register(Arc::new(crate::plugins::HttpRequestPlugin::new()));
+        self.register(Arc::new(crate::plugins::LoggerPlugin));
+        self.register(Arc::new(crate::plugins::ShellPlugin));
+        self.register_notifier(Arc::new(crate::notifiers::FileNotifier));
+        self.register_notifier(Arc::new(crate::notifiers::SmtpNotifier));
+        self.register_notifier(Arc::new(crate::notifiers::WebhookNotifier::new()));
+    }
+    
+    pub fn with_wasm_limits(mut self, limits: WasmLimits) -> Self {
+        self.wasm = self.wasm.with_limits(limits);
+        self
+    }
+    
+    /// Records plugin errors in `metrics`, which schedulers built on this manager share.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+    
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+    
+    /// Adds a plugin, replacing any registered under the same name.
+    pub fn register(&self, plugin: Arc<dyn Plugin>) {
+        self.plugins.write().unwrap().insert(plugin.name().to_string(), plugin);
+    }
+    
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Plugin>> {
+        self.plugins.read().unwrap().get(name)
+            .cloned()
+            .ok_or_else(|| ChronoError::PluginError(format!("Plugin {} not found", name)))
+    }
+    
+    pub fn plugin_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.plugins.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// Adds a notifier, replacing any registered under the same name.
+    pub fn register_notifier(&self, notifier: Arc<dyn Notifier>) {
+        self.notifiers.write().unwrap().insert(notifier.name().to_string(), notifier);
+    }
+    
+    pub fn notifier(&self, name: &str) -> Result<Arc<dyn Notifier>> {
+        self.notifiers.read().unwrap().get(name)
+            .cloned()
+            .ok_or_else(|| ChronoError::PluginError(format!("Notifier {} not found", name)))
+    }
+    
+    pub fn notifier_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.notifiers.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// Checks that the rule's notifier exists and accepts its config, as far as
+    /// placeholders allow.
+    pub fn validate_notification(&self, rule: &NotificationRule) -> Result<()> {
+        let templated = crate::template::check(&rule.config)?;
+        let notifier = self.notifier(&rule.notifier)?;
+        if templated {
+            return Ok(());
+        }
+        notifier.validate(&rule.config)
+    }
+    
+    /// Checks that the plugin exists and accepts its config. A config containing
+    /// placeholders can only be checked for syntax until it is rendered at run time.
+    pub fn validate(&self, plugin: &PluginConfig) -> Result<()> {
+        let templated = crate::template::check(&plugin.config)?;
+        if !plugin.wasm_path.is_empty() {
+            return self.wasm.load(Path::new(&plugin.wasm_path)).map(|_| ());
+        }
+        let handler = self.get(&plugin.name)?;
+        if templated {
+            return Ok(());
+        }
+        handler.validate(&plugin.config)
+    }
+    
+    pub async fn execute(&self, ctx: PluginContext, plugin: &PluginConfig) -> Result<PluginOutput> {
+        let result = if !plugin.wasm_path.is_empty() {
+            self.wasm.execute(Path::new(&plugin.wasm_path), ctx, &plugin.config).await
+        } else {
+            match self.get(&plugin.name) {
+                Ok(handler) => handler.execute(ctx, &plugin.config).await,
+                Err(e) => Err(e),
+            }
+        };
+        if matches!(&result, Err(e) if !matches!(e, ChronoError::Cancelled(_))) {
+            let label = if plugin.wasm_path.is_empty() { &plugin.name } else { &plugin.wasm_path };
+            self.metrics.record_plugin_error(label);
+        }
+        result
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{ChronoError, Plugin, PluginContext, PluginOutput, Result};
+use async_trait::async_trait;
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECONDS: f64 = 30.0;
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 4096;
+
+/// Performs one HTTP request per run.
+///
+/// Config: `url` (required), `method` (default `GET`), `headers` and `query` objects,
+/// `body` (strings are sent as-is, anything else as JSON), `timeout_seconds`,
+/// `expected_status` (list of codes; default any 2xx) and `max_response_bytes`, the
+/// amount of response body kept in the execution output. A response with any other
+/// status fails the run, its status, headers and body still kept in the output.
+pub struct HttpRequestPlugin {
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct RequestConfig {
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    query: BTreeMap<String, JsonValue>,
+    #[serde(default)]
+    body: Option<JsonValue>,
+    #[serde(default)]
+    timeout_seconds: Option<f64>,
+    #[serde(default)]
+    expected_status: Vec<u16>,
+    #[serde(default)]
+    max_response_bytes: Option<usize>,
+}
+
+impl RequestConfig {
+    fn parse(config: &JsonValue) -> Result<Self> {
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| ChronoError::PluginError(format!("http_request: {}", e)))?;
+        reqwest::Url::parse(&parsed.url)
+            .map_err(|e| ChronoError::PluginError(format!("http_request: invalid url '{}': {}", parsed.url, e)))?;
+        parsed.method()?;
+        parsed.timeout()?;
+        Ok(parsed)
+    }
+
+    fn timeout(&self) -> Result<Duration> {
+        let seconds = self.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        Duration::try_from_secs_f64(seconds).ok().filter(|t| !t.is_zero()).ok_or_else(|| {
+            ChronoError::PluginError(format!("http_request: timeout_seconds must be positive, got {}", seconds))
+        })
+    }
+
+    fn method(&self) -> Result<Method> {
+        let method = self.method.as_deref().unwrap_or("GET").to_ascii_uppercase();
+        Method::from_bytes(method.as_bytes())
+            .map_err(|_| ChronoError::PluginError(format!("http_request: invalid method '{}'", method)))
+    }
+
+    fn status_expected(&self, status: u16) -> bool {
+        if self.expected_status.is_empty() {
+            (200..300).contains(&status)
+        } else {
+            self.expected_status.contains(&status)
+        }
+    }
+}
+
+impl HttpRequestPlugin {
+    pub fn new() -> Self {
+        Self { client: Client::new() }
+    }
+
+    async fn send(&self, request: &RequestConfig) -> Result<PluginOutput> {
+        let method = request.method()?;
+        let query: Vec<(&str, String)> = request.query.iter()
+            .map(|(k, v)| (k.as_str(), v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string())))
+            .collect();
+
+        let mut builder = self.client
+            .request(method.clone(), &request.url)
+            .query(&query)
+            .timeout(request.timeout()?);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        builder = match &request.body {
+            Some(JsonValue::String(text)) => builder.body(text.clone()),
+            Some(json) => builder.json(json),
+            None => builder,
+        };
+
+        let mut response = builder.send().await
+            .map_err(|e| ChronoError::PluginError(format!("{} {} failed: {}", method, request.url, e)))?;
+        let status = response.status().as_u16();
+        let headers: BTreeMap<String, String> = response.headers().iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect();
+
+        // Read only as much of the body as will be kept.
+        let limit = request.max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await
+            .map_err(|e| ChronoError::PluginError(format!("{} {} failed reading body: {}", method, request.url, e)))?
+        {
+            let room = limit - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&body).into_owned();
+
+        let output = PluginOutput::new(format!("{} {} returned {}", method, request.url, status)).with_data(
+            serde_json::json!({
+                "status": status,
+                "headers": headers,
+                "body": body,
+                "truncated": truncated,
+            }),
+        );
+        if !request.status_expected(status) {
+            let message = format!("{} {} returned unexpected status {}", method, request.url, status);
+            return Err(ChronoError::PluginFailed(message.clone(), Box::new(PluginOutput { message, ..output })));
+        }
+        Ok(output)
+    }
+}
+
+impl Default for HttpRequestPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Plugin for HttpRequestPlugin {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn config_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string" },
+                "method": { "type": "string" },
+                "headers": { "type": "object", "additionalProperties": { "type": "string" } },
+                "query": { "type": "object" },
+                "body": {},
+                "timeout_seconds": { "type": "number" },
+                "expected_status": { "type": "array", "items": { "type": "integer" } },
+                "max_response_bytes": { "type": "integer" }
+            }
+        })
+    }
+
+    fn validate(&self, config: &JsonValue) -> Result<()> {
+        RequestConfig::parse(config).map(|_| ())
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &JsonValue) -> Result<PluginOutput> {
+        let request = RequestConfig::parse(config)?;
+        ctx.log.info(format!("{} {}", request.method()?, request.url));
+        tokio::select! {
+            result = self.send(&request) => result,
+            _ = ctx.cancel.cancelled() => Err(ChronoError::Cancelled(format!("request to {}", request.url))),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{Plugin, PluginContext, PluginOutput, Result};
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+
+pub struct LoggerPlugin;
+
+#[async_trait]
+impl Plugin for LoggerPlugin {
+    fn name(&self) -> &str {
+        "logger"
+    }
+    
+    fn config_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "properties": { "message": { "type": "string" } }
+        })
+    }
+    
+    async fn execute(&self, ctx: PluginContext, config: &JsonValue) -> Result<PluginOutput> {
+        let msg = config.get("message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("No message");
+        ctx.log.info(msg);
+        Ok(PluginOutput::new(format!("Logged: {}", msg)))
+    }
+}
@@ -0,0 +1,9 @@
+//! Plugins registered by `PluginManager::new`.
+
+mod http;
+mod logger;
+mod shell;
+
+pub use http::HttpRequestPlugin;
+pub use logger::LoggerPlugin;
+pub use shell::ShellPlugin;
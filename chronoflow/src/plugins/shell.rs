@@ -0,0 +1,352 @@
+use crate::{ChronoError, Plugin, PluginContext, PluginOutput, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// How long output is still read once the child is gone, unless the timeout allows
+/// longer. Whatever it started can keep the pipes open after it exits.
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+/// Spawns a process per run and captures its exit code, stdout and stderr.
+///
+/// Config: `argv` (required, no shell is involved), `env`, `clear_env`, `cwd`, `stdin`,
+/// `timeout_seconds`, `max_output_bytes` (per stream) and `limits`, a set of rlimits
+/// applied to the child: `cpu_seconds`, `memory_bytes`, `file_size_bytes`,
+/// `open_files` and `processes`. The child runs in its own process group, which is
+/// killed on timeout or cancellation, or if the run is dropped. It is also killed if
+/// anything the child started still holds the output pipes open once the child has
+/// exited and the deadline, or a second's grace if that is later, has passed. A
+/// non-zero exit fails the run.
+pub struct ShellPlugin;
+
+#[derive(Deserialize)]
+struct ShellConfig {
+    argv: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    clear_env: bool,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    stdin: Option<String>,
+    #[serde(default)]
+    timeout_seconds: Option<f64>,
+    #[serde(default)]
+    max_output_bytes: Option<usize>,
+    #[serde(default)]
+    limits: ResourceLimits,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResourceLimits {
+    cpu_seconds: Option<u64>,
+    memory_bytes: Option<u64>,
+    file_size_bytes: Option<u64>,
+    open_files: Option<u64>,
+    processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Runs in the forked child before exec, so it only makes async-signal-safe calls.
+    #[cfg(unix)]
+    fn apply(&self) -> std::io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_CPU, self.cpu_seconds),
+            (libc::RLIMIT_AS, self.memory_bytes),
+            (libc::RLIMIT_FSIZE, self.file_size_bytes),
+            (libc::RLIMIT_NOFILE, self.open_files),
+            (libc::RLIMIT_NPROC, self.processes),
+        ];
+        for (resource, value) in limits {
+            if let Some(value) = value {
+                let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+                // SAFETY: setrlimit only reads the struct we pass and is async-signal-safe.
+                if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ShellConfig {
+    fn parse(config: &JsonValue) -> Result<Self> {
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| ChronoError::PluginError(format!("shell: {}", e)))?;
+        if parsed.argv.is_empty() {
+            return Err(ChronoError::PluginError("shell: argv must not be empty".into()));
+        }
+        parsed.timeout()?;
+        Ok(parsed)
+    }
+
+    fn timeout(&self) -> Result<Option<Duration>> {
+        self.timeout_seconds.map(|seconds| {
+            Duration::try_from_secs_f64(seconds).ok().filter(|t| !t.is_zero()).ok_or_else(|| {
+                ChronoError::PluginError(format!("shell: timeout_seconds must be positive, got {}", seconds))
+            })
+        }).transpose()
+    }
+}
+
+struct Captured {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+/// Drains `reader` to the end, or until `stop`, so the child never blocks on a full
+/// pipe, keeping only the first `limit` bytes.
+async fn read_capped(mut reader: impl AsyncRead + Unpin, limit: usize, stop: CancellationToken) -> Captured {
+    let mut bytes = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(n) if n > 0 => n,
+                _ => break,
+            },
+            _ = stop.cancelled() => break,
+        };
+        let room = limit - bytes.len();
+        if n > room {
+            truncated = true;
+        }
+        bytes.extend_from_slice(&buf[..n.min(room)]);
+    }
+    Captured { bytes, truncated }
+}
+
+/// The child's process group, `pgid` being the child's pid. Dropped while still armed,
+/// e.g. with the run's future, it kills the group: `kill_on_drop` only reaches the child,
+/// not whatever the child started.
+struct ProcessGroup {
+    pgid: Option<u32>,
+    armed: bool,
+}
+
+impl ProcessGroup {
+    fn new(pgid: Option<u32>) -> Self {
+        Self { pgid, armed: true }
+    }
+
+    /// Kills the group. It can outlive the child, so this still works once the child
+    /// is reaped.
+    fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pgid {
+            // SAFETY: signalling a process group we created has no memory-safety impact.
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+
+    /// Kills the group and the child.
+    fn kill_with(&self, child: &mut Child) {
+        self.kill();
+        let _ = child.start_kill();
+    }
+
+    /// Leaves the group be on drop, once the run has ended on its own terms.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if self.armed {
+            self.kill();
+        }
+    }
+}
+
+enum Ending {
+    Exited(ExitStatus),
+    TimedOut(Duration),
+    Cancelled,
+}
+
+impl ShellPlugin {
+    async fn run(&self, ctx: &PluginContext, config: &ShellConfig) -> Result<PluginOutput> {
+        let mut command = Command::new(&config.argv[0]);
+        command.args(&config.argv[1..]);
+        if config.clear_env {
+            command.env_clear();
+        }
+        command.envs(&config.env)
+            .stdin(if config.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &config.cwd {
+            command.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        {
+            let limits = config.limits;
+            command.process_group(0);
+            // SAFETY: the hook only calls setrlimit, which is async-signal-safe.
+            unsafe {
+                command.pre_exec(move || limits.apply());
+            }
+        }
+
+        ctx.log.info(format!("running {:?}", config.argv));
+        let mut child = command.spawn()
+            .map_err(|e| ChronoError::PluginError(format!("failed to spawn '{}': {}", config.argv[0], e)))?;
+        let mut group = ProcessGroup::new(child.id());
+
+        if let (Some(input), Some(mut stdin)) = (config.stdin.clone(), child.stdin.take()) {
+            tokio::spawn(async move {
+                let _ = stdin.write_all(input.as_bytes()).await;
+            });
+        }
+        let limit = config.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES);
+        let stop_reading = CancellationToken::new();
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stdout = tokio::spawn(read_capped(stdout, limit, stop_reading.clone()));
+        let stderr = tokio::spawn(read_capped(stderr, limit, stop_reading.clone()));
+
+        let timeout = config.timeout()?;
+        // Past what `Instant` can hold is as good as no timeout.
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let expired = async {
+            match deadline {
+                Some(at) => tokio::time::sleep_until(at).await,
+                None => std::future::pending().await,
+            }
+        };
+        let ending = tokio::select! {
+            status = child.wait() => Ending::Exited(status
+                .map_err(|e| ChronoError::PluginError(format!("failed waiting for '{}': {}", config.argv[0], e)))?),
+            _ = expired => Ending::TimedOut(timeout.unwrap_or_default()),
+            _ = ctx.cancel.cancelled() => Ending::Cancelled,
+        };
+        if !matches!(ending, Ending::Exited(_)) {
+            group.kill_with(&mut child);
+            let _ = child.wait().await;
+        }
+
+        // Output is read until the pipes close, which whatever the child started can put
+        // off indefinitely; after an exit, until the deadline or cancellation at most.
+        let grace = Instant::now() + DRAIN_GRACE;
+        let drain_until = match (&ending, deadline) {
+            (Ending::Exited(_), Some(at)) => at.max(grace),
+            _ => grace,
+        };
+        let exited = matches!(ending, Ending::Exited(_));
+        let readers = async { tokio::join!(stdout, stderr) };
+        tokio::pin!(readers);
+        let captured = tokio::select! {
+            captured = &mut readers => Some(captured),
+            _ = tokio::time::sleep_until(drain_until) => None,
+            _ = ctx.cancel.cancelled(), if exited => None,
+        };
+        let (stdout, stderr) = match captured {
+            Some(captured) => captured,
+            None => {
+                ctx.log.warn("output still open after the process ended; killing its process group");
+                group.kill_with(&mut child);
+                stop_reading.cancel();
+                readers.await
+            }
+        };
+        group.disarm();
+        let stdout = stdout.unwrap_or(Captured { bytes: Vec::new(), truncated: false });
+        let stderr = stderr.unwrap_or(Captured { bytes: Vec::new(), truncated: false });
+        let (exit_code, signal) = match &ending {
+            Ending::Exited(status) => (status.code(), exit_signal(status)),
+            _ => (None, None),
+        };
+        let output = PluginOutput::new(String::new()).with_data(serde_json::json!({
+            "exit_code": exit_code,
+            "signal": signal,
+            "stdout": String::from_utf8_lossy(&stdout.bytes),
+            "stderr": String::from_utf8_lossy(&stderr.bytes),
+            "stdout_truncated": stdout.truncated,
+            "stderr_truncated": stderr.truncated,
+        }));
+
+        let program = &config.argv[0];
+        let message = match ending {
+            Ending::Exited(status) if status.success() => {
+                return Ok(PluginOutput { message: format!("{} exited with status 0", program), ..output });
+            }
+            Ending::Exited(_) => match (exit_code, signal) {
+                (Some(code), _) => format!("{} exited with status {}", program, code),
+                (None, Some(signal)) => format!("{} was killed by signal {}", program, signal),
+                (None, None) => format!("{} exited abnormally", program),
+            },
+            Ending::TimedOut(limit) => format!("{} timed out after {:?}", program, limit),
+            Ending::Cancelled => format!("{} was cancelled", program),
+        };
+        Err(ChronoError::PluginFailed(message.clone(), Box::new(PluginOutput { message, ..output })))
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+#[async_trait]
+impl Plugin for ShellPlugin {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn config_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "required": ["argv"],
+            "properties": {
+                "argv": { "type": "array", "items": { "type": "string" }, "minItems": 1 },
+                "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                "clear_env": { "type": "boolean" },
+                "cwd": { "type": "string" },
+                "stdin": { "type": "string" },
+                "timeout_seconds": { "type": "number" },
+                "max_output_bytes": { "type": "integer" },
+                "limits": {
+                    "type": "object",
+                    "properties": {
+                        "cpu_seconds": { "type": "integer" },
+                        "memory_bytes": { "type": "integer" },
+                        "file_size_bytes": { "type": "integer" },
+                        "open_files": { "type": "integer" },
+                        "processes": { "type": "integer" }
+                    }
+                }
+            }
+        })
+    }
+
+    fn validate(&self, config: &JsonValue) -> Result<()> {
+        ShellConfig::parse(config).map(|_| ())
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &JsonValue) -> Result<PluginOutput> {
+        let config = ShellConfig::parse(config)?;
+        self.run(&ctx, &config).await
+    }
+}
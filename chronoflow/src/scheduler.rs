@@ -1,150 +1,1702 @@
-use crate::{Task, TaskExecution, ExecutionStatus, Schedule, PluginManager, Result, ChronoError};
+use crate::{Task, TaskExecution, ExecutionLog, ExecutionStatus, FailureReason, LogLine, Schedule, MisfirePolicy, Metrics, Notification, NotifyOn, OverlapPolicy, PluginManager, PluginContext, PluginOutput, Result, ChronoError, CronExpr};
+use crate::storage::{NullStorage, Storage};
+use crate::template::{NoSecrets, SecretProvider, TemplateContext};
+use crate::workflow::{self, WorkflowRun, WorkflowStatus};
 use chrono::{DateTime, Utc, Duration};
-use std::collections::HashMap;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn, Instrument};
 use uuid::Uuid;
 
-pub struct Scheduler {
+// Upper bound on catch-up runs started for a single task under `MisfirePolicy::FireAll`.
+const MAX_CATCH_UP_RUNS: usize = 100;
+
+// Missed fire times stepped through when planning a task's runs. A task further behind
+// than this, e.g. a short interval after a long downtime, skips ahead to its next fire
+// time after now.
+const MAX_PLANNED_FIRES: usize = 10_000;
+
+// How long runs cancelled by a shutdown get to record their status before whatever is
+// left is marked interrupted directly.
+const INTERRUPT_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+// How long a timed-out or cancelled plugin is still polled after its cancel token fires.
+// Shorter than `INTERRUPT_GRACE`, so a shutdown still records how such runs ended.
+const STOP_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Longest wait between sweeps for executions past `RetentionPolicy::max_age`.
+const RETENTION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Upper bound on runs waiting behind a running one under `OverlapPolicy::Queue`; further
+// runs are skipped.
+const MAX_QUEUED_RUNS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// How late a fire time may be before it counts as missed and the task's
+    /// misfire policy applies.
+    pub misfire_threshold: std::time::Duration,
+    /// Limit for tasks that don't set `timeout_seconds`; `None` lets them run unbounded.
+    pub default_timeout: Option<std::time::Duration>,
+    /// Plugin attempts running at once across all tasks; `None` for no limit. Runs over
+    /// the limit wait as `Queued`.
+    pub max_concurrent_runs: Option<usize>,
+    /// Like `max_concurrent_runs`, per plugin name.
+    pub plugin_concurrency: HashMap<String, usize>,
+    /// How much execution history to keep.
+    pub retention: RetentionPolicy,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            misfire_threshold: std::time::Duration::from_secs(60),
+            default_timeout: None,
+            max_concurrent_runs: None,
+            plugin_concurrency: HashMap::new(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+/// Which finished executions the scheduler forgets, in memory and in storage. Queued
+/// and running executions, and those of unfinished workflow runs, are always kept.
+/// The default keeps everything.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Executions kept per task, newest first.
+    pub max_per_task: Option<usize>,
+    /// Executions that finished longer ago than this are dropped.
+    pub max_age: Option<std::time::Duration>,
+}
+
+impl RetentionPolicy {
+    fn is_unlimited(&self) -> bool {
+        self.max_per_task.is_none() && self.max_age.is_none()
+    }
+}
+
+/// Semaphores enforcing the concurrency limits of a `SchedulerConfig`.
+#[derive(Default)]
+struct ConcurrencyLimits {
+    global: Option<Arc<Semaphore>>,
+    per_plugin: HashMap<String, Arc<Semaphore>>,
+}
+
+impl ConcurrencyLimits {
+    fn new(config: &SchedulerConfig) -> Self {
+        Self {
+            global: config.max_concurrent_runs.map(|n| Arc::new(Semaphore::new(n))),
+            per_plugin: config.plugin_concurrency.iter()
+                .map(|(name, n)| (name.clone(), Arc::new(Semaphore::new(*n))))
+                .collect(),
+        }
+    }
+}
+
+/// A task's runs that are going or waiting, for its overlap policy.
+#[derive(Default)]
+struct TaskRuns {
+    /// First-attempt execution ids of runs in progress, with what cancels them.
+    active: Vec<(Uuid, CancellationToken)>,
+    /// Recorded `Queued` executions waiting for the active runs to finish.
+    queued: VecDeque<(Uuid, PlannedRun)>,
+}
+
+/// Criteria for [`Scheduler::query_executions`]. Unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionFilter {
+    #[serde(default)]
+    pub task_id: Option<Uuid>,
+    #[serde(default)]
+    pub status: Option<ExecutionStatus>,
+    #[serde(default)]
+    pub workflow_run_id: Option<Uuid>,
+    /// Only executions started at or after this time.
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only executions started before this time.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Number of matching executions to skip, newest first.
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl ExecutionFilter {
+    fn matches(&self, execution: &TaskExecution) -> bool {
+        self.task_id.is_none_or(|id| execution.task_id == id)
+            && self.status.is_none_or(|status| execution.status == status)
+            && self.workflow_run_id.is_none_or(|id| execution.workflow_run_id == Some(id))
+            && self.since.is_none_or(|since| execution.started_at >= since)
+            && self.until.is_none_or(|until| execution.started_at < until)
+    }
+}
+
+/// One page of [`Scheduler::query_executions`] results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPage {
+    pub items: Vec<TaskExecution>,
+    /// Matching executions across all pages.
+    pub total: usize,
+}
+
+/// Aggregates over the executions matching a filter, from [`Scheduler::execution_stats`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    /// Matching executions, in any status.
+    pub total: usize,
+    pub succeeded: usize,
+    /// Executions that failed or timed out.
+    pub failed: usize,
+    /// `succeeded` over `succeeded + failed`; `None` before any run has completed.
+    /// Skipped and cancelled runs don't count either way.
+    pub success_rate: Option<f64>,
+    pub p50_duration_ms: Option<u64>,
+    pub p95_duration_ms: Option<u64>,
+    /// The most recent failed or timed-out execution.
+    pub last_failure: Option<TaskExecution>,
+}
+
+/// Coordinates a scheduler with the other nodes of a cluster, e.g.
+/// [`ClusterNode`](crate::cluster::ClusterNode).
+#[async_trait]
+pub trait Coordinator: Send + Sync {
+    /// Whether this node is the one that fires tasks.
+    fn is_leader(&self) -> bool;
+    
+    /// Resolves when leadership may have changed.
+    async fn leadership_changed(&self);
+    
+    /// Waits until every change this node has written to storage so far is held by
+    /// enough of the cluster to survive a change of leader.
+    async fn sync(&self) -> Result<()>;
+    
+    /// Offers a due run to the cluster's shared work queue, for whichever node claims
+    /// it to start with [`Scheduler::run_job`]. Returns false to have this scheduler
+    /// start it itself.
+    fn dispatch(&self, _job: &Job) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// A due run waiting in a cluster's work queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub scheduled_at: DateTime<Utc>,
+    pub catch_up: bool,
+    pub enqueued_at: DateTime<Utc>,
+}
+
+impl Job {
+    pub fn new(task_id: Uuid, scheduled_at: DateTime<Utc>, catch_up: bool) -> Self {
+        Self { id: Uuid::new_v4(), task_id, scheduled_at, catch_up, enqueued_at: Utc::now() }
+    }
+}
+
+/// Pending fire times, earliest first. Entries are never removed in place: one whose
+/// time no longer matches the task's `next_run` is stale and dropped when popped.
+type TimerQueue = BinaryHeap<Reverse<(DateTime<Utc>, Uuid)>>;
+
+/// When each notification rule, keyed by task id and the rule's index, last sent
+/// something.
+type LastNotified = HashMap<(Uuid, usize), DateTime<Utc>>;
+
+/// Shuts down a [`Scheduler`] from elsewhere, e.g. a signal handler.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shared: Shared,
+}
+
+/// What [`ShutdownHandle::shutdown`] did with the runs in progress.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    /// Runs that finished within the grace period.
+    pub drained: usize,
+    /// Runs cancelled at the deadline or still waiting to start, now `Interrupted`.
+    pub interrupted: usize,
+}
+
+/// State shared between the `Scheduler` handle, its timer loop and running executions.
+#[derive(Clone)]
+struct Shared {
     tasks: Arc<Mutex<HashMap<Uuid, Task>>>,
     executions: Arc<Mutex<HashMap<Uuid, TaskExecution>>>,
+    workflow_runs: Arc<Mutex<HashMap<Uuid, WorkflowRun>>>,
+    queue: Arc<Mutex<TimerQueue>>,
+    wakeup: Arc<Notify>,
+    running: Arc<AtomicBool>,
     plugin_manager: Arc<PluginManager>,
+    storage: Arc<dyn Storage>,
+    secrets: Arc<dyn SecretProvider>,
+    coordinator: Option<Arc<dyn Coordinator>>,
+    task_runs: Arc<Mutex<HashMap<Uuid, TaskRuns>>>,
+    limits: Arc<ConcurrencyLimits>,
+    config: Arc<SchedulerConfig>,
+    metrics: Arc<Metrics>,
+    shutdown: CancellationToken,
+    run_finished: Arc<Notify>,
+    notified: Arc<Mutex<LastNotified>>,
+}
+
+pub struct Scheduler {
+    shared: Shared,
 }
 
 impl Scheduler {
+    /// Records metrics into `plugin_manager`'s [`Metrics`].
     pub fn new(plugin_manager: Arc<PluginManager>) -> Self {
+        let metrics = plugin_manager.metrics();
         Self {
-            tasks: Arc::new(Mutex::new(HashMap::new())),
-            executions: Arc::new(Mutex::new(HashMap::new())),
-            plugin_manager,
+            shared: Shared {
+                tasks: Arc::new(Mutex::new(HashMap::new())),
+                executions: Arc::new(Mutex::new(HashMap::new())),
+                workflow_runs: Arc::new(Mutex::new(HashMap::new())),
+                queue: Arc::new(Mutex::new(BinaryHeap::new())),
+                wakeup: Arc::new(Notify::new()),
+                running: Arc::new(AtomicBool::new(false)),
+                plugin_manager,
+                storage: Arc::new(NullStorage),
+                secrets: Arc::new(NoSecrets),
+                coordinator: None,
+                task_runs: Arc::new(Mutex::new(HashMap::new())),
+                limits: Arc::new(ConcurrencyLimits::default()),
+                config: Arc::new(SchedulerConfig::default()),
+                metrics,
+                shutdown: CancellationToken::new(),
+                run_finished: Arc::new(Notify::new()),
+                notified: Arc::new(Mutex::new(HashMap::new())),
+            },
         }
     }
     
-    pub fn add_task(&self, task: Task) -> Uuid {
+    pub fn with_config(mut self, config: SchedulerConfig) -> Self {
+        self.shared.limits = Arc::new(ConcurrencyLimits::new(&config));
+        self.shared.config = Arc::new(config);
+        self
+    }
+    
+    /// Source for `{{ secrets.<name> }}` placeholders in plugin configs.
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretProvider>) -> Self {
+        self.shared.secrets = secrets;
+        self
+    }
+    
+    /// Runs the scheduler as one node of a cluster: only fires tasks while
+    /// `coordinator` says this node leads, and only once due fires are replicated.
+    pub fn with_coordinator(mut self, coordinator: Arc<dyn Coordinator>) -> Self {
+        self.shared.coordinator = Some(coordinator);
+        self
+    }
+    
+    /// Persists tasks and executions to `storage`, first restoring whatever it holds.
+    ///
+    /// Fire times that passed while the scheduler was down are left in place for the
+    /// first tick, where each task's misfire policy decides how to catch up. Executions
+    /// still marked `Running` or `Queued` were cut off by the shutdown and are recorded
+    /// as interrupted; unfinished workflow runs as failed.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Result<Self> {
+        let snapshot = storage.load()?;
+        
+        for task in snapshot.tasks {
+            self.shared.tasks.lock().unwrap().insert(task.id, task.clone());
+            self.shared.schedule(&task);
+        }
+        
+        {
+            let mut executions = self.shared.executions.lock().unwrap();
+            for mut execution in snapshot.executions {
+                if matches!(execution.status, ExecutionStatus::Running | ExecutionStatus::Queued) {
+                    execution.status = ExecutionStatus::Interrupted;
+                    execution.error = Some("scheduler stopped before the execution finished".to_string());
+                    storage.save_execution(&execution)?;
+                }
+                executions.insert(execution.id, execution);
+            }
+        }
+        
+        {
+            let mut workflow_runs = self.shared.workflow_runs.lock().unwrap();
+            for mut run in snapshot.workflow_runs {
+                if !run.is_finished() {
+                    run.finish(WorkflowStatus::Failed);
+                    storage.save_workflow_run(&run)?;
+                }
+                workflow_runs.insert(run.id, run);
+            }
+        }
+        
+        self.shared.storage = storage;
+        Ok(self)
+    }
+    
+    /// Checks a task's own settings and its plugin config without registering it.
+    pub fn validate_task(&self, task: &Task) -> Result<()> {
+        task.validate()?;
+        for rule in &task.notifications {
+            self.shared.plugin_manager.validate_notification(rule)?;
+        }
+        self.shared.plugin_manager.validate(&task.plugin)
+    }
+    
+    /// Registers a task. Its dependencies must already be registered and must not form
+    /// a cycle through it.
+    pub fn add_task(&self, mut task: Task) -> Result<Uuid> {
+        self.validate_task(&task)?;
+        if task.next_run.is_none() {
+            task.next_run = first_run(&task, Utc::now());
+        }
         let id = task.id;
-        self.tasks.lock().unwrap().insert(id, task);
-        id
+        {
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            workflow::check_dependencies(&tasks, &task)?;
+            self.shared.storage.save_task(&task)?;
+            tasks.insert(id, task.clone());
+        }
+        self.shared.schedule(&task);
+        self.shared.storage.flush()?;
+        Ok(id)
     }
     
+    /// Replaces the task with `task.id`, keeping its creation time and run history. The
+    /// next fire time is recomputed if the schedule, timezone or enabled state changed.
+    pub fn update_task(&self, mut task: Task) -> Result<Task> {
+        self.validate_task(&task)?;
+        {
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            let current = tasks.get(&task.id)
+                .ok_or_else(|| ChronoError::TaskNotFound(task.id.to_string()))?;
+            workflow::check_dependencies(&tasks, &task)?;
+            
+            if task.name != current.name {
+                self.shared.metrics.forget_task(&task.id, &current.name);
+            }
+            task.created_at = current.created_at;
+            task.last_run = current.last_run;
+            let timing_changed = task.schedule != current.schedule
+                || task.timezone != current.timezone
+                || task.enabled != current.enabled;
+            task.next_run = if timing_changed { first_run(&task, Utc::now()) } else { current.next_run };
+            
+            self.shared.storage.save_task(&task)?;
+            tasks.insert(task.id, task.clone());
+        }
+        self.shared.schedule(&task);
+        self.shared.storage.flush()?;
+        Ok(task)
+    }
+    
+    /// Pauses or resumes a task's schedule. A resumed task starts again from its next
+    /// fire time after now rather than catching up on the ones it missed while paused.
+    pub fn set_enabled(&self, id: &Uuid, enabled: bool) -> Result<Task> {
+        let task = {
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            let stored = tasks.get_mut(id).ok_or_else(|| ChronoError::TaskNotFound(id.to_string()))?;
+            if stored.enabled == enabled {
+                return Ok(stored.clone());
+            }
+            let mut task = stored.clone();
+            task.enabled = enabled;
+            if enabled {
+                task.next_run = first_run(&task, Utc::now());
+            }
+            self.shared.storage.save_task(&task)?;
+            *stored = task.clone();
+            task
+        };
+        self.shared.schedule(&task);
+        self.shared.storage.flush()?;
+        Ok(task)
+    }
+    
+    /// Starts a run of the task right away, outside its schedule and whether or not it
+    /// is enabled. Returns the new execution's id.
+    pub fn trigger_now(&self, id: &Uuid) -> Result<Uuid> {
+        let task = self.get_task(id)?;
+        self.trigger(&task, None)
+    }
+    
+    /// Starts a run of an enabled task for `event`, which the task's config can refer to
+    /// as `{{ event.* }}`. Returns the new execution's id.
+    pub fn trigger_event(&self, id: &Uuid, event: serde_json::Value) -> Result<Uuid> {
+        let task = self.get_task(id)?;
+        if !task.enabled {
+            return Err(ChronoError::InvalidTask(format!("task '{}' is disabled", task.name)));
+        }
+        self.trigger(&task, Some(event))
+    }
+    
+    /// Publishes `payload` to `topic`, starting a run of every enabled task subscribed
+    /// to it. Their event is `{"source": "topic", "topic", "payload"}`. Returns the new
+    /// executions' ids; publishing to a topic without subscribers is not an error.
+    pub fn publish(&self, topic: &str, payload: serde_json::Value) -> Result<Vec<Uuid>> {
+        let event = serde_json::json!({ "source": "topic", "topic": topic, "payload": payload });
+        self.fire(|schedule| matches!(schedule, Schedule::Topic { name } if name == topic), event)
+    }
+    
+    /// Starts a run of every enabled task listening on webhook `path` with `event`, the
+    /// request as the API received it. Returns the new executions' ids.
+    pub fn fire_webhook(&self, path: &str, event: serde_json::Value) -> Result<Vec<Uuid>> {
+        self.fire(|schedule| schedule.accepts_webhook(path), event)
+    }
+    
+    fn fire(&self, accepts: impl Fn(&Schedule) -> bool, event: serde_json::Value) -> Result<Vec<Uuid>> {
+        let tasks: Vec<Task> = self.shared.tasks.lock().unwrap().values()
+            .filter(|t| t.enabled && accepts(&t.schedule))
+            .cloned()
+            .collect();
+        tasks.iter().map(|task| self.trigger(task, Some(event.clone()))).collect()
+    }
+    
+    fn trigger(&self, task: &Task, event: Option<serde_json::Value>) -> Result<Uuid> {
+        if self.shared.shutdown.is_cancelled() {
+            return Err(ChronoError::ShuttingDown);
+        }
+        if !self.is_leader() {
+            return Err(ChronoError::ConsensusError("only the cluster leader runs tasks".into()));
+        }
+        let now = Utc::now();
+        let source = if event.is_some() { "triggered by an event" } else { "triggered manually" };
+        info!(task_id = %task.id, "Running task: {} ({})", task.name, source);
+        let run = PlannedRun { scheduled_at: now, catch_up: false, workflow_run: self.shared.begin_workflow_run(task) };
+        let execution_id = self.shared.start_execution(task, &run, event);
+        
+        let updated = self.shared.tasks.lock().unwrap().get_mut(&task.id).map(|stored| {
+            stored.last_run = Some(now);
+            stored.clone()
+        });
+        if let Some(updated) = updated {
+            self.shared.storage.save_task(&updated)?;
+        }
+        Ok(execution_id)
+    }
+    
+    /// Removes a task. Tasks that other tasks depend on can't be removed until their
+    /// dependents are.
     pub fn remove_task(&self, id: &Uuid) -> Result<()> {
-        self.tasks.lock().unwrap().remove(id)
-            .ok_or_else(|| ChronoError::TaskNotFound(id.to_string()))?;
-        Ok(())
+        let mut tasks = self.shared.tasks.lock().unwrap();
+        if !tasks.contains_key(id) {
+            return Err(ChronoError::TaskNotFound(id.to_string()));
+        }
+        if let Some(dependent) = workflow::dependents(&tasks, *id).first() {
+            return Err(ChronoError::InvalidTask(format!(
+                "task {} is a dependency of '{}'", id, tasks[dependent].name
+            )));
+        }
+        self.shared.storage.delete_task(id)?;
+        if let Some(task) = tasks.remove(id) {
+            self.shared.metrics.forget_task(id, &task.name);
+        }
+        drop(tasks);
+        self.shared.wakeup.notify_one();
+        self.shared.storage.flush()
     }
     
     pub fn get_task(&self, id: &Uuid) -> Result<Task> {
-        self.tasks.lock().unwrap().get(id)
+        self.shared.tasks.lock().unwrap().get(id)
             .cloned()
             .ok_or_else(|| ChronoError::TaskNotFound(id.to_string()))
     }
     
     pub fn list_tasks(&self) -> Vec<Task> {
-        self.tasks.lock().unwrap().values().cloned().collect()
+        self.shared.tasks.lock().unwrap().values().cloned().collect()
+    }
+    
+    pub fn get_execution(&self, id: &Uuid) -> Option<TaskExecution> {
+        self.shared.executions.lock().unwrap().get(id).cloned()
+    }
+    
+    pub fn list_executions(&self, task_id: &Uuid) -> Vec<TaskExecution> {
+        let mut executions: Vec<TaskExecution> = self.shared.executions.lock().unwrap()
+            .values()
+            .filter(|e| e.task_id == *task_id)
+            .cloned()
+            .collect();
+        executions.sort_by_key(|e| e.started_at);
+        executions
+    }
+    
+    /// Executions matching `filter`, newest first, paginated.
+    pub fn query_executions(&self, filter: &ExecutionFilter) -> ExecutionPage {
+        let mut matching: Vec<TaskExecution> = self.shared.executions.lock().unwrap()
+            .values()
+            .filter(|e| filter.matches(e))
+            .cloned()
+            .collect();
+        matching.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(b.id.cmp(&a.id)));
+        
+        let total = matching.len();
+        let items = matching.into_iter()
+            .skip(filter.offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect();
+        ExecutionPage { items, total }
+    }
+    
+    /// Success rate, duration percentiles and the latest failure of the executions
+    /// matching `filter`. Its `offset` and `limit` are ignored.
+    pub fn execution_stats(&self, filter: &ExecutionFilter) -> ExecutionStats {
+        let executions = self.shared.executions.lock().unwrap();
+        let matching: Vec<&TaskExecution> = executions.values().filter(|e| filter.matches(e)).collect();
+        
+        let succeeded = matching.iter().filter(|e| e.status == ExecutionStatus::Success).count();
+        let failures: Vec<&TaskExecution> = matching.iter()
+            .copied()
+            .filter(|e| matches!(e.status, ExecutionStatus::Failed | ExecutionStatus::Timeout))
+            .collect();
+        let completed = succeeded + failures.len();
+        let mut durations: Vec<u64> = matching.iter()
+            .filter(|e| matches!(e.status, ExecutionStatus::Success | ExecutionStatus::Failed | ExecutionStatus::Timeout))
+            .filter_map(|e| e.duration_ms)
+            .collect();
+        durations.sort_unstable();
+        
+        ExecutionStats {
+            total: matching.len(),
+            succeeded,
+            failed: failures.len(),
+            success_rate: (completed > 0).then(|| succeeded as f64 / completed as f64),
+            p50_duration_ms: percentile(&durations, 50),
+            p95_duration_ms: percentile(&durations, 95),
+            last_failure: failures.into_iter()
+                .max_by(|a, b| a.started_at.cmp(&b.started_at).then(a.id.cmp(&b.id)))
+                .cloned(),
+        }
+    }
+    
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.shared.metrics
+    }
+    
+    /// Current metrics in the Prometheus text format, with the queue gauges refreshed.
+    pub fn gather_metrics(&self) -> String {
+        let scheduled = self.shared.tasks.lock().unwrap()
+            .values()
+            .filter(|t| t.enabled && t.next_run.is_some())
+            .count();
+        let (queued, running) = self.shared.executions.lock().unwrap()
+            .values()
+            .fold((0, 0), |(queued, running), e| match e.status {
+                ExecutionStatus::Queued => (queued + 1, running),
+                ExecutionStatus::Running => (queued, running + 1),
+                _ => (queued, running),
+            });
+        self.shared.metrics.set_queue_depth(scheduled, queued, running);
+        self.shared.metrics.encode()
+    }
+    
+    /// Drops finished executions outside the retention policy; returns how many.
+    /// Happens on its own as runs finish and periodically once started.
+    pub fn prune_history(&self) -> usize {
+        self.shared.prune_history(None)
+    }
+    
+    pub fn get_workflow_run(&self, id: &Uuid) -> Option<WorkflowRun> {
+        self.shared.workflow_runs.lock().unwrap().get(id).cloned()
+    }
+    
+    /// Workflow runs started by `root_task_id` firing, oldest first.
+    pub fn list_workflow_runs(&self, root_task_id: &Uuid) -> Vec<WorkflowRun> {
+        let mut runs: Vec<WorkflowRun> = self.shared.workflow_runs.lock().unwrap()
+            .values()
+            .filter(|r| r.root_task_id == *root_task_id)
+            .cloned()
+            .collect();
+        runs.sort_by_key(|r| r.started_at);
+        runs
+    }
+    
+    /// Starts a run taken from a cluster's work queue. Returns the new execution's id.
+    pub fn run_job(&self, job: &Job) -> Result<Uuid> {
+        let task = self.get_task(&job.task_id)?;
+        if job.catch_up {
+            info!(task_id = %task.id, "Running task: {} (catch-up for {})", task.name, job.scheduled_at);
+        } else {
+            info!(task_id = %task.id, "Running task: {}", task.name);
+        }
+        let run = PlannedRun {
+            scheduled_at: job.scheduled_at,
+            catch_up: job.catch_up,
+            workflow_run: self.shared.begin_workflow_run(&task),
+        };
+        Ok(self.shared.start_execution(&task, &run, None))
     }
     
+    /// Stores a task as replicated from the cluster leader, bypassing validation and
+    /// the storage backend, and reschedules it.
+    pub fn apply_task(&self, task: Task) {
+        self.shared.tasks.lock().unwrap().insert(task.id, task.clone());
+        self.shared.schedule(&task);
+    }
+    
+    /// Drops a task removed on the cluster leader.
+    pub fn apply_task_removal(&self, id: &Uuid) {
+        if let Some(task) = self.shared.tasks.lock().unwrap().remove(id) {
+            self.shared.metrics.forget_task(id, &task.name);
+        }
+        self.shared.wakeup.notify_one();
+    }
+    
+    /// Records an execution reported by the cluster leader.
+    pub fn apply_execution(&self, execution: TaskExecution) {
+        let task_id = execution.task_id;
+        self.shared.executions.lock().unwrap().insert(execution.id, execution);
+        self.shared.prune_history(Some(task_id));
+    }
+    
+    /// Replaces every task with `tasks`, e.g. the cluster's agreed state when this
+    /// node gains or loses leadership, and rebuilds the timer queue from them.
+    pub fn replace_tasks(&self, tasks: Vec<Task>) {
+        {
+            // Same lock order as `pop_due`.
+            let mut queue = self.shared.queue.lock().unwrap();
+            let mut map = self.shared.tasks.lock().unwrap();
+            map.clear();
+            queue.clear();
+            for task in tasks {
+                if let Some(at) = task.next_run {
+                    queue.push(Reverse((at, task.id)));
+                }
+                map.insert(task.id, task);
+            }
+        }
+        self.shared.wakeup.notify_one();
+    }
+    
+    /// Whether this scheduler fires tasks: always, unless it is a cluster follower.
+    pub fn is_leader(&self) -> bool {
+        self.shared.coordinator.as_ref().is_none_or(|c| c.is_leader())
+    }
+    
+    /// A handle that shuts the scheduler down, see [`ShutdownHandle::shutdown`].
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle { shared: self.shared.clone() }
+    }
+    
+    pub fn is_shutting_down(&self) -> bool {
+        self.shared.shutdown.is_cancelled()
+    }
+    
+    /// Whether the timer loop has been started and not shut down.
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::SeqCst)
+    }
+    
+    /// Spawns the timer loop. It sleeps until the earliest `next_run` in the queue and
+    /// is woken early whenever tasks are added or removed.
     pub async fn start(&self) {
-        let tasks = Arc::clone(&self.tasks);
-        let executions = Arc::clone(&self.executions);
-        let plugin_manager = Arc::clone(&self.plugin_manager);
+        let shared = self.shared.clone();
+        shared.running.store(true, Ordering::SeqCst);
+        // History restored from storage may predate the current policy.
+        shared.prune_history(None);
+        
+        if let Some(max_age) = shared.config.retention.max_age {
+            let shared = shared.clone();
+            let period = max_age.clamp(std::time::Duration::from_secs(1), RETENTION_SWEEP_INTERVAL);
+            tokio::spawn(async move {
+                let mut sweep = tokio::time::interval(period);
+                loop {
+                    tokio::select! {
+                        _ = sweep.tick() => shared.prune_history(None),
+                        _ = shared.shutdown.cancelled() => break,
+                    };
+                }
+            });
+        }
         
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
-            
             loop {
-                interval.tick().await;
-                let now = Utc::now();
+                if shared.shutdown.is_cancelled() {
+                    break;
+                }
+                if let Some(coordinator) = &shared.coordinator {
+                    if !coordinator.is_leader() {
+                        // The sleep covers a change that lands before we start waiting.
+                        tokio::select! {
+                            _ = coordinator.leadership_changed() => {}
+                            _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+                            _ = shared.shutdown.cancelled() => {}
+                        }
+                        continue;
+                    }
+                }
                 
-                let task_list: Vec<Task> = tasks.lock().unwrap().values().cloned().collect();
+                let now = Utc::now();
+                let earliest = shared.queue.lock().unwrap().peek().map(|Reverse((at, _))| *at);
                 
-                for mut task in task_list {
-                    if !task.enabled {
-                        continue;
+                match earliest {
+                    Some(at) if at <= now => {
+                        let mut fired = Vec::new();
+                        while let Some(task) = shared.pop_due(now) {
+                            fired.push(shared.advance(&task, now));
+                        }
+                        // In a cluster, a fire only runs once the rest of the cluster has
+                        // recorded it, so a new leader can't run it again.
+                        if shared.replicate().await {
+                            for (task, runs) in &fired {
+                                shared.start_runs(task, runs);
+                            }
+                        }
                     }
-                    
-                    if should_run(&task, now) {
-                        println!("Running task: {}", task.name);
-                        start_execution(&task, &executions, &plugin_manager).await;
-                        task.last_run = Some(now);
-                        task.next_run = calculate_next_run(&task.schedule, now);
-                        tasks.lock().unwrap().insert(task.id, task);
+                    Some(at) => {
+                        let wait = (at - now).to_std().unwrap_or_default();
+                        tokio::select! {
+                            _ = tokio::time::sleep(wait) => {}
+                            _ = shared.wakeup.notified() => {}
+                            _ = shared.shutdown.cancelled() => {}
+                        }
                     }
+                    None => tokio::select! {
+                        _ = shared.wakeup.notified() => {}
+                        _ = shared.shutdown.cancelled() => {}
+                    },
                 }
             }
         });
     }
 }
 
-fn should_run(task: &Task, now: DateTime<Utc>) -> bool {
-    match &task.schedule {
-        Schedule::Once { at } => task.last_run.is_none() && now >= *at,
-        Schedule::Interval { seconds } => {
-            if let Some(last) = task.last_run {
-                now >= last + Duration::seconds(*seconds as i64)
-            } else {
-                true
+impl ShutdownHandle {
+    /// Stops the timer loop and refuses new runs, then waits up to `grace` for the runs
+    /// in progress to finish. Whatever is still running at the deadline is cancelled;
+    /// those runs and the ones waiting to start are recorded as `Interrupted`.
+    pub async fn shutdown(&self, grace: std::time::Duration) -> ShutdownReport {
+        let shared = &self.shared;
+        shared.shutdown.cancel();
+        shared.running.store(false, Ordering::SeqCst);
+        
+        let queued = shared.interrupt_queued();
+        let active = shared.active_runs();
+        let mut left = 0;
+        if !shared.wait_for_runs(tokio::time::Instant::now() + grace).await {
+            let runs: Vec<(Uuid, CancellationToken)> = shared.task_runs.lock().unwrap()
+                .values()
+                .flat_map(|runs| runs.active.iter().cloned())
+                .collect();
+            left = runs.len();
+            warn!("Interrupting {} run(s) still in progress after {:?}", left, grace);
+            for (_, cancel) in &runs {
+                cancel.cancel();
             }
-        },
-        Schedule::Cron(_) => {
-            if let Some(next) = task.next_run {
-                now >= next
-            } else {
-                true
+            // Cancelled runs stop at once unless a plugin blocks its thread; record
+            // whatever is left ourselves.
+            if !shared.wait_for_runs(tokio::time::Instant::now() + INTERRUPT_GRACE).await {
+                for (first_id, _) in &runs {
+                    shared.interrupt_attempts(*first_id);
+                }
             }
         }
+        ShutdownReport { drained: active.saturating_sub(left), interrupted: queued + left }
     }
 }
 
-fn calculate_next_run(schedule: &Schedule, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
-    match schedule {
-        Schedule::Once { .. } => None,
-        Schedule::Interval { seconds } => Some(from + Duration::seconds(*seconds as i64)),
-        Schedule::Cron(_) => Some(from + Duration::seconds(60)),
+impl Shared {
+    /// Queues the task's `next_run` and wakes the timer loop in case it is now the earliest.
+    fn schedule(&self, task: &Task) {
+        if let Some(at) = task.next_run {
+            self.queue.lock().unwrap().push(Reverse((at, task.id)));
+            self.wakeup.notify_one();
+        }
+    }
+    
+    /// Pops the next queue entry due at `now` that still refers to a live, enabled task.
+    fn pop_due(&self, now: DateTime<Utc>) -> Option<Task> {
+        let mut queue = self.queue.lock().unwrap();
+        let tasks = self.tasks.lock().unwrap();
+        while let Some(Reverse((at, id))) = queue.peek().copied() {
+            if at > now {
+                return None;
+            }
+            queue.pop();
+            match tasks.get(&id) {
+                Some(task) if task.enabled && task.next_run == Some(at) => return Some(task.clone()),
+                _ => continue,
+            }
+        }
+        None
+    }
+    
+    /// Works out the runs a due task should start and moves its `next_run` on. The runs
+    /// are started separately, by `start_runs`.
+    fn advance(&self, task: &Task, now: DateTime<Utc>) -> (Task, Vec<PlannedRun>) {
+        let misfire_threshold = Duration::from_std(self.config.misfire_threshold)
+            .unwrap_or_else(|_| Duration::seconds(60));
+        let plan = plan_runs(task, now, misfire_threshold);
+        
+        // The task may have been removed since it was popped.
+        let updated = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.get_mut(&task.id).map(|stored| {
+                if !plan.runs.is_empty() {
+                    stored.last_run = Some(now);
+                }
+                stored.next_run = plan.next_run;
+                stored.clone()
+            })
+        };
+        if let Some(updated) = &updated {
+            if let Err(e) = self.storage.save_task(updated) {
+                error!("Failed to persist task {}: {}", updated.id, e);
+            }
+            self.schedule(updated);
+        }
+        (updated.unwrap_or_else(|| task.clone()), plan.runs)
+    }
+    
+    /// Waits for the coordinator, if any, to replicate everything persisted so far.
+    /// Returns false if this node can no longer vouch for it, e.g. after losing
+    /// leadership.
+    async fn replicate(&self) -> bool {
+        match &self.coordinator {
+            Some(coordinator) => match coordinator.sync().await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Not starting due runs: {}", e);
+                    false
+                }
+            },
+            None => true,
+        }
+    }
+    
+    fn start_runs(&self, task: &Task, runs: &[PlannedRun]) {
+        for run in runs {
+            if let Some(coordinator) = &self.coordinator {
+                match coordinator.dispatch(&Job::new(task.id, run.scheduled_at, run.catch_up)) {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!("Failed to queue task {}: {}", task.name, e);
+                        continue;
+                    }
+                }
+            }
+            if run.catch_up {
+                info!(task_id = %task.id, "Running task: {} (catch-up for {})", task.name, run.scheduled_at);
+            } else {
+                info!(task_id = %task.id, "Running task: {}", task.name);
+            }
+            let run = PlannedRun { workflow_run: self.begin_workflow_run(task), ..*run };
+            self.start_execution(task, &run, None);
+        }
     }
 }
 
-async fn start_execution(
-    task: &Task,
-    executions: &Arc<Mutex<HashMap<Uuid, TaskExecution>>>,
-    plugin_manager: &Arc<PluginManager>,
-) -> Uuid {
-    let exec_id = Uuid::new_v4();
-    let execution = TaskExecution {
-        id: exec_id,
-        task_id: task.id,
-        started_at: Utc::now(),
-        finished_at: None,
-        status: ExecutionStatus::Running,
-        output: None,
-        error: None,
+#[derive(Clone, Copy)]
+struct PlannedRun {
+    scheduled_at: DateTime<Utc>,
+    catch_up: bool,
+    workflow_run: Option<Uuid>,
+}
+
+struct RunPlan {
+    runs: Vec<PlannedRun>,
+    next_run: Option<DateTime<Utc>>,
+}
+
+/// Works out which runs a due task should start at `now`, applying its misfire policy
+/// to fire times more than `threshold` in the past.
+fn plan_runs(task: &Task, now: DateTime<Utc>, threshold: Duration) -> RunPlan {
+    let mut due = VecDeque::new();
+    let mut next = task.next_run;
+    let mut stepped = 0;
+    while let Some(fire) = next.filter(|t| *t <= now) {
+        if due.len() == MAX_CATCH_UP_RUNS {
+            due.pop_front();
+        }
+        due.push_back(fire);
+        // A schedule that doesn't move forward would never catch up.
+        next = calculate_next_run(task, fire).filter(|t| *t > fire);
+        stepped += 1;
+        if stepped == MAX_PLANNED_FIRES {
+            next = next.filter(|t| *t > now).or_else(|| calculate_next_run(task, now));
+            break;
+        }
+    }
+    
+    let (missed, on_time): (Vec<_>, Vec<_>) = due.into_iter().partition(|t| now - *t > threshold);
+    let on_time = on_time.last().map(|t| PlannedRun { scheduled_at: *t, catch_up: false, workflow_run: None });
+    let runs = match (missed.last(), task.misfire_policy) {
+        (None, _) => on_time.into_iter().collect(),
+        (Some(_), MisfirePolicy::FireAll) => missed.iter()
+            .map(|t| PlannedRun { scheduled_at: *t, catch_up: true, workflow_run: None })
+            .chain(on_time)
+            .collect(),
+        (Some(_), MisfirePolicy::Skip) => on_time.into_iter().collect(),
+        (Some(latest), MisfirePolicy::FireOnceNow) => vec![on_time.unwrap_or(PlannedRun {
+            scheduled_at: *latest,
+            catch_up: true,
+            workflow_run: None,
+        })],
+        (Some(latest), MisfirePolicy::FireWithinGrace { grace_seconds }) => {
+            // A grace period too long to represent covers any fire time.
+            let within_grace = i64::try_from(grace_seconds).ok()
+                .and_then(Duration::try_seconds)
+                .is_none_or(|grace| now - *latest <= grace);
+            match on_time {
+                Some(run) => vec![run],
+                None if within_grace => vec![PlannedRun { scheduled_at: *latest, catch_up: true, workflow_run: None }],
+                None => Vec::new(),
+            }
+        }
     };
     
-    executions.lock().unwrap().insert(exec_id, execution.clone());
+    RunPlan { runs, next_run: next }
+}
+
+fn first_run(task: &Task, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match &task.schedule {
+        Schedule::Once { at } => Some(*at),
+        Schedule::Interval { .. } => Some(now),
+        Schedule::Cron(_) => calculate_next_run(task, now),
+        _ => None,
+    }
+}
+
+fn calculate_next_run(task: &Task, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match &task.schedule {
+        Schedule::Interval { seconds } => {
+            let interval = Duration::try_seconds(i64::try_from(*seconds).ok()?)?;
+            from.checked_add_signed(interval)
+        }
+        Schedule::Cron(expr) => {
+            let local = from.with_timezone(&task.timezone);
+            CronExpr::parse(expr).ok()?.next_after_in(&local).map(|t| t.with_timezone(&Utc))
+        }
+        _ => None,
+    }
+}
+
+/// Nearest-rank percentile `p` of `sorted`.
+fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+/// The status `finish_execution` records for `result`.
+fn result_status(result: &Result<PluginOutput>) -> ExecutionStatus {
+    match result {
+        Ok(_) => ExecutionStatus::Success,
+        Err(ChronoError::Timeout(_)) => ExecutionStatus::Timeout,
+        Err(ChronoError::Cancelled(_)) => ExecutionStatus::Cancelled,
+        Err(ChronoError::Interrupted(_)) => ExecutionStatus::Interrupted,
+        Err(_) => ExecutionStatus::Failed,
+    }
+}
+
+impl Shared {
+    fn record_execution(&self, execution: TaskExecution) {
+        if let Err(e) = self.storage.save_execution(&execution) {
+            error!("Failed to persist execution {}: {}", execution.id, e);
+        }
+        let task_id = execution.task_id;
+        self.executions.lock().unwrap().insert(execution.id, execution);
+        self.prune_history(Some(task_id));
+    }
     
-    let task_clone = task.clone();
-    let executions_clone = Arc::clone(executions);
-    let plugin_manager_clone = Arc::clone(plugin_manager);
+    /// Applies the retention policy to `task_id`'s executions, or to every task's.
+    fn prune_history(&self, task_id: Option<Uuid>) -> usize {
+        let policy = &self.config.retention;
+        if policy.is_unlimited() {
+            return 0;
+        }
+        // Later steps of a workflow run read the outputs of its earlier ones.
+        let unfinished_runs: HashSet<Uuid> = self.workflow_runs.lock().unwrap()
+            .values()
+            .filter(|r| !r.is_finished())
+            .map(|r| r.id)
+            .collect();
+        let cutoff = policy.max_age
+            .and_then(|age| Duration::from_std(age).ok())
+            .map(|age| Utc::now() - age);
+        
+        let mut executions = self.executions.lock().unwrap();
+        let mut by_task: HashMap<Uuid, Vec<&TaskExecution>> = HashMap::new();
+        for execution in executions.values() {
+            if task_id.is_none_or(|id| execution.task_id == id)
+                && !matches!(execution.status, ExecutionStatus::Queued | ExecutionStatus::Running)
+                && execution.workflow_run_id.is_none_or(|id| !unfinished_runs.contains(&id))
+            {
+                by_task.entry(execution.task_id).or_default().push(execution);
+            }
+        }
+        
+        let mut expired = Vec::new();
+        for mut finished in by_task.into_values() {
+            finished.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(b.id.cmp(&a.id)));
+            for (index, execution) in finished.into_iter().enumerate() {
+                let too_many = policy.max_per_task.is_some_and(|max| index >= max);
+                let too_old = cutoff.is_some_and(|cutoff| execution.finished_at.unwrap_or(execution.started_at) < cutoff);
+                if too_many || too_old {
+                    expired.push(execution.id);
+                }
+            }
+        }
+        for id in &expired {
+            executions.remove(id);
+        }
+        drop(executions);
+        
+        if !expired.is_empty() {
+            if let Err(e) = self.storage.delete_executions(&expired) {
+                error!("Failed to delete {} expired execution(s): {}", expired.len(), e);
+            }
+        }
+        expired.len()
+    }
     
-    tokio::spawn(async move {
-        let result = plugin_manager_clone.execute_plugin(
-            &task_clone.plugin.name,
-            &task_clone.plugin.config,
+    fn finish_execution(&self, exec_id: Uuid, result: &Result<PluginOutput>, logs: Vec<LogLine>) {
+        let mut execs = self.executions.lock().unwrap();
+        let Some(exec) = execs.get_mut(&exec_id) else {
+            return;
+        };
+        let finished_at = Utc::now();
+        exec.finished_at = Some(finished_at);
+        exec.duration_ms = Some((finished_at - exec.started_at).num_milliseconds().max(0) as u64);
+        
+        exec.status = result_status(result);
+        exec.logs = logs;
+        exec.failure_reason = match result {
+            Ok(_) | Err(ChronoError::Cancelled(_) | ChronoError::Interrupted(_)) => None,
+            Err(ChronoError::Timeout(_)) => Some(FailureReason::Timeout),
+            Err(ChronoError::TemplateError(_)) => Some(FailureReason::Template),
+            Err(_) => Some(FailureReason::Plugin),
+        };
+        match result {
+            Ok(output) => exec.output = Some(output.clone()),
+            Err(e @ ChronoError::PluginFailed(_, output)) => {
+                exec.error = Some(e.to_string());
+                exec.output = Some((**output).clone());
+            }
+            Err(e) => exec.error = Some(e.to_string()),
+        }
+        
+        if let Err(e) = self.storage.save_execution(exec) {
+            error!("Failed to persist execution {}: {}", exec_id, e);
+        }
+        let (task_id, status, duration) = (exec.task_id, exec.status, exec.duration_ms);
+        drop(execs);
+        self.metrics.record_execution(
+            &task_id,
+            &self.task_name(&task_id),
+            status,
+            duration.map(std::time::Duration::from_millis),
         );
+        self.prune_history(Some(task_id));
+    }
+    
+    /// The task's name for metric labels; empty once the task is removed.
+    fn task_name(&self, task_id: &Uuid) -> String {
+        self.tasks.lock().unwrap().get(task_id).map(|t| t.name.clone()).unwrap_or_default()
+    }
+    
+    /// The `task`, `run`, `upstream` and `event` values placeholders in the task's
+    /// config can refer to.
+    fn template_values(&self, task: &Task, execution: &TaskExecution) -> serde_json::Value {
+        let mut upstream = serde_json::Map::new();
+        if let Some(run_id) = execution.workflow_run_id {
+            let run_executions = self.workflow_runs.lock().unwrap()
+                .get(&run_id)
+                .map(|r| r.executions.clone())
+                .unwrap_or_default();
+            let tasks = self.tasks.lock().unwrap();
+            let executions = self.executions.lock().unwrap();
+            // Later attempts overwrite earlier ones, leaving each task's final result.
+            for upstream_exec in run_executions.iter().filter_map(|id| executions.get(id)) {
+                if upstream_exec.task_id == task.id || upstream_exec.finished_at.is_none() {
+                    continue;
+                }
+                let Some(upstream_task) = tasks.get(&upstream_exec.task_id) else {
+                    continue;
+                };
+                let output = upstream_exec.output.as_ref();
+                upstream.insert(upstream_task.name.clone(), serde_json::json!({
+                    "status": upstream_exec.status,
+                    "message": output.map(|o| o.message.clone()),
+                    "output": output.map(|o| o.data.clone()),
+                    "error": upstream_exec.error,
+                    "execution_id": upstream_exec.id,
+                    "finished_at": upstream_exec.finished_at,
+                }));
+            }
+        }
+        
+        serde_json::json!({
+            "task": { "id": task.id, "name": task.name },
+            "run": {
+                "execution_id": execution.id,
+                "scheduled_at": execution.scheduled_at,
+                "attempt": execution.attempt,
+                "catch_up": execution.catch_up,
+                "workflow_run_id": execution.workflow_run_id,
+            },
+            "upstream": upstream,
+            "event": execution.event,
+        })
+    }
+    
+    /// Runs one attempt of the task's plugin, bounded by the task's timeout or the
+    /// scheduler default. An overrunning plugin is cancelled and reported as a timeout.
+    /// Placeholders in the plugin config are rendered first; if that fails the plugin
+    /// doesn't run. Cancelling `run` stops the attempt. Either way a stopped plugin sees
+    /// its cancel token fire and has `STOP_GRACE` to wind down. The plugin writes to `log`.
+    async fn run_plugin(
+        &self,
+        task: &Task,
+        execution: &TaskExecution,
+        run: &CancellationToken,
+        log: &ExecutionLog,
+    ) -> Result<PluginOutput> {
+        let mut plugin = task.plugin.clone();
+        if crate::template::check(&plugin.config)? {
+            let context = TemplateContext::new(self.template_values(task, execution), self.secrets.as_ref());
+            plugin.config = context.render(&plugin.config)?;
+        }
         
-        let mut execs = executions_clone.lock().unwrap();
+        let cancel = run.child_token();
+        let ctx = PluginContext {
+            task_id: task.id,
+            execution_id: execution.id,
+            attempt: execution.attempt,
+            scheduled_at: execution.scheduled_at,
+            cancel: cancel.clone(),
+            log: log.clone(),
+        };
+        let limit = task.timeout_seconds.map(std::time::Duration::from_secs).or(self.config.default_timeout);
+        let expired = async {
+            match limit {
+                Some(limit) => {
+                    tokio::time::sleep(limit).await;
+                    limit
+                }
+                None => std::future::pending().await,
+            }
+        };
+        let call = self.plugin_manager.execute(ctx, &plugin);
+        tokio::pin!(call);
+        // `run` first: cancelling it cancels the plugin's token too, and the plugin
+        // returning early because of that shouldn't hide why it was stopped.
+        let stopped = tokio::select! {
+            biased;
+            _ = run.cancelled() => Err(self.stop_reason()),
+            result = &mut call => return result,
+            limit = expired => {
+                log.warn(format!("timed out after {:?}", limit));
+                Err(ChronoError::Timeout(limit))
+            }
+        };
+        // The plugin only sees the cancellation while it is still polled, so it gets a
+        // moment to clean up, e.g. kill what it spawned, before it is dropped.
+        cancel.cancel();
+        let _ = tokio::time::timeout(STOP_GRACE, &mut call).await;
+        stopped
+    }
+    
+    /// Waits for a slot under the plugin's and the global concurrency limits, showing
+    /// the execution as `Queued` meanwhile. The slots are freed when the permits drop.
+    async fn acquire_slots(
+        &self,
+        task: &Task,
+        exec_id: Uuid,
+        run: &CancellationToken,
+    ) -> Result<Vec<OwnedSemaphorePermit>> {
+        // Narrower limit first, so a run doesn't hold a global slot while it waits.
+        let semaphores = self.limits.per_plugin.get(&task.plugin.name).into_iter()
+            .chain(self.limits.global.as_ref());
+        let mut permits = Vec::new();
+        let mut waited = false;
+        for semaphore in semaphores {
+            match semaphore.clone().try_acquire_owned() {
+                Ok(permit) => permits.push(permit),
+                Err(_) => {
+                    if !waited {
+                        waited = true;
+                        self.update_execution(exec_id, |e| e.status = ExecutionStatus::Queued);
+                    }
+                    tokio::select! {
+                        permit = semaphore.clone().acquire_owned() => {
+                            permits.push(permit.expect("limit semaphores are never closed"));
+                        }
+                        _ = run.cancelled() => return Err(self.stop_reason()),
+                    }
+                }
+            }
+        }
+        if waited {
+            self.update_execution(exec_id, |e| {
+                e.status = ExecutionStatus::Running;
+                e.started_at = Utc::now();
+            });
+        }
+        Ok(permits)
+    }
+    
+    fn update_execution(&self, exec_id: Uuid, change: impl FnOnce(&mut TaskExecution)) {
+        let mut execs = self.executions.lock().unwrap();
         if let Some(exec) = execs.get_mut(&exec_id) {
-            exec.finished_at = Some(Utc::now());
+            change(exec);
+            if let Err(e) = self.storage.save_execution(exec) {
+                error!("Failed to persist execution {}: {}", exec_id, e);
+            }
+        }
+    }
+    
+    /// Starts a workflow run rooted at `task` if any tasks depend on it.
+    fn begin_workflow_run(&self, task: &Task) -> Option<Uuid> {
+        let tasks = self.tasks.lock().unwrap();
+        if workflow::dependents(&tasks, task.id).is_empty() {
+            return None;
+        }
+        let run = WorkflowRun::new(task.id, &tasks);
+        let id = run.id;
+        self.save_workflow_run(&run);
+        self.workflow_runs.lock().unwrap().insert(id, run);
+        Some(id)
+    }
+    
+    fn save_workflow_run(&self, run: &WorkflowRun) {
+        if let Err(e) = self.storage.save_workflow_run(run) {
+            error!("Failed to persist workflow run {}: {}", run.id, e);
+        }
+    }
+    
+    fn record_execution_in_workflow(&self, run_id: Uuid, exec_id: Uuid) {
+        if let Some(run) = self.workflow_runs.lock().unwrap().get_mut(&run_id) {
+            run.executions.push(exec_id);
+            self.save_workflow_run(run);
+        }
+    }
+    
+    /// Records the final outcome of `task_id` in a workflow run and starts whichever
+    /// downstream tasks that unblocks.
+    fn complete_workflow_step(&self, run_id: Uuid, task_id: Uuid, status: ExecutionStatus) {
+        let ready: Vec<Task> = {
+            let tasks = self.tasks.lock().unwrap();
+            let mut runs = self.workflow_runs.lock().unwrap();
+            let Some(run) = runs.get_mut(&run_id) else {
+                return;
+            };
+            let ready = run.complete_step(task_id, status, &tasks);
+            self.save_workflow_run(run);
+            if run.is_finished() {
+                info!("Workflow run {} finished: {:?}", run_id, run.status);
+            }
+            ready.iter().filter_map(|id| tasks.get(id).cloned()).collect()
+        };
+        
+        let now = Utc::now();
+        for task in ready {
+            info!(task_id = %task.id, "Running task: {} (workflow run {})", task.name, run_id);
+            let run = PlannedRun { scheduled_at: now, catch_up: false, workflow_run: Some(run_id) };
+            self.start_execution(&task, &run, None);
+        }
+    }
+    
+    /// Records the first attempt of `run` and, as the task's overlap policy allows,
+    /// spawns it, queues it behind the task's running executions or skips it.
+    fn start_execution(&self, task: &Task, run: &PlannedRun, event: Option<serde_json::Value>) -> Uuid {
+        let mut first = TaskExecution::new(task.id, run.scheduled_at, run.catch_up);
+        first.workflow_run_id = run.workflow_run;
+        first.event = event;
+        let first_id = first.id;
+        
+        if self.shutdown.is_cancelled() {
+            info!(task_id = %task.id, "Not running task: {} (shutting down)", task.name);
+            first.status = ExecutionStatus::Interrupted;
+            first.finished_at = Some(Utc::now());
+            first.duration_ms = Some(0);
+            first.error = Some(interrupted().to_string());
+            self.metrics.record_execution(&task.id, &task.name, ExecutionStatus::Interrupted, None);
+            self.record_first_attempt(first);
+            if let Some(run_id) = run.workflow_run {
+                self.complete_workflow_step(run_id, task.id, ExecutionStatus::Interrupted);
+            }
+            return first_id;
+        }
+        
+        enum Admission {
+            Start(CancellationToken),
+            Queue,
+            Skip(Uuid),
+        }
+        let admission = {
+            let mut task_runs = self.task_runs.lock().unwrap();
+            let runs = task_runs.entry(task.id).or_default();
+            let earlier = runs.active.first().map(|(id, _)| *id)
+                .or_else(|| runs.queued.back().map(|(id, _)| *id));
+            match (earlier, task.overlap_policy) {
+                (Some(earlier), OverlapPolicy::Skip) => Admission::Skip(earlier),
+                (Some(earlier), OverlapPolicy::Queue) if runs.queued.len() >= MAX_QUEUED_RUNS => Admission::Skip(earlier),
+                (Some(_), OverlapPolicy::Queue) => {
+                    runs.queued.push_back((first_id, *run));
+                    Admission::Queue
+                }
+                (earlier, policy) => {
+                    if earlier.is_some() && policy == OverlapPolicy::CancelPrevious {
+                        for (_, cancel) in &runs.active {
+                            cancel.cancel();
+                        }
+                    }
+                    let cancel = CancellationToken::new();
+                    runs.active.push((first_id, cancel.clone()));
+                    Admission::Start(cancel)
+                }
+            }
+        };
+        
+        match admission {
+            Admission::Start(cancel) => {
+                self.record_first_attempt(first.clone());
+                self.spawn_execution(task.clone(), *run, first, cancel);
+            }
+            Admission::Queue => {
+                info!(task_id = %task.id, "Queued task: {} (waiting for its previous run)", task.name);
+                first.status = ExecutionStatus::Queued;
+                self.record_first_attempt(first);
+            }
+            Admission::Skip(earlier) => {
+                info!(task_id = %task.id, "Skipped task: {} (previous run still in progress)", task.name);
+                let now = Utc::now();
+                first.status = ExecutionStatus::Skipped;
+                first.finished_at = Some(now);
+                first.duration_ms = Some(0);
+                first.error = Some(format!("skipped: execution {} of this task is still in progress", earlier));
+                self.metrics.record_execution(&task.id, &task.name, ExecutionStatus::Skipped, None);
+                self.record_first_attempt(first);
+                if let Some(run_id) = run.workflow_run {
+                    self.complete_workflow_step(run_id, task.id, ExecutionStatus::Skipped);
+                }
+            }
+        }
+        first_id
+    }
+    
+    fn record_first_attempt(&self, execution: TaskExecution) {
+        let (exec_id, workflow_run) = (execution.id, execution.workflow_run_id);
+        self.record_execution(execution);
+        if let Some(run_id) = workflow_run {
+            self.record_execution_in_workflow(run_id, exec_id);
+        }
+    }
+    
+    /// Runs an admitted execution in the background. Failed attempts are retried
+    /// according to the task's retry policy, each as its own execution linked to the
+    /// first. Once the last attempt finishes, the workflow run it belongs to moves on
+    /// and the task's next queued run starts.
+    fn spawn_execution(&self, task: Task, run: PlannedRun, first: TaskExecution, cancel: CancellationToken) {
+        let shared = self.clone();
+        let first_id = first.id;
+        let span = tracing::info_span!("task_run", task_id = %task.id, task = %task.name, execution_id = %first_id);
+        
+        tokio::spawn(async move {
+            let mut execution = first;
+            let started = Utc::now();
+            let sla_timers = shared.start_sla_timers(&task, first_id, started);
+            let previous_failed = task.notifications.iter().any(|r| r.on == NotifyOn::Recovery)
+                && shared.previous_run_failed(task.id, first_id);
             
-            match result {
-                Ok(output) => {
-                    exec.status = ExecutionStatus::Success;
-                    exec.output = Some(output);
-                },
-                Err(e) => {
-                    exec.status = ExecutionStatus::Failed;
-                    exec.error = Some(e.to_string());
+            let status = loop {
+                let log = ExecutionLog::default();
+                let attempt = tracing::info_span!("attempt", execution_id = %execution.id, attempt = execution.attempt);
+                let result = async {
+                    let _permits = shared.acquire_slots(&task, execution.id, &cancel).await?;
+                    if let (1, Some(scheduled_at)) = (execution.attempt, execution.scheduled_at) {
+                        let lag = (Utc::now() - scheduled_at).to_std().unwrap_or_default();
+                        shared.metrics.record_lag(&task.id, &task.name, lag);
+                    }
+                    shared.run_plugin(&task, &execution, &cancel, &log).await
+                }.instrument(attempt).await;
+                if let Err(e) = &result {
+                    warn!("Task {} failed: {}", task.name, e);
+                }
+                shared.finish_execution(execution.id, &result, log.lines());
+                
+                let delay = match (&result, &task.retry) {
+                    // A config that failed to render would fail the same way again.
+                    (Err(ChronoError::TemplateError(_) | ChronoError::Cancelled(_) | ChronoError::Interrupted(_)), _) => {
+                        break result_status(&result)
+                    }
+                    (Err(e), Some(policy)) if policy.should_retry(execution.attempt, &e.to_string()) => {
+                        policy.delay_for(execution.attempt)
+                    }
+                    _ => break result_status(&result),
+                };
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = cancel.cancelled() => break result_status(&result),
                 }
+                
+                // Don't retry tasks that were removed or disabled in the meantime.
+                if !shared.tasks.lock().unwrap().get(&task.id).is_some_and(|t| t.enabled) {
+                    break result_status(&result);
+                }
+                
+                let attempt = execution.attempt + 1;
+                info!("Retrying task: {} (attempt {})", task.name, attempt);
+                let event = execution.event.take();
+                execution = TaskExecution::new(task.id, run.scheduled_at, run.catch_up);
+                execution.attempt = attempt;
+                execution.event = event;
+                execution.retry_of = Some(first_id);
+                execution.workflow_run_id = run.workflow_run;
+                shared.record_execution(execution.clone());
+                if let Some(run_id) = run.workflow_run {
+                    shared.record_execution_in_workflow(run_id, execution.id);
+                }
+            };
+            
+            for timer in sla_timers {
+                timer.abort();
             }
+            shared.notify_outcome(&task, first_id, execution.id, status, started, previous_failed);
+            if let Some(run_id) = run.workflow_run {
+                shared.complete_workflow_step(run_id, task.id, status);
+            }
+            shared.finish_run(task.id, first_id);
+            shared.run_finished.notify_waiters();
+        }.instrument(span));
+    }
+    
+    /// Whether the task's latest finished run other than `first_id`'s failed.
+    fn previous_run_failed(&self, task_id: Uuid, first_id: Uuid) -> bool {
+        let executions = self.executions.lock().unwrap();
+        executions.values()
+            .filter(|e| e.task_id == task_id && e.id != first_id && e.retry_of != Some(first_id))
+            .filter(|e| matches!(e.status, ExecutionStatus::Success | ExecutionStatus::Failed | ExecutionStatus::Timeout))
+            .max_by_key(|e| e.finished_at)
+            .is_some_and(|e| e.status != ExecutionStatus::Success)
+    }
+    
+    /// Starts a timer per distinct SLA in the task's rules that notifies if the run is
+    /// still going when it expires. The run aborts the timers once it finishes.
+    fn start_sla_timers(&self, task: &Task, first_id: Uuid, started: DateTime<Utc>) -> Vec<tokio::task::JoinHandle<()>> {
+        let mut slas: Vec<u64> = task.notifications.iter()
+            .filter_map(|rule| match rule.on {
+                NotifyOn::SlaMiss { seconds } => Some(seconds),
+                _ => None,
+            })
+            .collect();
+        slas.sort_unstable();
+        slas.dedup();
+        slas.into_iter()
+            .map(|seconds| {
+                let (shared, task) = (self.clone(), task.clone());
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+                    let now = Utc::now();
+                    shared.notify(&task, Notification {
+                        event: NotifyOn::SlaMiss { seconds },
+                        task_id: task.id,
+                        task: task.name.clone(),
+                        execution_id: first_id,
+                        status: ExecutionStatus::Running,
+                        error: None,
+                        duration_ms: (now - started).num_milliseconds().max(0) as u64,
+                        at: now,
+                    });
+                }.in_current_span())
+            })
+            .collect()
+    }
+    
+    /// Sends the success, recovery or failure notifications for a finished run.
+    /// Skipped, cancelled and interrupted runs notify nobody.
+    fn notify_outcome(
+        &self,
+        task: &Task,
+        first_id: Uuid,
+        last_id: Uuid,
+        status: ExecutionStatus,
+        started: DateTime<Utc>,
+        previous_failed: bool,
+    ) {
+        let events: &[NotifyOn] = match status {
+            ExecutionStatus::Success if previous_failed => &[NotifyOn::Success, NotifyOn::Recovery],
+            ExecutionStatus::Success => &[NotifyOn::Success],
+            ExecutionStatus::Failed | ExecutionStatus::Timeout => &[NotifyOn::Failure],
+            _ => return,
+        };
+        if !task.notifications.iter().any(|rule| events.contains(&rule.on)) {
+            return;
+        }
+        let error = self.executions.lock().unwrap().get(&last_id).and_then(|e| e.error.clone());
+        let now = Utc::now();
+        for event in events {
+            self.notify(task, Notification {
+                event: *event,
+                task_id: task.id,
+                task: task.name.clone(),
+                execution_id: first_id,
+                status,
+                error: error.clone(),
+                duration_ms: (now - started).num_milliseconds().max(0) as u64,
+                at: now,
+            });
+        }
+    }
+    
+    /// Sends `notification` through each of the task's rules for its event, in the
+    /// background. A rule that sent something within its dedup window is skipped.
+    fn notify(&self, task: &Task, notification: Notification) {
+        for (index, rule) in task.notifications.iter().enumerate() {
+            if rule.on != notification.event {
+                continue;
+            }
+            {
+                let mut notified = self.notified.lock().unwrap();
+                let recent = notified.get(&(task.id, index))
+                    .and_then(|last| u64::try_from((notification.at - *last).num_seconds()).ok())
+                    .is_some_and(|elapsed| elapsed < rule.dedup_seconds);
+                if recent {
+                    info!(task_id = %task.id, "Not sending '{}' again via {} (deduplicated)", notification.summary(), rule.notifier);
+                    self.metrics.record_notification(&rule.notifier, "deduplicated");
+                    continue;
+                }
+                notified.insert((task.id, index), notification.at);
+            }
+            
+            let values = serde_json::json!({
+                "task": { "id": task.id, "name": task.name },
+                "notification": notification.to_json(),
+            });
+            let config = TemplateContext::new(values, self.secrets.as_ref()).render(&rule.config);
+            let (shared, notifier, notification) = (self.clone(), rule.notifier.clone(), notification.clone());
+            tokio::spawn(async move {
+                let result = match (config, shared.plugin_manager.notifier(&notifier)) {
+                    (Ok(config), Ok(handler)) => handler.notify(&notification, &config).await,
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                };
+                match result {
+                    Ok(()) => shared.metrics.record_notification(&notifier, "sent"),
+                    Err(e) => {
+                        warn!("Failed to send '{}' via {}: {}", notification.summary(), notifier, e);
+                        shared.metrics.record_notification(&notifier, "failed");
+                    }
+                }
+            }.in_current_span());
+        }
+    }
+    
+    /// Forgets a finished run and, once none of the task's runs are active, starts the
+    /// next queued one.
+    fn finish_run(&self, task_id: Uuid, first_id: Uuid) {
+        let next = {
+            let mut task_runs = self.task_runs.lock().unwrap();
+            let Some(runs) = task_runs.get_mut(&task_id) else {
+                return;
+            };
+            runs.active.retain(|(id, _)| *id != first_id);
+            if !runs.active.is_empty() {
+                return;
+            }
+            match runs.queued.pop_front() {
+                Some((exec_id, run)) => {
+                    let cancel = CancellationToken::new();
+                    runs.active.push((exec_id, cancel.clone()));
+                    (exec_id, run, cancel)
+                }
+                None => {
+                    task_runs.remove(&task_id);
+                    return;
+                }
+            }
+        };
+        
+        let (exec_id, run, cancel) = next;
+        let task = self.tasks.lock().unwrap().get(&task_id).cloned();
+        let Some(task) = task else {
+            // The task was removed while the run waited.
+            self.finish_execution(exec_id, &Err(ChronoError::Cancelled("task was removed".into())), Vec::new());
+            if let Some(run_id) = run.workflow_run {
+                self.complete_workflow_step(run_id, task_id, ExecutionStatus::Cancelled);
+            }
+            self.finish_run(task_id, exec_id);
+            return;
+        };
+        info!(task_id = %task.id, "Running task: {} (queued)", task.name);
+        self.update_execution(exec_id, |e| {
+            e.status = ExecutionStatus::Running;
+            e.started_at = Utc::now();
+        });
+        let Some(first) = self.executions.lock().unwrap().get(&exec_id).cloned() else {
+            self.finish_run(task_id, exec_id);
+            return;
+        };
+        self.spawn_execution(task, run, first, cancel);
+    }
+    
+    /// Why a run stopped when its cancellation token fired.
+    fn stop_reason(&self) -> ChronoError {
+        if self.shutdown.is_cancelled() {
+            interrupted()
+        } else {
+            ChronoError::Cancelled("superseded by a newer run of the task".into())
         }
-    });
+    }
+    
+    fn active_runs(&self) -> usize {
+        self.task_runs.lock().unwrap().values().map(|runs| runs.active.len()).sum()
+    }
     
-    exec_id
-}
\ No newline at end of file
+    /// Waits until no runs are active or `deadline` passes; returns whether they all finished.
+    async fn wait_for_runs(&self, deadline: tokio::time::Instant) -> bool {
+        loop {
+            let finished = self.run_finished.notified();
+            tokio::pin!(finished);
+            finished.as_mut().enable();
+            if self.active_runs() == 0 {
+                return true;
+            }
+            tokio::select! {
+                _ = finished => {}
+                _ = tokio::time::sleep_until(deadline) => return false,
+            }
+        }
+    }
+    
+    /// Records every queued run as interrupted; returns how many there were.
+    fn interrupt_queued(&self) -> usize {
+        let queued: Vec<(Uuid, Uuid, PlannedRun)> = {
+            let mut task_runs = self.task_runs.lock().unwrap();
+            let queued = task_runs.iter_mut()
+                .flat_map(|(task_id, runs)| runs.queued.drain(..).map(|(id, run)| (*task_id, id, run)))
+                .collect();
+            task_runs.retain(|_, runs| !runs.active.is_empty());
+            queued
+        };
+        for (task_id, exec_id, run) in &queued {
+            self.finish_execution(*exec_id, &Err(interrupted()), Vec::new());
+            if let Some(run_id) = run.workflow_run {
+                self.complete_workflow_step(run_id, *task_id, ExecutionStatus::Interrupted);
+            }
+        }
+        queued.len()
+    }
+    
+    /// Records the unfinished attempts of the run started as `first_id` as interrupted.
+    fn interrupt_attempts(&self, first_id: Uuid) {
+        let unfinished: Vec<Uuid> = self.executions.lock().unwrap()
+            .values()
+            .filter(|e| e.id == first_id || e.retry_of == Some(first_id))
+            .filter(|e| matches!(e.status, ExecutionStatus::Queued | ExecutionStatus::Running))
+            .map(|e| e.id)
+            .collect();
+        for exec_id in unfinished {
+            self.finish_execution(exec_id, &Err(interrupted()), Vec::new());
+        }
+    }
+}
+
+fn interrupted() -> ChronoError {
+    ChronoError::Interrupted("the scheduler shut down".into())
+}
@@ -0,0 +1,51 @@
+//! Consistent hashing of tasks onto cluster nodes.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Points each node gets on the ring; more points spread tasks more evenly.
+pub const DEFAULT_VNODES: usize = 64;
+
+/// Maps task ids to the nodes that own them. Adding or removing a node only moves
+/// the tasks that hash next to its points, about `1/n` of them.
+#[derive(Debug, Clone, Default)]
+pub struct HashRing {
+    points: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    pub fn new<I, S>(nodes: I, vnodes: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut points = BTreeMap::new();
+        for node in nodes {
+            let node = node.as_ref();
+            for i in 0..vnodes.max(1) {
+                points.insert(hash(format!("{}#{}", node, i).as_bytes()), node.to_string());
+            }
+        }
+        Self { points }
+    }
+
+    /// The node owning `task_id`: the first point at or after its hash, wrapping
+    /// around. `None` only for an empty ring.
+    pub fn owner(&self, task_id: &Uuid) -> Option<&str> {
+        let key = hash(task_id.as_bytes());
+        self.points.range(key..)
+            .chain(self.points.iter())
+            .next()
+            .map(|(_, node)| node.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let digest = Sha256::digest(bytes);
+    u64::from_be_bytes(digest[..8].try_into().expect("sha256 digests are 32 bytes"))
+}
@@ -0,0 +1,277 @@
+use crate::cluster::{RaftLog, RaftRecord};
+use crate::{ChronoError, Result, Task, TaskExecution, WorkflowRun};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The smallest log [`FileStorage`] compacts while running, by default.
+const DEFAULT_COMPACTION_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Everything a storage backend knows about, as returned by [`Storage::load`].
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub tasks: Vec<Task>,
+    pub executions: Vec<TaskExecution>,
+    pub workflow_runs: Vec<WorkflowRun>,
+    /// A cluster node's Raft state, if one was saved.
+    pub raft: Option<RaftLog>,
+}
+
+/// Durable home for tasks, their execution history and workflow runs.
+pub trait Storage: Send + Sync {
+    fn save_task(&self, task: &Task) -> Result<()>;
+    fn delete_task(&self, id: &Uuid) -> Result<()>;
+    fn save_execution(&self, execution: &TaskExecution) -> Result<()>;
+    /// Forgets executions dropped by the scheduler's retention policy.
+    fn delete_executions(&self, ids: &[Uuid]) -> Result<()>;
+    fn save_workflow_run(&self, run: &WorkflowRun) -> Result<()>;
+    fn load(&self) -> Result<Snapshot>;
+
+    /// Waits until every change saved so far is durable. The scheduler calls it after
+    /// changing a task, outside its locks, and fails the change if this fails. Backends
+    /// that write synchronously have nothing to wait for.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Durably records a change to a cluster node's Raft state before returning; see
+    /// [`crate::cluster`]. A backend that drops it leaves the node to rejoin empty.
+    fn save_raft(&self, _record: &RaftRecord) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Storage that keeps nothing; the scheduler's in-memory maps are the only copy.
+pub struct NullStorage;
+
+impl Storage for NullStorage {
+    fn save_task(&self, _task: &Task) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_task(&self, _id: &Uuid) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_execution(&self, _execution: &TaskExecution) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_executions(&self, _ids: &[Uuid]) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_workflow_run(&self, _run: &WorkflowRun) -> Result<()> {
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Snapshot> {
+        Ok(Snapshot::default())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum LogRecord {
+    Task(Task),
+    TaskDeleted { id: Uuid },
+    Execution(TaskExecution),
+    ExecutionsDeleted { ids: Vec<Uuid> },
+    WorkflowRun(WorkflowRun),
+    Raft { change: RaftRecord },
+}
+
+/// Append-only JSON-lines log. Every change is one line. [`Storage::load`] replays the
+/// log and rewrites it compacted, and so does an append that leaves the log at least
+/// twice its last compacted size and over the [compaction
+/// threshold](Self::with_compaction_threshold).
+pub struct FileStorage {
+    path: PathBuf,
+    log: Mutex<LogFile>,
+    compaction_threshold: u64,
+}
+
+struct LogFile {
+    file: File,
+    /// Bytes in the log now, and right after it was last compacted.
+    len: u64,
+    compacted_len: u64,
+}
+
+impl FileStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| storage_error(&path, e))?;
+        }
+        let file = open_append(&path)?;
+        let len = file.metadata().map_err(|e| storage_error(&path, e))?.len();
+        Ok(Self {
+            path,
+            log: Mutex::new(LogFile { file, len, compacted_len: 0 }),
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        })
+    }
+
+    /// Compacts the log while running once it reaches `bytes`, 16 MiB by default.
+    pub fn with_compaction_threshold(mut self, bytes: u64) -> Self {
+        self.compaction_threshold = bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&self, record: &LogRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| ChronoError::StorageError(e.to_string()))?;
+        line.push('\n');
+        let mut log = self.log.lock().unwrap();
+        log.file.write_all(line.as_bytes())
+            .and_then(|_| log.file.sync_data())
+            .map_err(|e| storage_error(&self.path, e))?;
+        log.len += line.len() as u64;
+
+        if log.len >= self.compaction_threshold.max(log.compacted_len.saturating_mul(2)) {
+            // The change itself is saved; a failed compaction is retried once the log
+            // has doubled again.
+            if let Err(e) = self.replay().and_then(|snapshot| self.compact(&mut log, &snapshot)) {
+                warn!("Failed to compact {}: {}", self.path.display(), e);
+                log.compacted_len = log.len;
+            }
+        }
+        Ok(())
+    }
+
+    fn replay(&self) -> Result<Snapshot> {
+        let file = File::open(&self.path).map_err(|e| storage_error(&self.path, e))?;
+        let lines: Vec<String> = BufReader::new(file)
+            .lines()
+            .collect::<std::io::Result<_>>()
+            .map_err(|e| storage_error(&self.path, e))?;
+
+        let mut tasks = HashMap::new();
+        let mut executions = HashMap::new();
+        let mut workflow_runs = HashMap::new();
+        let mut raft: Option<RaftLog> = None;
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = match serde_json::from_str(line) {
+                Ok(record) => record,
+                // A torn final write from a crash is expected; anything earlier is corruption.
+                Err(_) if index + 1 == lines.len() => break,
+                Err(e) => {
+                    return Err(ChronoError::StorageError(format!(
+                        "{}:{}: {}", self.path.display(), index + 1, e
+                    )));
+                }
+            };
+            match record {
+                LogRecord::Task(task) => {
+                    tasks.insert(task.id, task);
+                }
+                LogRecord::TaskDeleted { id } => {
+                    tasks.remove(&id);
+                }
+                LogRecord::Execution(execution) => {
+                    executions.insert(execution.id, execution);
+                }
+                LogRecord::ExecutionsDeleted { ids } => {
+                    for id in ids {
+                        executions.remove(&id);
+                    }
+                }
+                LogRecord::WorkflowRun(run) => {
+                    workflow_runs.insert(run.id, run);
+                }
+                LogRecord::Raft { change } => raft.get_or_insert_with(RaftLog::default).apply(change),
+            }
+        }
+
+        let mut executions: Vec<TaskExecution> = executions.into_values().collect();
+        executions.sort_by_key(|e| e.started_at);
+        let mut workflow_runs: Vec<WorkflowRun> = workflow_runs.into_values().collect();
+        workflow_runs.sort_by_key(|r| r.started_at);
+        Ok(Snapshot { tasks: tasks.into_values().collect(), executions, workflow_runs, raft })
+    }
+
+    /// Rewrites the log as `snapshot`. The caller holds `log`'s lock, so no append can
+    /// slip in between replaying the log and replacing it.
+    fn compact(&self, log: &mut LogFile, snapshot: &Snapshot) -> Result<()> {
+        let tmp = self.path.with_extension("compact");
+        {
+            let file = File::create(&tmp).map_err(|e| storage_error(&tmp, e))?;
+            let mut writer = BufWriter::new(file);
+            let records = snapshot.tasks.iter().cloned().map(LogRecord::Task)
+                .chain(snapshot.executions.iter().cloned().map(LogRecord::Execution))
+                .chain(snapshot.workflow_runs.iter().cloned().map(LogRecord::WorkflowRun))
+                .chain(snapshot.raft.iter().flat_map(RaftLog::records).map(|change| LogRecord::Raft { change }));
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)
+                    .map_err(|e| ChronoError::StorageError(e.to_string()))?;
+                writer.write_all(b"\n").map_err(|e| storage_error(&tmp, e))?;
+            }
+            let file = writer.into_inner().map_err(|e| storage_error(&tmp, e.into_error()))?;
+            file.sync_all().map_err(|e| storage_error(&tmp, e))?;
+        }
+
+        fs::rename(&tmp, &self.path).map_err(|e| storage_error(&self.path, e))?;
+        log.file = open_append(&self.path)?;
+        log.len = log.file.metadata().map_err(|e| storage_error(&self.path, e))?.len();
+        log.compacted_len = log.len;
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn save_task(&self, task: &Task) -> Result<()> {
+        self.append(&LogRecord::Task(task.clone()))
+    }
+
+    fn delete_task(&self, id: &Uuid) -> Result<()> {
+        self.append(&LogRecord::TaskDeleted { id: *id })
+    }
+
+    fn save_execution(&self, execution: &TaskExecution) -> Result<()> {
+        self.append(&LogRecord::Execution(execution.clone()))
+    }
+
+    fn delete_executions(&self, ids: &[Uuid]) -> Result<()> {
+        self.append(&LogRecord::ExecutionsDeleted { ids: ids.to_vec() })
+    }
+
+    fn save_workflow_run(&self, run: &WorkflowRun) -> Result<()> {
+        self.append(&LogRecord::WorkflowRun(run.clone()))
+    }
+
+    fn save_raft(&self, record: &RaftRecord) -> Result<()> {
+        self.append(&LogRecord::Raft { change: record.clone() })
+    }
+
+    fn load(&self) -> Result<Snapshot> {
+        let mut log = self.log.lock().unwrap();
+        let snapshot = self.replay()?;
+        self.compact(&mut log, &snapshot)?;
+        Ok(snapshot)
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| storage_error(path, e))
+}
+
+fn storage_error(path: &Path, err: std::io::Error) -> ChronoError {
+    ChronoError::StorageError(format!("{}: {}", path.display(), err))
+}
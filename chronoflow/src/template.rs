@@ -0,0 +1,187 @@
+//! `{{ ... }}` placeholders in plugin config, rendered before every attempt.
+//!
+//! A placeholder is a dotted path into the run's context:
+//!
+//! - `task.id`, `task.name`
+//! - `run.execution_id`, `run.scheduled_at`, `run.attempt`, `run.catch_up`,
+//!   `run.workflow_run_id`
+//! - `upstream.<task name>.{status, message, output, error, execution_id, finished_at}`
+//!   for tasks that finished earlier in the same workflow run; `output` is the
+//!   plugin's `data`, so `upstream.fetch.output.id` reaches into it. Array elements are
+//!   addressed by index, e.g. `upstream.fetch.output.items.0`.
+//! - `event.*`: the event that started the run of an event-triggered task, e.g.
+//!   `event.body.id` for a webhook or `event.path` for a file change.
+//! - `env.<NAME>`: an environment variable of the scheduler process.
+//! - `secrets.<name>`: a value from the scheduler's [`SecretProvider`].
+//!
+//! A string that is exactly one placeholder is replaced by the value itself, keeping
+//! its JSON type. Placeholders inside longer strings are substituted as text. Only
+//! values are rendered, not object keys.
+
+use crate::{ChronoError, Result};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Source of the values behind `{{ secrets.<name> }}`.
+pub trait SecretProvider: Send + Sync {
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+/// Provides no secrets; any `secrets.*` placeholder fails to render.
+pub struct NoSecrets;
+
+impl SecretProvider for NoSecrets {
+    fn get(&self, _name: &str) -> Option<String> {
+        None
+    }
+}
+
+impl SecretProvider for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+/// Reads each secret from a file of the same name in a directory, as mounted by
+/// Docker and Kubernetes. A single trailing newline is dropped.
+pub struct DirSecrets {
+    dir: PathBuf,
+}
+
+impl DirSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SecretProvider for DirSecrets {
+    fn get(&self, name: &str) -> Option<String> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return None;
+        }
+        let mut value = std::fs::read_to_string(self.dir.join(name)).ok()?;
+        if value.ends_with('\n') {
+            value.pop();
+            if value.ends_with('\r') {
+                value.pop();
+            }
+        }
+        Some(value)
+    }
+}
+
+/// Everything placeholders can refer to for one attempt.
+pub struct TemplateContext<'a> {
+    values: JsonValue,
+    secrets: &'a dyn SecretProvider,
+}
+
+impl<'a> TemplateContext<'a> {
+    /// `values` holds the `task`, `run` and `upstream` objects; `env` and `secrets` are
+    /// looked up on demand.
+    pub fn new(values: JsonValue, secrets: &'a dyn SecretProvider) -> Self {
+        Self { values, secrets }
+    }
+
+    pub fn render(&self, config: &JsonValue) -> Result<JsonValue> {
+        match config {
+            JsonValue::String(text) => self.render_str(text),
+            JsonValue::Array(items) => items.iter().map(|v| self.render(v)).collect(),
+            JsonValue::Object(map) => map.iter()
+                .map(|(k, v)| Ok((k.clone(), self.render(v)?)))
+                .collect::<Result<_>>()
+                .map(JsonValue::Object),
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn render_str(&self, text: &str) -> Result<JsonValue> {
+        let parts = parse(text)?;
+        if let [Part::Placeholder(path)] = parts.as_slice() {
+            return self.lookup(path);
+        }
+        let mut rendered = String::new();
+        for part in parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Placeholder(path) => match self.lookup(path)? {
+                    JsonValue::String(s) => rendered.push_str(&s),
+                    JsonValue::Null => {}
+                    other => rendered.push_str(&other.to_string()),
+                },
+            }
+        }
+        Ok(JsonValue::String(rendered))
+    }
+
+    fn lookup(&self, path: &str) -> Result<JsonValue> {
+        let placeholder = format!("{{{{ {} }}}}", path);
+        let segments: Vec<&str> = path.split('.').collect();
+        match segments.as_slice() {
+            ["env", name] => std::env::var(name)
+                .map(JsonValue::String)
+                .map_err(|_| template_error(&placeholder, "environment variable is not set")),
+            ["secrets", name] => self.secrets.get(name)
+                .map(JsonValue::String)
+                .ok_or_else(|| template_error(&placeholder, "unknown secret")),
+            _ => {
+                let mut value = &self.values;
+                for (i, segment) in segments.iter().enumerate() {
+                    let next = match value {
+                        JsonValue::Object(map) => map.get(*segment),
+                        JsonValue::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                        _ => None,
+                    };
+                    value = next.ok_or_else(|| {
+                        template_error(&placeholder, &format!("'{}' not found", segments[..=i].join(".")))
+                    })?;
+                }
+                Ok(value.clone())
+            }
+        }
+    }
+}
+
+/// Checks placeholder syntax throughout `config` and reports whether it has any.
+pub fn check(config: &JsonValue) -> Result<bool> {
+    match config {
+        JsonValue::String(text) => Ok(parse(text)?.iter().any(|p| matches!(p, Part::Placeholder(_)))),
+        JsonValue::Array(items) => items.iter().try_fold(false, |any, v| Ok(check(v)? || any)),
+        JsonValue::Object(map) => map.values().try_fold(false, |any, v| Ok(check(v)? || any)),
+        _ => Ok(false),
+    }
+}
+
+enum Part<'t> {
+    Literal(&'t str),
+    /// The trimmed path between the braces.
+    Placeholder(&'t str),
+}
+
+fn parse(text: &str) -> Result<Vec<Part<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(Part::Literal(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let end = after.find("}}")
+            .ok_or_else(|| template_error(&format!("'{}'", text), "unterminated placeholder"))?;
+        let path = after[..end].trim();
+        if path.is_empty() || path.split('.').any(|s| s.is_empty() || s.contains(char::is_whitespace)) {
+            return Err(template_error(&format!("'{}'", text), &format!("invalid placeholder '{}'", path)));
+        }
+        parts.push(Part::Placeholder(path));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest));
+    }
+    Ok(parts)
+}
+
+fn template_error(what: &str, reason: &str) -> ChronoError {
+    ChronoError::TemplateError(format!("{}: {}", what, reason))
+}
@@ -1,6 +1,10 @@
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use crate::{ChronoError, CronExpr, PluginOutput, Result};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -8,20 +12,309 @@ pub struct Task {
     pub name: String,
     pub schedule: Schedule,
     pub plugin: PluginConfig,
+    /// Zone in which cron fields are evaluated. Intervals and `Once` are absolute and
+    /// unaffected. Local times skipped by a DST jump fire at the end of the gap; times
+    /// repeated by a DST fallback fire once, on their first occurrence.
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    #[serde(default)]
+    pub misfire_policy: MisfirePolicy,
+    #[serde(default)]
+    pub overlap_policy: OverlapPolicy,
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Per-attempt limit; overrides `SchedulerConfig::default_timeout`.
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// Upstream tasks whose completion triggers this one as part of a workflow run.
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+    /// Who to tell when runs of the task succeed, fail, recover or run long.
+    #[serde(default)]
+    pub notifications: Vec<NotificationRule>,
+    /// Definition file the task was loaded from; such tasks are managed by reloading
+    /// the file rather than through the API.
+    #[serde(default)]
+    pub source: Option<String>,
     pub enabled: bool,
     pub created_at: DateTime<Utc>,
     pub last_run: Option<DateTime<Utc>>,
     pub next_run: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Schedule {
     Cron(String),
     Interval { seconds: u64 },
     Once { at: DateTime<Utc> },
+    /// No time trigger; the task only runs when started by its upstream dependencies.
+    Manual,
+    /// Runs whenever a request is posted to `/hooks/<path>` on the API.
+    Webhook { path: String },
+    /// Runs whenever a file under `dir` whose path relative to it matches `glob` (any
+    /// file when unset) is created, modified or removed.
+    FileWatch {
+        dir: String,
+        #[serde(default)]
+        glob: Option<String>,
+    },
+    /// Runs whenever an event is published to the topic with
+    /// [`Scheduler::publish`](crate::Scheduler::publish).
+    Topic { name: String },
+}
+
+/// An edge in a workflow: run this task after `task_id` finishes, if `condition` holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub task_id: Uuid,
+    #[serde(default)]
+    pub condition: TriggerCondition,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriggerCondition {
+    #[default]
+    OnSuccess,
+    OnFailure,
+    /// Run once the upstream task has finished, whatever the outcome, including when
+    /// it was skipped.
+    Always,
+}
+
+/// What to do with fire times that were missed because the scheduler was paused,
+/// overloaded or down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MisfirePolicy {
+    /// Collapse all missed fires into a single catch-up run.
+    #[default]
+    FireOnceNow,
+    /// Run once for every missed fire time, oldest first.
+    FireAll,
+    /// Drop missed fires and wait for the next regular one.
+    Skip,
+    /// Run once if the latest missed fire is at most `grace_seconds` old, otherwise skip.
+    FireWithinGrace { grace_seconds: u64 },
+}
+
+/// What to do when a run of a task is due while an earlier run of it is still going.
+/// Retries of the earlier run count as part of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverlapPolicy {
+    /// Start the new run alongside the earlier one.
+    #[default]
+    Allow,
+    /// Don't start the new run; it is recorded as `Skipped`.
+    Skip,
+    /// Start the new run once the earlier ones have finished, in order.
+    Queue,
+    /// Cancel the earlier run and start the new one, without waiting for the earlier
+    /// plugin to wind down.
+    CancelPrevious,
+}
+
+/// How failed executions are retried.
+///
+/// The delay before attempt `n + 1` is `backoff_base_ms * 2^(n - 1)`, capped at
+/// `max_delay_ms`, with up to `jitter` (a fraction of the delay) subtracted at random.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first run.
+    pub max_attempts: u32,
+    pub backoff_base_ms: u64,
+    pub max_delay_ms: u64,
+    #[serde(default)]
+    pub jitter: f64,
+    /// Only errors containing one of these substrings are retried; empty retries all.
+    #[serde(default)]
+    pub retry_on: Vec<String>,
+}
+
+/// When a [`NotificationRule`] fires. Failure, success and recovery are judged on a
+/// run's final attempt, after any retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NotifyOn {
+    Success,
+    /// The run failed or timed out.
+    Failure,
+    /// The run succeeded and the task's previous run had failed or timed out.
+    Recovery,
+    /// The run was still going `seconds` after it started.
+    SlaMiss { seconds: u64 },
+}
+
+/// Sends a notification through the named [`Notifier`](crate::Notifier) when `on`
+/// happens. A rule that fired stays quiet for `dedup_seconds` afterwards, so a
+/// flapping task notifies once per window rather than on every run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationRule {
+    pub on: NotifyOn,
+    pub notifier: String,
+    #[serde(default = "empty_object")]
+    pub config: serde_json::Value,
+    #[serde(default = "default_dedup_seconds")]
+    pub dedup_seconds: u64,
+}
+
+fn empty_object() -> serde_json::Value {
+    serde_json::Value::Object(Default::default())
+}
+
+fn default_dedup_seconds() -> u64 {
+    300
+}
+
+impl NotificationRule {
+    pub fn new(on: NotifyOn, notifier: impl Into<String>, config: serde_json::Value) -> Self {
+        Self { on, notifier: notifier.into(), config, dedup_seconds: default_dedup_seconds() }
+    }
+    
+    pub fn with_dedup_seconds(mut self, seconds: u64) -> Self {
+        self.dedup_seconds = seconds;
+        self
+    }
+    
+    pub fn validate(&self) -> Result<()> {
+        if self.on == (NotifyOn::SlaMiss { seconds: 0 }) {
+            return Err(ChronoError::InvalidTask("SLA seconds must be greater than zero".into()));
+        }
+        Ok(())
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(ChronoError::InvalidTask("retry max_attempts must be at least 1".into()));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(ChronoError::InvalidTask("retry jitter must be between 0 and 1".into()));
+        }
+        Ok(())
+    }
+    
+    pub fn should_retry(&self, attempt: u32, error: &str) -> bool {
+        attempt < self.max_attempts
+            && (self.retry_on.is_empty() || self.retry_on.iter().any(|p| error.contains(p.as_str())))
+    }
+    
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32);
+        let delay = self.backoff_base_ms.saturating_mul(1 << exponent).min(self.max_delay_ms);
+        let jitter = (delay as f64 * self.jitter * rand::thread_rng().gen::<f64>()) as u64;
+        Duration::from_millis(delay - jitter)
+    }
+}
+
+/// Longest accepted `Schedule::Interval`, about a hundred years.
+pub const MAX_INTERVAL_SECONDS: u64 = 100 * 366 * 24 * 60 * 60;
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Schedule::Cron(expr) => {
+                let cron = CronExpr::parse(expr)?;
+                if cron.next_after(Utc::now()).is_none() {
+                    return Err(ChronoError::InvalidCron(format!("'{}' never fires", expr)));
+                }
+            }
+            Schedule::Interval { seconds: 0 } => {
+                return Err(ChronoError::InvalidTask("interval must be at least one second".into()));
+            }
+            Schedule::Interval { seconds } if *seconds > MAX_INTERVAL_SECONDS => {
+                return Err(ChronoError::InvalidTask(format!(
+                    "interval must be at most {} seconds", MAX_INTERVAL_SECONDS
+                )));
+            }
+            Schedule::Webhook { path } if webhook_path(path).is_empty() => {
+                return Err(ChronoError::InvalidTask("webhook path is empty".into()));
+            }
+            Schedule::FileWatch { dir, glob } => {
+                if dir.is_empty() {
+                    return Err(ChronoError::InvalidTask("watched directory is empty".into()));
+                }
+                if let Some(glob) = glob {
+                    globset::Glob::new(glob)
+                        .map_err(|e| ChronoError::InvalidTask(format!("invalid glob '{}': {}", glob, e)))?;
+                }
+            }
+            Schedule::Topic { name } if name.is_empty() => {
+                return Err(ChronoError::InvalidTask("topic name is empty".into()));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether the task runs on events rather than at times.
+    pub fn is_event(&self) -> bool {
+        matches!(self, Schedule::Webhook { .. } | Schedule::FileWatch { .. } | Schedule::Topic { .. })
+    }
+
+    /// Whether this is a webhook schedule for `path`; surrounding slashes don't matter.
+    pub fn accepts_webhook(&self, path: &str) -> bool {
+        matches!(self, Schedule::Webhook { path: own } if webhook_path(own) == webhook_path(path))
+    }
+}
+
+fn webhook_path(path: &str) -> &str {
+    path.trim_matches('/')
 }
 
+/// The user-supplied parts of a [`Task`], as accepted by the API. Scheduler-managed
+/// fields (`id`, `created_at`, `last_run`, `next_run`) are left out.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSpec {
+    pub name: String,
+    pub schedule: Schedule,
+    pub plugin: PluginConfig,
+    /// IANA zone name; UTC when omitted.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub misfire_policy: MisfirePolicy,
+    #[serde(default)]
+    pub overlap_policy: OverlapPolicy,
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+    #[serde(default)]
+    pub notifications: Vec<NotificationRule>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl TaskSpec {
+    pub fn into_task(self) -> Result<Task> {
+        let mut task = Task::new(self.name, self.schedule, self.plugin)?
+            .with_misfire_policy(self.misfire_policy)
+            .with_overlap_policy(self.overlap_policy);
+        if let Some(timezone) = &self.timezone {
+            task = task.with_timezone(timezone)?;
+        }
+        if let Some(retry) = self.retry {
+            task = task.with_retry(retry)?;
+        }
+        task.timeout_seconds = self.timeout_seconds;
+        task.depends_on = self.depends_on;
+        task.notifications = self.notifications;
+        task.enabled = self.enabled;
+        task.validate()?;
+        Ok(task)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PluginConfig {
     pub name: String,
     pub wasm_path: String,
@@ -35,29 +328,176 @@ pub struct TaskExecution {
     pub started_at: DateTime<Utc>,
     pub finished_at: Option<DateTime<Utc>>,
     pub status: ExecutionStatus,
-    pub output: Option<String>,
+    pub output: Option<PluginOutput>,
     pub error: Option<String>,
+    /// Wall-clock time between start and finish, set once the execution ends.
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// The fire time this run was started for.
+    #[serde(default)]
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Set when the run makes up for a fire time that was missed.
+    #[serde(default)]
+    pub catch_up: bool,
+    /// 1 for the first run of a fire time, incremented for each retry.
+    #[serde(default = "first_attempt")]
+    pub attempt: u32,
+    /// The first attempt's execution, when this one is a retry.
+    #[serde(default)]
+    pub retry_of: Option<Uuid>,
+    /// The workflow run this execution belongs to, if it was part of a DAG run.
+    #[serde(default)]
+    pub workflow_run_id: Option<Uuid>,
+    /// Why the execution failed, when it did.
+    #[serde(default)]
+    pub failure_reason: Option<FailureReason>,
+    /// What the plugin logged while it ran.
+    #[serde(default)]
+    pub logs: Vec<LogLine>,
+    /// The event that started the run, for event-triggered tasks.
+    #[serde(default)]
+    pub event: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn first_attempt() -> u32 {
+    1
+}
+
+impl TaskExecution {
+    pub fn new(task_id: Uuid, scheduled_at: DateTime<Utc>, catch_up: bool) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            task_id,
+            started_at: Utc::now(),
+            finished_at: None,
+            status: ExecutionStatus::Running,
+            output: None,
+            error: None,
+            duration_ms: None,
+            scheduled_at: Some(scheduled_at),
+            catch_up,
+            attempt: first_attempt(),
+            retry_of: None,
+            workflow_run_id: None,
+            failure_reason: None,
+            logs: Vec::new(),
+            event: None,
+        }
+    }
+}
+
+/// One line of an execution's log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    pub at: DateTime<Utc>,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExecutionStatus {
+    /// Waiting for an earlier run of the task or for a concurrency slot.
+    Queued,
     Running,
     Success,
     Failed,
     Timeout,
+    /// Not run because an earlier run of the task was still going.
+    Skipped,
+    /// Stopped before it finished, e.g. by a newer run under `CancelPrevious`.
+    Cancelled,
+    /// Cut off, or never started, because the scheduler shut down.
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureReason {
+    /// The plugin ran and reported an error.
+    Plugin,
+    Timeout,
+    /// The plugin config could not be rendered, so the plugin never ran.
+    Template,
 }
 
 impl Task {
-    pub fn new(name: String, schedule: Schedule, plugin: PluginConfig) -> Self {
-        Self {
+    pub fn new(name: String, schedule: Schedule, plugin: PluginConfig) -> Result<Self> {
+        schedule.validate()?;
+        Ok(Self {
             id: Uuid::new_v4(),
             name,
             schedule,
             plugin,
+            timezone: default_timezone(),
+            misfire_policy: MisfirePolicy::default(),
+            overlap_policy: OverlapPolicy::default(),
+            retry: None,
+            timeout_seconds: None,
+            depends_on: Vec::new(),
+            notifications: Vec::new(),
+            source: None,
             enabled: true,
             created_at: Utc::now(),
             last_run: None,
             next_run: None,
+        })
+    }
+    
+    pub fn with_timezone(mut self, timezone: &str) -> Result<Self> {
+        self.timezone = timezone.parse()
+            .map_err(|_| ChronoError::InvalidTimezone(timezone.to_string()))?;
+        Ok(self)
+    }
+    
+    pub fn with_misfire_policy(mut self, policy: MisfirePolicy) -> Self {
+        self.misfire_policy = policy;
+        self
+    }
+    
+    pub fn with_overlap_policy(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap_policy = policy;
+        self
+    }
+    
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Result<Self> {
+        policy.validate()?;
+        self.retry = Some(policy);
+        Ok(self)
+    }
+    
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = Some(seconds);
+        self
+    }
+    
+    pub fn with_dependency(mut self, task_id: Uuid, condition: TriggerCondition) -> Self {
+        self.depends_on.push(Dependency { task_id, condition });
+        self
+    }
+    
+    pub fn with_notification(mut self, rule: NotificationRule) -> Self {
+        self.notifications.push(rule);
+        self
+    }
+    
+    pub fn validate(&self) -> Result<()> {
+        self.schedule.validate()?;
+        if self.timeout_seconds == Some(0) {
+            return Err(ChronoError::InvalidTask("timeout_seconds must be greater than zero".into()));
+        }
+        if let Some(retry) = &self.retry {
+            retry.validate()?;
+        }
+        for rule in &self.notifications {
+            rule.validate()?;
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,266 @@
+//! Sandboxed WebAssembly plugins, loaded from `PluginConfig::wasm_path`.
+//!
+//! # Host ABI
+//!
+//! A plugin module must export:
+//!
+//! - `memory`: its linear memory.
+//! - `alloc(len: i32) -> i32`: returns a pointer to `len` writable bytes.
+//! - `run(ptr: i32, len: i32) -> i64`: receives the UTF-8 JSON request at `ptr..ptr+len`
+//!   and returns `(out_ptr << 32) | out_len` locating its UTF-8 JSON response.
+//!
+//! The request is `{"config": <task config>, "context": {"task_id", "execution_id",
+//! "attempt", "scheduled_at"}}`. The response is either `{"ok": {"message": "...",
+//! "data": <any>}}` or `{"error": "..."}`.
+//!
+//! The host provides one optional import, `chronoflow.log(ptr: i32, len: i32)`, which
+//! writes a UTF-8 line to the execution's log.
+//!
+//! Every run gets a fresh instance with its own fuel budget and memory cap, and is
+//! interrupted if the run's cancellation token fires.
+
+use crate::{ChronoError, ExecutionLog, PluginContext, PluginOutput, Result};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use wasmtime::{Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, UpdateDeadline};
+
+/// Resource limits applied to each WASM plugin run.
+#[derive(Debug, Clone, Copy)]
+pub struct WasmLimits {
+    /// Fuel units available to a run; roughly one per executed instruction.
+    pub fuel: u64,
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 1_000_000_000,
+            max_memory_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+struct RunState {
+    limits: StoreLimits,
+    cancel: CancellationToken,
+    log: ExecutionLog,
+}
+
+/// Compiled modules kept by default; see `WasmRuntime::with_cache_capacity`.
+const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// Compiled modules keyed by the SHA-256 of their file contents. Once full, the least
+/// recently used module makes room for a new one.
+struct ModuleCache {
+    capacity: usize,
+    /// Each module with the tick it was last used at.
+    modules: HashMap<[u8; 32], (Module, u64)>,
+    tick: u64,
+}
+
+impl ModuleCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, modules: HashMap::new(), tick: 0 }
+    }
+
+    fn get(&mut self, hash: &[u8; 32]) -> Option<Module> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.modules.get_mut(hash).map(|(module, used)| {
+            *used = tick;
+            module.clone()
+        })
+    }
+
+    fn insert(&mut self, hash: [u8; 32], module: Module) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.modules.len() >= self.capacity && !self.modules.contains_key(&hash) {
+            let oldest = self.modules.iter().min_by_key(|(_, (_, used))| *used).map(|(hash, _)| *hash);
+            self.modules.remove(&oldest.expect("a full cache has entries"));
+        }
+        self.tick += 1;
+        self.modules.insert(hash, (module, self.tick));
+    }
+}
+
+pub struct WasmRuntime {
+    engine: Engine,
+    limits: WasmLimits,
+    modules: Mutex<ModuleCache>,
+}
+
+impl WasmRuntime {
+    pub fn new(limits: WasmLimits) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        Ok(Self { engine, limits, modules: Mutex::new(ModuleCache::new(DEFAULT_CACHE_CAPACITY)) })
+    }
+
+    pub fn with_limits(mut self, limits: WasmLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how many compiled modules are kept; 0 compiles every run afresh.
+    pub fn with_cache_capacity(self, capacity: usize) -> Self {
+        *self.modules.lock().unwrap() = ModuleCache::new(capacity);
+        self
+    }
+
+    /// How many compiled modules are cached.
+    pub fn cached_modules(&self) -> usize {
+        self.modules.lock().unwrap().modules.len()
+    }
+
+    pub fn limits(&self) -> WasmLimits {
+        self.limits
+    }
+
+    /// Compiles the module at `path`, or returns the cached copy if a file with the same
+    /// contents was loaded before, and checks that it exports the plugin ABI.
+    pub fn load(&self, path: &Path) -> Result<Module> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| ChronoError::PluginError(format!("{}: {}", path.display(), e)))?;
+        let hash: [u8; 32] = Sha256::digest(&bytes).into();
+
+        if let Some(module) = self.modules.lock().unwrap().get(&hash) {
+            return Ok(module);
+        }
+
+        let module = Module::new(&self.engine, &bytes)
+            .map_err(|e| ChronoError::PluginError(format!("{}: {:#}", path.display(), e)))?;
+        for export in ["memory", "alloc", "run"] {
+            if module.get_export(export).is_none() {
+                return Err(ChronoError::PluginError(format!(
+                    "{}: missing export '{}'", path.display(), export
+                )));
+            }
+        }
+        self.modules.lock().unwrap().insert(hash, module.clone());
+        Ok(module)
+    }
+
+    pub async fn execute(&self, path: &Path, ctx: PluginContext, config: &JsonValue) -> Result<PluginOutput> {
+        let module = self.load(path)?;
+        let request = serde_json::json!({
+            "config": config,
+            "context": {
+                "task_id": ctx.task_id,
+                "execution_id": ctx.execution_id,
+                "attempt": ctx.attempt,
+                "scheduled_at": ctx.scheduled_at,
+            },
+        });
+        let request = serde_json::to_vec(&request).map_err(|e| ChronoError::PluginError(e.to_string()))?;
+
+        // Bumping the epoch makes every running store consult its callback, which traps
+        // only if its own run was cancelled.
+        let engine = self.engine.clone();
+        let cancel = ctx.cancel.clone();
+        let interrupter = tokio::spawn(async move {
+            cancel.cancelled().await;
+            engine.increment_epoch();
+        });
+
+        let engine = self.engine.clone();
+        let limits = self.limits;
+        let cancel = ctx.cancel.clone();
+        let log = ctx.log.clone();
+        let span = tracing::Span::current();
+        let result = tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            run_module(&engine, &module, limits, cancel, log, &request)
+        })
+            .await
+            .unwrap_or_else(|e| Err(ChronoError::PluginError(format!("wasm plugin panicked: {}", e))));
+        interrupter.abort();
+        result
+    }
+}
+
+fn run_module(
+    engine: &Engine,
+    module: &Module,
+    limits: WasmLimits,
+    cancel: CancellationToken,
+    log: ExecutionLog,
+    request: &[u8],
+) -> Result<PluginOutput> {
+    let state = RunState {
+        limits: StoreLimitsBuilder::new().memory_size(limits.max_memory_bytes).build(),
+        cancel,
+        log,
+    };
+    let mut store = Store::new(engine, state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(limits.fuel).map_err(wasm_error)?;
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(|ctx| {
+        if ctx.data().cancel.is_cancelled() {
+            Err(wasmtime::Error::msg("execution cancelled"))
+        } else {
+            Ok(UpdateDeadline::Continue(1))
+        }
+    });
+
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("chronoflow", "log", |mut caller: Caller<'_, RunState>, ptr: i32, len: i32| {
+            if let Some(wasmtime::Extern::Memory(memory)) = caller.get_export("memory") {
+                if let Ok(bytes) = read_bytes(memory.data(&caller), ptr, len) {
+                    caller.data().log.info(String::from_utf8_lossy(bytes));
+                }
+            }
+        })
+        .map_err(wasm_error)?;
+
+    let instance = linker.instantiate(&mut store, module).map_err(wasm_error)?;
+    let memory = instance.get_memory(&mut store, "memory")
+        .ok_or_else(|| ChronoError::PluginError("module does not export memory".into()))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc").map_err(wasm_error)?;
+    let run = instance.get_typed_func::<(i32, i32), i64>(&mut store, "run").map_err(wasm_error)?;
+
+    let len = i32::try_from(request.len())
+        .map_err(|_| ChronoError::PluginError("plugin config too large".into()))?;
+    let ptr = alloc.call(&mut store, len).map_err(wasm_error)?;
+    memory.write(&mut store, ptr as u32 as usize, request)
+        .map_err(|e| ChronoError::PluginError(format!("wasm: {}", e)))?;
+
+    let packed = run.call(&mut store, (ptr, len)).map_err(wasm_error)?;
+    let (out_ptr, out_len) = ((packed >> 32) as i32, packed as i32);
+    let response = read_bytes(memory.data(&store), out_ptr, out_len)?;
+    parse_response(response)
+}
+
+fn read_bytes(memory: &[u8], ptr: i32, len: i32) -> Result<&[u8]> {
+    let start = ptr as u32 as usize;
+    let end = start.checked_add(len as u32 as usize);
+    end.and_then(|end| memory.get(start..end))
+        .ok_or_else(|| ChronoError::PluginError("wasm plugin returned an out-of-bounds buffer".into()))
+}
+
+fn parse_response(bytes: &[u8]) -> Result<PluginOutput> {
+    let response: JsonValue = serde_json::from_slice(bytes)
+        .map_err(|e| ChronoError::PluginError(format!("invalid wasm plugin response: {}", e)))?;
+    if let Some(ok) = response.get("ok") {
+        return serde_json::from_value(ok.clone())
+            .map_err(|e| ChronoError::PluginError(format!("invalid wasm plugin output: {}", e)));
+    }
+    let error = response.get("error")
+        .and_then(|e| e.as_str())
+        .unwrap_or("wasm plugin response has neither 'ok' nor 'error'");
+    Err(ChronoError::PluginError(error.to_string()))
+}
+
+fn wasm_error(err: wasmtime::Error) -> ChronoError {
+    ChronoError::PluginError(format!("wasm: {:#}", err))
+}
@@ -0,0 +1,300 @@
+//! Task dependency graphs.
+//!
+//! Tasks list their upstream tasks in `Task::depends_on`. When a task with dependents
+//! fires, a [`WorkflowRun`] is started covering it and everything downstream of it.
+//! Each downstream task starts once all of its upstream tasks that take part in the run
+//! have finished, and only if every one of their trigger conditions holds; otherwise it
+//! is skipped, which in turn only satisfies `Always` conditions further down.
+//!
+//! A task may only join branches that start from the same root task: if its upstream
+//! tasks were started by separate roots, each root's run would start it on its own
+//! without waiting for the others, so such graphs are rejected when the task is added.
+
+use crate::{ChronoError, ExecutionStatus, Result, Task, TriggerCondition};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkflowStatus {
+    Running,
+    Success,
+    /// At least one step failed or timed out.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Success,
+    Failed,
+    /// Not run because an upstream trigger condition did not hold.
+    Skipped,
+}
+
+impl StepStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, StepStatus::Success | StepStatus::Failed | StepStatus::Skipped)
+    }
+}
+
+impl From<ExecutionStatus> for StepStatus {
+    fn from(status: ExecutionStatus) -> Self {
+        match status {
+            ExecutionStatus::Queued | ExecutionStatus::Running => StepStatus::Running,
+            ExecutionStatus::Success => StepStatus::Success,
+            ExecutionStatus::Failed
+            | ExecutionStatus::Timeout
+            | ExecutionStatus::Cancelled
+            | ExecutionStatus::Interrupted => StepStatus::Failed,
+            ExecutionStatus::Skipped => StepStatus::Skipped,
+        }
+    }
+}
+
+impl TriggerCondition {
+    pub fn is_met(self, upstream: StepStatus) -> bool {
+        match self {
+            TriggerCondition::OnSuccess => upstream == StepStatus::Success,
+            TriggerCondition::OnFailure => upstream == StepStatus::Failed,
+            TriggerCondition::Always => upstream.is_finished(),
+        }
+    }
+}
+
+/// One run of a DAG, tying together the executions of every task in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub id: Uuid,
+    /// The task whose firing started the run.
+    pub root_task_id: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: WorkflowStatus,
+    /// Progress of every task in the run, keyed by task id.
+    pub steps: BTreeMap<Uuid, StepStatus>,
+    /// Executions started for the run, including retries, in start order.
+    pub executions: Vec<Uuid>,
+}
+
+impl WorkflowRun {
+    /// A run rooted at `root` covering every task downstream of it. The root step
+    /// starts out `Running`.
+    pub(crate) fn new(root: Uuid, tasks: &HashMap<Uuid, Task>) -> Self {
+        let mut steps: BTreeMap<Uuid, StepStatus> = downstream(tasks, root)
+            .into_iter()
+            .map(|id| (id, StepStatus::Pending))
+            .collect();
+        steps.insert(root, StepStatus::Running);
+        Self {
+            id: Uuid::new_v4(),
+            root_task_id: root,
+            started_at: Utc::now(),
+            finished_at: None,
+            status: WorkflowStatus::Running,
+            steps,
+            executions: Vec::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status != WorkflowStatus::Running
+    }
+
+    /// Records the final status of a step, then starts or skips every step whose
+    /// upstream steps are now all finished. Returns the tasks to start; their steps are
+    /// already marked `Running`.
+    pub(crate) fn complete_step(
+        &mut self,
+        task_id: Uuid,
+        status: ExecutionStatus,
+        tasks: &HashMap<Uuid, Task>,
+    ) -> Vec<Uuid> {
+        if let Some(step) = self.steps.get_mut(&task_id) {
+            *step = status.into();
+        }
+
+        let mut ready = Vec::new();
+        // Skipping a step can unblock the ones after it, so repeat until nothing changes.
+        loop {
+            let mut changed = false;
+            let pending: Vec<Uuid> = self.steps.iter()
+                .filter(|(_, status)| **status == StepStatus::Pending)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in pending {
+                let next = match tasks.get(&id) {
+                    Some(task) => match self.evaluate(task) {
+                        Some(true) if task.enabled => StepStatus::Running,
+                        Some(_) => StepStatus::Skipped,
+                        None => continue,
+                    },
+                    // Removed since the run started.
+                    None => StepStatus::Skipped,
+                };
+                if next == StepStatus::Running {
+                    ready.push(id);
+                }
+                self.steps.insert(id, next);
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        if self.steps.values().all(|s| s.is_finished()) {
+            self.finish(if self.steps.values().any(|s| *s == StepStatus::Failed) {
+                WorkflowStatus::Failed
+            } else {
+                WorkflowStatus::Success
+            });
+        }
+        ready
+    }
+
+    pub(crate) fn finish(&mut self, status: WorkflowStatus) {
+        self.status = status;
+        self.finished_at = Some(Utc::now());
+    }
+
+    /// `None` while some upstream step in this run is unfinished, otherwise whether all
+    /// of the task's trigger conditions hold.
+    fn evaluate(&self, task: &Task) -> Option<bool> {
+        let mut met = true;
+        for dep in &task.depends_on {
+            let Some(upstream) = self.steps.get(&dep.task_id) else {
+                continue;
+            };
+            if !upstream.is_finished() {
+                return None;
+            }
+            met &= dep.condition.is_met(*upstream);
+        }
+        Some(met)
+    }
+}
+
+/// Tasks that list `id` as a direct dependency.
+pub(crate) fn dependents(tasks: &HashMap<Uuid, Task>, id: Uuid) -> Vec<Uuid> {
+    tasks.values()
+        .filter(|t| t.depends_on.iter().any(|d| d.task_id == id))
+        .map(|t| t.id)
+        .collect()
+}
+
+/// Every task reachable from `root` by following dependency edges downstream.
+fn downstream(tasks: &HashMap<Uuid, Task>, root: Uuid) -> HashSet<Uuid> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(id) = stack.pop() {
+        for next in dependents(tasks, id) {
+            if next != root && seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Checks that `task`'s dependencies exist and that adding it to `tasks`, replacing
+/// any task with the same id, keeps the graph acyclic and every join within one root's
+/// runs.
+pub(crate) fn check_dependencies(tasks: &HashMap<Uuid, Task>, task: &Task) -> Result<()> {
+    for dep in &task.depends_on {
+        if dep.task_id != task.id && !tasks.contains_key(&dep.task_id) {
+            return Err(ChronoError::InvalidTask(format!(
+                "'{}' depends on unknown task {}", task.name, dep.task_id
+            )));
+        }
+    }
+
+    // The existing graph is acyclic, so any cycle has to pass through `task`: look for
+    // a path from its upstream tasks back to it.
+    let upstream_of = |id: Uuid| -> Vec<Uuid> {
+        let deps = if id == task.id {
+            &task.depends_on
+        } else {
+            match tasks.get(&id) {
+                Some(t) => &t.depends_on,
+                None => return Vec::new(),
+            }
+        };
+        deps.iter().map(|d| d.task_id).collect()
+    };
+    let mut visited = HashSet::new();
+    let mut path = vec![task.id];
+    if find_path(task.id, &upstream_of, &mut visited, &mut path) {
+        let name = |id: &Uuid| if *id == task.id {
+            task.name.clone()
+        } else {
+            tasks.get(id).map(|t| t.name.clone()).unwrap_or_else(|| id.to_string())
+        };
+        let cycle: Vec<String> = path.iter().rev().map(name).collect();
+        return Err(ChronoError::InvalidTask(format!("dependency cycle: {}", cycle.join(" -> "))));
+    }
+
+    // Changing `task`'s dependencies can change the roots of everything downstream of it.
+    let mut graph = tasks.clone();
+    graph.insert(task.id, task.clone());
+    let affected = std::iter::once(task.id).chain(downstream(&graph, task.id));
+    for id in affected {
+        let roots = roots(&graph, id);
+        if roots.len() > 1 {
+            let mut names: Vec<&str> = roots.iter().map(|r| graph[r].name.as_str()).collect();
+            names.sort_unstable();
+            return Err(ChronoError::InvalidTask(format!(
+                "'{}' joins tasks started by separate roots ('{}'); give them a common upstream task",
+                graph[&id].name, names.join("', '")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The tasks without dependencies that `id` is downstream of, whose firing starts the
+/// workflow runs it takes part in.
+fn roots(tasks: &HashMap<Uuid, Task>, id: Uuid) -> HashSet<Uuid> {
+    let mut roots = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![id];
+    while let Some(current) = stack.pop() {
+        let deps = &tasks[&current].depends_on;
+        if deps.is_empty() && current != id {
+            roots.insert(current);
+        }
+        for dep in deps {
+            if seen.insert(dep.task_id) {
+                stack.push(dep.task_id);
+            }
+        }
+    }
+    roots
+}
+
+/// Depth-first search upstream from the last node in `path` for `target`, leaving the
+/// route in `path` when found.
+fn find_path(
+    target: Uuid,
+    upstream_of: &impl Fn(Uuid) -> Vec<Uuid>,
+    visited: &mut HashSet<Uuid>,
+    path: &mut Vec<Uuid>,
+) -> bool {
+    let current = *path.last().unwrap();
+    for next in upstream_of(current) {
+        if next == target {
+            path.push(next);
+            return true;
+        }
+        if visited.insert(next) {
+            path.push(next);
+            if find_path(target, upstream_of, visited, path) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
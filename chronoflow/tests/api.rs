@@ -0,0 +1,197 @@
+use chronoflow::client::ApiClient;
+use chronoflow::{
+    api, ChronoError, ExecutionFilter, ExecutionStatus, PluginConfig, PluginManager, Schedule, Scheduler, Task,
+    TaskSpec,
+};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+struct Server {
+    scheduler: Arc<Scheduler>,
+    url: String,
+    http: Client,
+}
+
+async fn serve(token: Option<&str>) -> Server {
+    let scheduler = Arc::new(Scheduler::new(Arc::new(PluginManager::new())));
+    scheduler.start().await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = api::router(scheduler.clone(), token.map(str::to_string));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    Server { scheduler, url, http: Client::new() }
+}
+
+impl Server {
+    async fn call(&self, method: &str, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = self.http.request(method.parse().unwrap(), format!("{}{}", self.url, path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+        (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+}
+
+fn spec(name: &str) -> Value {
+    json!({
+        "name": name,
+        "schedule": { "Interval": { "seconds": 3600 } },
+        "plugin": { "name": "logger", "wasm_path": "", "config": { "message": "hi" } },
+    })
+}
+
+#[tokio::test]
+async fn tasks_can_be_created_read_replaced_and_removed() {
+    let server = serve(None).await;
+    let (status, created) = server.call("POST", "/tasks", Some(spec("nightly"))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["name"], "nightly");
+    assert_eq!(created["enabled"], true);
+    let id = created["id"].as_str().unwrap().to_string();
+
+    let (status, listed) = server.call("GET", "/tasks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+
+    let (status, replaced) = server.call("PUT", &format!("/tasks/{}", id), Some(spec("hourly"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replaced["id"], id.as_str());
+    assert_eq!(replaced["name"], "hourly");
+
+    let (status, disabled) = server.call("POST", &format!("/tasks/{}/disable", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(disabled["enabled"], false);
+
+    let (status, _) = server.call("DELETE", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = server.call("GET", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains(&id), "{}", body);
+}
+
+#[tokio::test]
+async fn bad_requests_get_400_with_the_reason() {
+    let server = serve(None).await;
+    let mut bad_cron = spec("broken");
+    bad_cron["schedule"] = json!({ "Cron": "not a cron" });
+    let (status, body) = server.call("POST", "/tasks", Some(bad_cron)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("cron"), "{}", body);
+
+    let mut bad_zone = spec("broken");
+    bad_zone["timezone"] = json!("Mars/Olympus");
+    let (status, body) = server.call("POST", "/tasks", Some(bad_zone)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("Mars/Olympus"), "{}", body);
+
+    let mut unknown_plugin = spec("broken");
+    unknown_plugin["plugin"]["name"] = json!("nope");
+    let (status, _) = server.call("POST", "/tasks", Some(unknown_plugin)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = server.call("GET", "/tasks/not-a-uuid", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = server.call("GET", &format!("/executions/{}", Uuid::new_v4()), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn file_defined_tasks_are_read_only() {
+    let server = serve(None).await;
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    let mut task = Task::new("from-file".into(), Schedule::Manual, plugin).unwrap();
+    task.source = Some("tasks/report.yaml".into());
+    let id = server.scheduler.add_task(task).unwrap();
+
+    let (status, body) = server.call("PUT", &format!("/tasks/{}", id), Some(spec("renamed"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("tasks/report.yaml"), "{}", body);
+    let (status, _) = server.call("DELETE", &format!("/tasks/{}", id), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(server.scheduler.get_task(&id).is_ok());
+}
+
+#[tokio::test]
+async fn runs_are_started_and_listed() {
+    let server = serve(None).await;
+    let client = ApiClient::new(&server.url);
+    let mut manual = spec("manual");
+    manual["schedule"] = json!("Manual");
+    let task: TaskSpec = serde_json::from_value(manual).unwrap();
+    let task = client.create_task(&task).await.unwrap();
+
+    let execution_id = client.trigger(&task.id).await.unwrap();
+    let mut execution = client.get_execution(&execution_id).await.unwrap();
+    for _ in 0..100 {
+        if execution.status == ExecutionStatus::Success {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        execution = client.get_execution(&execution_id).await.unwrap();
+    }
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(execution.task_id, task.id);
+
+    let filter = ExecutionFilter { task_id: Some(task.id), ..ExecutionFilter::default() };
+    let page = client.list_executions(&filter).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].id, execution_id);
+
+    let (status, body) = server.call("GET", &format!("/tasks/{}/executions?limit=5000", task.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["limit"], 1000);
+    assert_eq!(body["total"], 1);
+    let (_, stats) = server.call("GET", &format!("/tasks/{}/stats", task.id), None).await;
+    assert_eq!(stats["total"], 1);
+}
+
+#[tokio::test]
+async fn webhooks_start_the_tasks_listening_on_their_path() {
+    let server = serve(None).await;
+    let mut hook = spec("on-push");
+    hook["schedule"] = json!({ "Webhook": { "path": "git/push" } });
+    server.call("POST", "/tasks", Some(hook)).await;
+
+    let (status, body) = server.call("POST", "/hooks/git/push?ref=main", Some(json!({ "commit": "abc" }))).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let id: Uuid = serde_json::from_value(body["execution_ids"][0].clone()).unwrap();
+    let event = server.scheduler.get_execution(&id).unwrap().event.unwrap();
+    assert_eq!(event["path"], "git/push");
+    assert_eq!(event["query"]["ref"], "main");
+    assert_eq!(event["body"]["commit"], "abc");
+
+    let (status, body) = server.call("POST", "/hooks/nobody", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("nobody"), "{}", body);
+}
+
+#[tokio::test]
+async fn a_token_is_required_when_set() {
+    let server = serve(Some("s3cret")).await;
+
+    // Health checks stay open for probes.
+    assert_eq!(server.call("GET", "/health", None).await.0, StatusCode::OK);
+    assert_eq!(server.call("GET", "/ready", None).await.0, StatusCode::OK);
+
+    for (method, path) in [("GET", "/tasks"), ("GET", "/metrics"), ("GET", "/executions"), ("POST", "/hooks/x")] {
+        let (status, body) = server.call(method, path, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{} {}", method, path);
+        assert_eq!(body["error"], "missing or wrong API token");
+    }
+    let wrong = server.http.get(format!("{}/tasks", server.url)).bearer_auth("s3cret!").send().await.unwrap();
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+    let err = ApiClient::new(&server.url).list_tasks().await.unwrap_err();
+    assert!(matches!(err, ChronoError::ApiError(401, _)), "{:?}", err);
+    let client = ApiClient::new(&server.url).with_token("s3cret");
+    let spec: TaskSpec = serde_json::from_value(spec("secured")).unwrap();
+    let task = client.create_task(&spec).await.unwrap();
+    let listed = client.list_tasks().await.unwrap();
+    assert_eq!(listed.iter().map(|t| t.id).collect::<Vec<_>>(), [task.id]);
+}
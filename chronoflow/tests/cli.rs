@@ -0,0 +1,204 @@
+mod common;
+
+use assert_cmd::Command;
+#[cfg(unix)]
+use common::Scratch;
+use serde_json::Value;
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Stdio};
+use std::time::Duration;
+
+/// The binary with `args`, isolated from any server configured in the environment.
+fn chronoflow(args: &[&str]) -> Command {
+    let mut command = Command::cargo_bin("chronoflow").unwrap();
+    command.args(args).env_remove("CHRONOFLOW_URL").env_remove("CHRONOFLOW_API_TOKEN");
+    command
+}
+
+fn stdout(command: &mut Command) -> String {
+    String::from_utf8(command.assert().success().get_output().stdout.clone()).unwrap()
+}
+
+fn json(command: &mut Command) -> Value {
+    serde_json::from_str(&stdout(command)).unwrap()
+}
+
+/// Runs `command`, expecting it to fail, and returns what it printed to stderr.
+fn failure(command: &mut Command) -> String {
+    String::from_utf8(command.assert().failure().code(1).get_output().stderr.clone()).unwrap()
+}
+
+#[test]
+fn validate_cron_reports_valid_and_invalid_expressions() {
+    assert_eq!(stdout(&mut chronoflow(&["validate-cron", "*/5 * * * *"])), "✅ '*/5 * * * *' is valid\n");
+    let valid = json(&mut chronoflow(&["validate-cron", "0 9 * * MON-FRI", "-o", "json"]));
+    assert_eq!(valid, serde_json::json!({ "expression": "0 9 * * MON-FRI", "valid": true }));
+
+    let error = failure(&mut chronoflow(&["validate-cron", "61 * * * *"]));
+    assert!(error.starts_with("error: "), "{}", error);
+}
+
+#[test]
+fn next_runs_lists_fire_times_in_the_zone() {
+    let args = ["next-runs", "0 9 * * *", "-n", "3", "--after", "2024-03-09T00:00:00Z", "--timezone", "US/Eastern"];
+    // Clocks go forward on March 10th; the runs stay at 9am local time.
+    assert_eq!(
+        stdout(&mut chronoflow(&args)),
+        "2024-03-09T09:00:00-05:00\n2024-03-10T09:00:00-04:00\n2024-03-11T09:00:00-04:00\n",
+    );
+    let args = ["next-runs", "30 0 1 1 *", "-n", "2", "--after", "2024-06-01T00:00:00Z", "-o", "json"];
+    let runs = json(&mut chronoflow(&args));
+    assert_eq!(runs, serde_json::json!(["2025-01-01T00:30:00+00:00", "2026-01-01T00:30:00+00:00"]));
+
+    let error = failure(&mut chronoflow(&["next-runs", "* * * * *", "--timezone", "Mars/Olympus"]));
+    assert!(error.contains("Mars/Olympus"), "{}", error);
+}
+
+#[test]
+fn task_add_checks_its_flags_before_calling_the_server() {
+    // Nothing listens on the server URL; these fail before it is contacted.
+    let server = "http://127.0.0.1:9";
+    let error = failure(&mut chronoflow(&["--server", server, "task", "add", "--name", "x"]));
+    assert!(error.contains("one of --cron, --every, --at, --webhook, --watch, --topic or --manual"), "{}", error);
+    let add = ["--server", server, "task", "add", "--name", "x", "--manual"];
+    let error = failure(chronoflow(&add).args(["--config", "{"]));
+    assert!(error.contains("--config is not valid JSON"), "{}", error);
+    let error = failure(chronoflow(&add).args(["--notify", "[]"]));
+    assert!(error.contains("--notify is not a valid rule"), "{}", error);
+}
+
+/// A `serve` process on a free local port, killed if the test ends without stopping it.
+#[cfg(unix)]
+struct Server {
+    child: Option<Child>,
+    url: String,
+}
+
+#[cfg(unix)]
+impl Server {
+    fn start(data: &std::path::Path, token: &str) -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("chronoflow"))
+            .args(["serve", "--addr", &addr.to_string(), "--api-token", token, "--data"])
+            .arg(data)
+            .env_remove("CHRONOFLOW_TASKS_DIR")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        for _ in 0..250 {
+            if TcpStream::connect(addr).is_ok() {
+                return Server { child: Some(child), url: format!("http://{}", addr) };
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        panic!("the server didn't start listening on {}", addr);
+    }
+
+    /// A client command against this server.
+    fn client(&self, token: &str, args: &[&str]) -> Command {
+        let mut command = chronoflow(&["--server", &self.url, "--api-token", token]);
+        command.args(args);
+        command
+    }
+
+    /// Stops the server as Ctrl+C would and returns what it printed.
+    fn interrupt(mut self) -> String {
+        let child = self.child.take().unwrap();
+        // SAFETY: signalling our own child process has no memory-safety impact.
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+        }
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{:?}", output.status);
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn the_client_commands_manage_a_served_scheduler() {
+    let scratch = Scratch::new("cli");
+    let data = scratch.state_file();
+    let server = Server::start(&data, "s3cret");
+    let client = |args: &[&str]| server.client("s3cret", args);
+
+    let error = failure(&mut server.client("wrong", &["task", "list"]));
+    assert!(error.starts_with("error: "), "{}", error);
+
+    let added = json(&mut client(&[
+        "task", "add", "--name", "greet", "--manual", "--config", r#"{"message": "hi"}"#, "--overlap", "skip", "-o",
+        "json",
+    ]));
+    let id = added[0]["id"].as_str().unwrap().to_string();
+    assert_eq!(added[0]["schedule"], "Manual");
+    assert_eq!(added[0]["overlap_policy"], "Skip");
+    assert_eq!(added[0]["plugin"]["config"]["message"], "hi");
+    json(&mut client(&["task", "add", "--name", "hourly", "--every", "3600", "--disabled", "-o", "json"]));
+
+    let listed = stdout(&mut client(&["task", "list"]));
+    let lines: Vec<&str> = listed.lines().collect();
+    assert!(lines[0].starts_with("ID"), "{}", listed);
+    assert_eq!(lines.len(), 3, "{}", listed);
+    let row = |name: &str| lines.iter().find(|l| l.contains(name)).unwrap().split("  ").filter(|c| !c.is_empty())
+        .map(str::trim).collect::<Vec<_>>();
+    assert_eq!(row("greet")[1..4], ["greet", "manual", "yes"], "{}", listed);
+    assert_eq!(row("hourly")[1..4], ["hourly", "every 3600s", "no"], "{}", listed);
+
+    let disabled = json(&mut client(&["task", "disable", &id, "-o", "json"]));
+    assert_eq!(disabled[0]["enabled"], false);
+    let enabled = json(&mut client(&["task", "enable", &id, "-o", "json"]));
+    assert_eq!(enabled[0]["enabled"], true);
+
+    let started = json(&mut client(&["task", "run", &id, "-o", "json"]));
+    let execution_id = started["execution_id"].as_str().unwrap().to_string();
+    let mut execution = Value::Null;
+    for _ in 0..100 {
+        execution = json(&mut client(&["exec", "show", &execution_id, "-o", "json"]));
+        if execution["status"] == "Success" {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(execution["status"], "Success", "{}", execution);
+    let details = stdout(&mut client(&["exec", "show", &execution_id]));
+    assert!(details.contains("STATUS        Success"), "{}", details);
+    assert!(details.contains(&format!("TASK          {}", id)), "{}", details);
+
+    let logs = stdout(&mut client(&["exec", "logs", &execution_id]));
+    assert!(logs.ends_with("Logged: hi\n"), "{}", logs);
+    let page = json(&mut client(&["exec", "list", "--task", &id, "--status", "success", "-o", "json"]));
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["items"][0]["id"], execution_id.as_str());
+    let table = stdout(&mut client(&["exec", "list", "--task", &id]));
+    assert!(table.ends_with("\n1-1 of 1\n"), "{}", table);
+    let stats = json(&mut client(&["exec", "stats", "--task", &id, "-o", "json"]));
+    assert_eq!((stats["total"].clone(), stats["success_rate"].clone()), (1.into(), 1.0.into()));
+
+    let removed = json(&mut client(&["task", "rm", &id, "-o", "json"]));
+    assert_eq!(removed["deleted"], id.as_str());
+    let error = failure(&mut client(&["task", "run", &id]));
+    assert!(error.starts_with("error: "), "{}", error);
+
+    let printed = server.interrupt();
+    assert!(printed.contains("0 run(s) finished, 0 interrupted"), "{}", printed);
+
+    // What was added survives a restart from the same data file.
+    let server = Server::start(&data, "s3cret");
+    let tasks = json(&mut server.client("s3cret", &["task", "list", "-o", "json"]));
+    let names: Vec<&str> = tasks.as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["hourly"]);
+    server.interrupt();
+}
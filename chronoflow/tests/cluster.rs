@@ -0,0 +1,453 @@
+mod common;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chronoflow::cluster::{ClusterConfig, ClusterNode, Command, Entry, Peer, RaftRecord, StateMachine, WorkerConfig};
+use chronoflow::{
+    api, ChronoError, Coordinator, FileStorage, NullStorage, Plugin, PluginConfig, PluginContext, PluginManager,
+    PluginOutput, Result, Schedule, Scheduler, Storage, Task,
+};
+use common::Scratch;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Every run the nodes were asked to do: node, task and fire time.
+type Runs = Arc<Mutex<Vec<(String, Uuid, Option<DateTime<Utc>>)>>>;
+
+struct Recorder {
+    node: String,
+    runs: Runs,
+}
+
+#[async_trait]
+impl Plugin for Recorder {
+    fn name(&self) -> &str {
+        "record"
+    }
+
+    async fn execute(&self, ctx: PluginContext, _config: &Value) -> Result<PluginOutput> {
+        self.runs.lock().unwrap().push((self.node.clone(), ctx.task_id, ctx.scheduled_at));
+        Ok(PluginOutput::new("recorded"))
+    }
+}
+
+struct TestNode {
+    id: String,
+    node: Arc<ClusterNode>,
+    scheduler: Arc<Scheduler>,
+    listener: Mutex<Option<TcpListener>>,
+    server: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TestNode {
+    async fn start(&self) {
+        let listener = self.listener.lock().unwrap().take().unwrap();
+        let app = api::router(self.scheduler.clone(), None).merge(self.node.router());
+        *self.server.lock().unwrap() = Some(tokio::spawn(async move { axum::serve(listener, app).await.unwrap() }));
+        self.node.start();
+        self.scheduler.start().await;
+    }
+
+    fn stop(&self) {
+        self.node.stop();
+        if let Some(server) = self.server.lock().unwrap().take() {
+            server.abort();
+        }
+    }
+}
+
+/// Builds `size` nodes that know about each other, without starting them.
+async fn nodes(size: usize, runs: &Runs, workers: Option<WorkerConfig>) -> Vec<TestNode> {
+    let mut listeners = Vec::new();
+    for _ in 0..size {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    build(listeners, runs, |config| config.workers = workers.clone(), |_| Arc::new(NullStorage))
+}
+
+/// Builds a node serving on each listener, with `storage` for each node's index.
+fn build(
+    listeners: Vec<TcpListener>,
+    runs: &Runs,
+    configure: impl Fn(&mut ClusterConfig),
+    storage: impl Fn(usize) -> Arc<dyn Storage>,
+) -> Vec<TestNode> {
+    let peers: Vec<Peer> = listeners.iter().enumerate()
+        .map(|(i, l)| Peer { id: format!("node-{}", i), url: format!("http://{}", l.local_addr().unwrap()) })
+        .collect();
+
+    let mut nodes = Vec::new();
+    for (i, listener) in listeners.into_iter().enumerate() {
+        let id = peers[i].id.clone();
+        let others = peers.iter().filter(|p| p.id != id).cloned().collect();
+        let mut config = ClusterConfig::new(id.clone(), others);
+        configure(&mut config);
+        let node = ClusterNode::new(config, storage(i));
+        let plugins = Arc::new(PluginManager::new());
+        plugins.register(Arc::new(Recorder { node: id.clone(), runs: runs.clone() }));
+        let scheduler = Scheduler::new(plugins)
+            .with_storage(node.storage()).unwrap()
+            .with_coordinator(node.clone());
+        let scheduler = Arc::new(scheduler);
+        node.attach(&scheduler);
+        nodes.push(TestNode {
+            id,
+            node,
+            scheduler,
+            listener: Mutex::new(Some(listener)),
+            server: Mutex::new(None),
+        });
+    }
+    nodes
+}
+
+async fn cluster(size: usize, runs: &Runs) -> Vec<TestNode> {
+    let nodes = nodes(size, runs, None).await;
+    for node in &nodes {
+        node.start().await;
+    }
+    nodes
+}
+
+async fn wait_for(what: &str, mut condition: impl FnMut() -> bool) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+async fn leader(nodes: &[TestNode]) -> usize {
+    wait_for("a leader", || nodes.iter().filter(|n| n.node.is_leader()).count() == 1).await;
+    nodes.iter().position(|n| n.node.is_leader()).unwrap()
+}
+
+fn no_runs() -> Runs {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+fn every_second(name: &str) -> Task {
+    let plugin = PluginConfig { name: "record".into(), wasm_path: String::new(), config: json!({}) };
+    Task::new(name.into(), Schedule::Interval { seconds: 1 }, plugin).unwrap()
+}
+
+fn manual(name: &str) -> Task {
+    let plugin = PluginConfig { name: "record".into(), wasm_path: String::new(), config: json!({}) };
+    Task::new(name.into(), Schedule::Manual, plugin).unwrap()
+}
+
+/// Fire times recorded for `task_id`, failing on any that ran twice.
+fn unique_runs(runs: &Runs, task_id: Uuid) -> usize {
+    let mut times: Vec<DateTime<Utc>> = runs.lock().unwrap().iter()
+        .filter(|(_, id, _)| *id == task_id)
+        .map(|(_, _, at)| at.expect("scheduled runs have a fire time"))
+        .collect();
+    let total = times.len();
+    times.sort();
+    times.dedup();
+    assert_eq!(times.len(), total, "a fire time ran more than once");
+    total
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn elects_a_single_leader_that_all_nodes_follow() {
+    let nodes = cluster(3, &no_runs()).await;
+    let leader = leader(&nodes).await;
+    let leader_id = nodes[leader].node.status().node_id;
+
+    wait_for("followers to learn the leader", || {
+        nodes.iter().all(|n| n.node.status().leader_id.as_deref() == Some(leader_id.as_str()))
+    }).await;
+    let term = nodes[leader].node.status().term;
+    assert!(nodes.iter().all(|n| n.node.status().term == term));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn task_changes_replicate_and_followers_refuse_writes() {
+    let nodes = cluster(3, &no_runs()).await;
+    let leader = leader(&nodes).await;
+    let follower = (leader + 1) % nodes.len();
+
+    let id = nodes[leader].scheduler.add_task(manual("replicated")).unwrap();
+    wait_for("the task on every node", || nodes.iter().all(|n| n.scheduler.get_task(&id).is_ok())).await;
+
+    let refused = nodes[follower].scheduler.add_task(manual("rejected"));
+    match refused {
+        Err(ChronoError::ConsensusError(message)) => assert!(message.contains("leader is"), "{}", message),
+        other => panic!("expected a consensus error, got {:?}", other),
+    }
+    assert!(matches!(nodes[follower].scheduler.trigger_now(&id), Err(ChronoError::ConsensusError(_))));
+
+    nodes[leader].scheduler.remove_task(&id).unwrap();
+    wait_for("the removal on every node", || nodes.iter().all(|n| n.scheduler.get_task(&id).is_err())).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn raft_routes_require_the_cluster_secret() {
+    let mut listeners = Vec::new();
+    for _ in 0..3 {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let configure = |config: &mut ClusterConfig| config.secret = Some("s3cret".into());
+    let nodes = build(listeners, &no_runs(), configure, |_| Arc::new(NullStorage));
+    let url = format!("http://{}", nodes[0].listener.lock().unwrap().as_ref().unwrap().local_addr().unwrap());
+    for node in &nodes {
+        node.start().await;
+    }
+    // The nodes authenticate each other.
+    let leader = leader(&nodes).await;
+    nodes[leader].scheduler.add_task(manual("authenticated")).unwrap();
+
+    let vote = json!({ "term": 1000, "candidate_id": "intruder", "last_log_index": 1000, "last_log_term": 1000 });
+    let http = reqwest::Client::new();
+    for token in [None, Some("wrong")] {
+        let mut request = http.post(format!("{}/cluster/vote", url)).json(&vote);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        assert_eq!(request.send().await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+    assert!(nodes[0].node.status().term < 1000, "the vote was refused before it was counted");
+    let status = http.get(format!("{}/cluster/status", url)).send().await.unwrap();
+    assert!(status.status().is_success());
+    for node in &nodes {
+        node.stop();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn task_changes_wait_for_a_majority() {
+    let nodes = cluster(3, &no_runs()).await;
+    let leader = leader(&nodes).await;
+    let before = nodes[leader].node.status().last_log_index;
+    let id = nodes[leader].scheduler.add_task(manual("held")).unwrap();
+    let status = nodes[leader].node.status();
+    assert!(status.commit_index > before, "returned before the change committed: {:?}", status);
+
+    for (i, node) in nodes.iter().enumerate() {
+        if i != leader {
+            node.stop();
+        }
+    }
+    match nodes[leader].scheduler.remove_task(&id) {
+        Err(ChronoError::ConsensusError(message)) => assert!(message.contains("majority"), "{}", message),
+        other => panic!("expected a consensus error, got {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn each_fire_runs_on_exactly_one_node() {
+    let runs = no_runs();
+    let nodes = cluster(3, &runs).await;
+    let leader = leader(&nodes).await;
+
+    let id = nodes[leader].scheduler.add_task(every_second("tick")).unwrap();
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+
+    let count = unique_runs(&runs, id);
+    assert!(count >= 2, "expected at least two runs, got {}", count);
+    for node in &nodes {
+        assert!(!node.scheduler.list_executions(&id).is_empty(), "executions reach every node");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn firing_moves_to_the_new_leader_without_repeats() {
+    let runs = no_runs();
+    let nodes = cluster(3, &runs).await;
+    let first = leader(&nodes).await;
+
+    let id = nodes[first].scheduler.add_task(every_second("failover")).unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let before = unique_runs(&runs, id);
+    assert!(before >= 1);
+
+    nodes[first].stop();
+    wait_for("a new leader", || {
+        nodes.iter().enumerate().any(|(i, n)| i != first && n.node.is_leader())
+    }).await;
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    let after = unique_runs(&runs, id);
+    assert!(after > before, "the new leader keeps firing ({} runs before, {} after)", before, after);
+}
+
+fn workers() -> Option<WorkerConfig> {
+    Some(WorkerConfig { steal_after: Duration::from_millis(500), ..WorkerConfig::default() })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn work_queue_spreads_runs_over_task_owners() {
+    let runs = no_runs();
+    let nodes = nodes(3, &runs, workers()).await;
+    for node in &nodes {
+        node.start().await;
+    }
+    let leader = leader(&nodes).await;
+    wait_for("all members", || nodes[leader].node.status().members.len() == 3).await;
+
+    let ids: Vec<Uuid> = (0..12)
+        .map(|i| nodes[leader].scheduler.add_task(every_second(&format!("sharded-{}", i))).unwrap())
+        .collect();
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+
+    for id in &ids {
+        assert!(unique_runs(&runs, *id) >= 2);
+    }
+    // Each task's runs happened on the node owning it.
+    for (node, task_id, _) in runs.lock().unwrap().iter() {
+        assert_eq!(nodes[0].node.owner(task_id).as_deref(), Some(node.as_str()));
+    }
+    let mut busy: Vec<String> = runs.lock().unwrap().iter().map(|(node, _, _)| node.clone()).collect();
+    busy.sort();
+    busy.dedup();
+    assert!(busy.len() >= 2, "runs were spread over {:?}", busy);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_run_repeats_while_nodes_join_and_leave() {
+    let runs = no_runs();
+    let nodes = nodes(5, &runs, workers()).await;
+    for node in &nodes[..4] {
+        node.start().await;
+    }
+    let first = leader(&nodes[..4]).await;
+    let ids: Vec<Uuid> = (0..10)
+        .map(|i| nodes[first].scheduler.add_task(every_second(&format!("churn-{}", i))).unwrap())
+        .collect();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    // A node joins.
+    nodes[4].start().await;
+    wait_for("the new member", || nodes[first].node.status().members.contains(&nodes[4].id)).await;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // A follower leaves; its tasks move to the remaining members.
+    let follower = (0..4).find(|i| *i != first).unwrap();
+    nodes[follower].stop();
+    wait_for("the member to be dropped", || !nodes[first].node.status().members.contains(&nodes[follower].id)).await;
+    for id in &ids {
+        assert_ne!(nodes[first].node.owner(id).as_deref(), Some(nodes[follower].id.as_str()));
+    }
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // So does the leader.
+    nodes[first].stop();
+    let live: Vec<usize> = (0..5).filter(|i| *i != first && *i != follower).collect();
+    wait_for("a new leader", || live.iter().any(|i| nodes[*i].node.is_leader())).await;
+    let before = runs.lock().unwrap().len();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    for id in &ids {
+        unique_runs(&runs, *id);
+    }
+    assert!(runs.lock().unwrap().len() > before, "runs continue after the leader leaves");
+}
+
+fn entry(term: u64, command: Command) -> Entry {
+    Entry { term, origin: "node-0".into(), command }
+}
+
+#[test]
+fn raft_state_is_saved_and_restored() {
+    let dir = Scratch::new("cluster");
+    let path = dir.join("node.log");
+    let task = manual("saved");
+    let mut state = StateMachine::default();
+    state.tasks.insert(task.id, task.clone());
+
+    let storage = FileStorage::open(&path).unwrap();
+    let records = [
+        RaftRecord::Vote { term: 3, voted_for: Some("node-1".into()) },
+        RaftRecord::Entries {
+            index: 1,
+            entries: vec![entry(1, Command::Noop), entry(1, Command::PutTask(task.clone())), entry(2, Command::Noop)],
+        },
+        // A new leader overwrote the third entry.
+        RaftRecord::Entries { index: 3, entries: vec![entry(3, Command::Noop)] },
+        RaftRecord::Snapshot { index: 2, term: 1, state },
+        RaftRecord::Entries { index: 4, entries: vec![entry(3, Command::RemoveTask(task.id))] },
+    ];
+    for record in &records {
+        storage.save_raft(record).unwrap();
+    }
+    drop(storage);
+
+    // Loading twice also covers the log as rewritten by the first load.
+    for _ in 0..2 {
+        let raft = FileStorage::open(&path).unwrap().load().unwrap().raft.expect("saved raft state");
+        assert_eq!((raft.term, raft.voted_for.as_deref()), (3, Some("node-1")));
+        assert_eq!((raft.snapshot_index, raft.snapshot_term), (2, 1));
+        assert!(raft.snapshot.tasks.contains_key(&task.id));
+        let terms: Vec<u64> = raft.entries.iter().map(|e| e.term).collect();
+        assert_eq!(terms, [3, 3]);
+        assert!(matches!(raft.entries[1].command, Command::RemoveTask(id) if id == task.id));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tasks_survive_a_full_cluster_restart() {
+    let dir = Scratch::new("cluster");
+    let storage = |i: usize| -> Arc<dyn Storage> {
+        Arc::new(FileStorage::open(dir.join(format!("node-{}.log", i))).unwrap())
+    };
+    // A short log, so that the restart also goes through a snapshot.
+    let configure = |config: &mut ClusterConfig| config.max_log_entries = 4;
+    let mut listeners = Vec::new();
+    for _ in 0..3 {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let addrs: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+
+    let nodes = build(listeners, &no_runs(), configure, storage);
+    for node in &nodes {
+        node.start().await;
+    }
+    let first = leader(&nodes).await;
+    let ids: Vec<Uuid> = (0..6)
+        .map(|i| nodes[first].scheduler.add_task(manual(&format!("kept-{}", i))).unwrap())
+        .collect();
+    let removed = nodes[first].scheduler.add_task(manual("removed")).unwrap();
+    nodes[first].scheduler.remove_task(&removed).unwrap();
+    wait_for("the tasks on every node", || {
+        nodes.iter().all(|n| n.scheduler.list_tasks().len() == ids.len())
+    }).await;
+    for node in &nodes {
+        node.stop();
+    }
+    drop(nodes);
+
+    let mut listeners = Vec::new();
+    for addr in &addrs {
+        // The old servers let go of their ports once their aborted tasks are dropped.
+        let mut bound = TcpListener::bind(addr).await;
+        for _ in 0..100 {
+            if bound.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            bound = TcpListener::bind(addr).await;
+        }
+        listeners.push(bound.unwrap());
+    }
+    let nodes = build(listeners, &no_runs(), configure, storage);
+    for node in &nodes {
+        assert!(!node.scheduler.list_tasks().is_empty(), "{} restored tasks from its snapshot", node.id);
+        node.start().await;
+    }
+    leader(&nodes).await;
+    wait_for("the tasks on every node again", || {
+        nodes.iter().all(|n| {
+            ids.iter().all(|id| n.scheduler.get_task(id).is_ok()) && n.scheduler.get_task(&removed).is_err()
+        })
+    }).await;
+    for node in &nodes {
+        node.stop();
+    }
+}
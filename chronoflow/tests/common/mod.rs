@@ -0,0 +1,74 @@
+//! Scaffolding shared by the integration tests. Each test file is its own crate and
+//! uses only part of it.
+#![allow(dead_code)]
+
+use chronoflow::{ExecutionStatus, FileStorage, Scheduler, TaskExecution};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A fresh directory under the system temp dir, removed with everything in it on drop.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    /// Creates the directory, named after `label` so leftovers can be traced to a test.
+    pub fn new(label: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("chronoflow-{}-{}", label, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Canonical, so paths reported back by file watches compare equal.
+        Scratch(std::fs::canonicalize(dir).unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Writes `text` to `name` and returns its path.
+    pub fn write(&self, name: &str, text: &str) -> PathBuf {
+        let path = self.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    /// The state log of a [`FileStorage`] kept in the directory.
+    pub fn state_file(&self) -> PathBuf {
+        self.join("state.jsonl")
+    }
+
+    pub fn storage(&self) -> Arc<FileStorage> {
+        Arc::new(FileStorage::open(self.state_file()).unwrap())
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Waits for execution `id` to leave the queue and finish, then returns it.
+pub async fn finished(scheduler: &Scheduler, id: &Uuid) -> TaskExecution {
+    for _ in 0..300 {
+        let execution = scheduler.get_execution(id).unwrap();
+        if !matches!(execution.status, ExecutionStatus::Queued | ExecutionStatus::Running) {
+            return execution;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("execution {} didn't finish", id);
+}
+
+/// Whether process `pid` still runs; a zombie waiting to be reaped doesn't count.
+#[cfg(target_os = "linux")]
+pub fn alive(pid: u32) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // The state follows the parenthesised command name.
+        Ok(stat) => !stat.rsplit_once(") ").is_some_and(|(_, rest)| rest.starts_with('Z')),
+        Err(_) => false,
+    }
+}
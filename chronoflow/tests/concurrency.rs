@@ -0,0 +1,190 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionStatus, OverlapPolicy, Plugin, PluginConfig, PluginContext, PluginManager, PluginOutput,
+    Result, Schedule, Scheduler, SchedulerConfig, Task,
+};
+use common::finished;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Sleeps for `config.ms`, tracking how many runs were in the plugin at once.
+#[derive(Default)]
+struct Sleeper {
+    running: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+#[async_trait]
+impl Plugin for Sleeper {
+    fn name(&self) -> &str {
+        "sleeper"
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(running, Ordering::SeqCst);
+        // Counted out on drop too, in case the run is dropped mid-call.
+        let _running = Running(&self.running);
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(config["ms"].as_u64().unwrap_or(0))) => {}
+            _ = ctx.cancel.cancelled() => return Err(ChronoError::Cancelled("stopped".into())),
+        }
+        Ok(PluginOutput::new("slept"))
+    }
+}
+
+struct Running<'a>(&'a AtomicUsize);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn scheduler(config: SchedulerConfig) -> (Arc<Sleeper>, Scheduler) {
+    let sleeper = Arc::new(Sleeper::default());
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(sleeper.clone());
+    let scheduler = Scheduler::new(plugins).with_config(config);
+    scheduler.start().await;
+    (sleeper, scheduler)
+}
+
+fn sleeps(name: &str, ms: u64, overlap: OverlapPolicy) -> Task {
+    let plugin = PluginConfig { name: "sleeper".into(), wasm_path: String::new(), config: json!({ "ms": ms }) };
+    Task::new(name.into(), Schedule::Manual, plugin).unwrap().with_overlap_policy(overlap)
+}
+
+fn status(scheduler: &Scheduler, id: &Uuid) -> ExecutionStatus {
+    scheduler.get_execution(id).unwrap().status
+}
+
+#[tokio::test]
+async fn allow_runs_overlapping_fires_side_by_side() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(sleeps("allow", 300, OverlapPolicy::Allow)).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+    let second = scheduler.trigger_now(&id).unwrap();
+
+    assert_eq!(finished(&scheduler, &first).await.status, ExecutionStatus::Success);
+    assert_eq!(finished(&scheduler, &second).await.status, ExecutionStatus::Success);
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn skip_records_the_fire_it_dropped() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(sleeps("skip", 300, OverlapPolicy::Skip)).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+    let second = scheduler.trigger_now(&id).unwrap();
+
+    let skipped = scheduler.get_execution(&second).unwrap();
+    assert_eq!(skipped.status, ExecutionStatus::Skipped);
+    let expected = format!("skipped: execution {} of this task is still in progress", first);
+    assert_eq!(skipped.error, Some(expected));
+    assert_eq!(skipped.duration_ms, Some(0));
+    assert_eq!(finished(&scheduler, &first).await.status, ExecutionStatus::Success);
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 1);
+
+    // Once the earlier run is done, the next fire runs again.
+    let third = scheduler.trigger_now(&id).unwrap();
+    assert_eq!(finished(&scheduler, &third).await.status, ExecutionStatus::Success);
+}
+
+#[tokio::test]
+async fn queue_runs_overlapping_fires_one_after_another() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(sleeps("queue", 200, OverlapPolicy::Queue)).unwrap();
+    let ids: Vec<Uuid> = (0..3).map(|_| scheduler.trigger_now(&id).unwrap()).collect();
+    assert_eq!(status(&scheduler, &ids[1]), ExecutionStatus::Queued);
+    assert_eq!(status(&scheduler, &ids[2]), ExecutionStatus::Queued);
+
+    let mut runs = Vec::new();
+    for id in &ids {
+        runs.push(finished(&scheduler, id).await);
+    }
+    assert!(runs.iter().all(|r| r.status == ExecutionStatus::Success), "{:?}", runs);
+    for pair in runs.windows(2) {
+        assert!(pair[0].finished_at.unwrap() <= pair[1].started_at, "runs in the order they fired");
+    }
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn cancel_previous_stops_the_older_run() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(sleeps("latest", 400, OverlapPolicy::CancelPrevious)).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let second = scheduler.trigger_now(&id).unwrap();
+
+    let cancelled = finished(&scheduler, &first).await;
+    assert_eq!(cancelled.status, ExecutionStatus::Cancelled);
+    assert_eq!(cancelled.error.as_deref(), Some("Execution cancelled: superseded by a newer run of the task"));
+    assert_eq!(status(&scheduler, &second), ExecutionStatus::Running);
+    assert_eq!(finished(&scheduler, &second).await.status, ExecutionStatus::Success);
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn removing_a_task_cancels_its_queued_runs() {
+    let (_, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(sleeps("doomed", 300, OverlapPolicy::Queue)).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+    let queued = scheduler.trigger_now(&id).unwrap();
+    scheduler.remove_task(&id).unwrap();
+
+    assert_eq!(finished(&scheduler, &first).await.status, ExecutionStatus::Success);
+    let queued = finished(&scheduler, &queued).await;
+    assert_eq!(queued.status, ExecutionStatus::Cancelled);
+    assert_eq!(queued.error.as_deref(), Some("Execution cancelled: task was removed"));
+}
+
+#[tokio::test]
+async fn the_global_limit_holds_runs_as_queued() {
+    let config = SchedulerConfig { max_concurrent_runs: Some(2), ..SchedulerConfig::default() };
+    let (sleeper, scheduler) = scheduler(config).await;
+    let mut ids = Vec::new();
+    for n in 0..4 {
+        let task = scheduler.add_task(sleeps(&format!("task-{}", n), 300, OverlapPolicy::Allow)).unwrap();
+        ids.push(scheduler.trigger_now(&task).unwrap());
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let count = |wanted| ids.iter().filter(|id| status(&scheduler, id) == wanted).count();
+    assert_eq!((count(ExecutionStatus::Running), count(ExecutionStatus::Queued)), (2, 2));
+
+    for id in &ids {
+        assert_eq!(finished(&scheduler, id).await.status, ExecutionStatus::Success);
+    }
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn a_plugin_limit_only_holds_back_that_plugin() {
+    let config = SchedulerConfig {
+        plugin_concurrency: HashMap::from([("sleeper".to_string(), 1)]),
+        ..SchedulerConfig::default()
+    };
+    let (sleeper, scheduler) = scheduler(config).await;
+    let a = scheduler.add_task(sleeps("a", 300, OverlapPolicy::Allow)).unwrap();
+    let b = scheduler.add_task(sleeps("b", 300, OverlapPolicy::Allow)).unwrap();
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    let logger = scheduler.add_task(Task::new("logger".into(), Schedule::Manual, plugin).unwrap()).unwrap();
+
+    let (a, b) = (scheduler.trigger_now(&a).unwrap(), scheduler.trigger_now(&b).unwrap());
+    let logged = scheduler.trigger_now(&logger).unwrap();
+    let logged = finished(&scheduler, &logged).await;
+    assert_eq!(logged.status, ExecutionStatus::Success);
+    let waiting = [status(&scheduler, &a), status(&scheduler, &b)];
+    assert!(waiting.contains(&ExecutionStatus::Queued), "{:?}", waiting);
+
+    assert_eq!(finished(&scheduler, &a).await.status, ExecutionStatus::Success);
+    assert_eq!(finished(&scheduler, &b).await.status, ExecutionStatus::Success);
+    assert_eq!(sleeper.peak.load(Ordering::SeqCst), 1);
+}
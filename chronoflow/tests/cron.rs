@@ -0,0 +1,180 @@
+use chrono::{DateTime, TimeZone, Utc};
+use chronoflow::{ChronoError, CronExpr};
+
+fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+}
+
+/// The first `n` fire times of `expr` after `after`, as `2024-01-31 Wed 00:00:00`.
+fn runs_after(expr: &str, after: DateTime<Utc>, n: usize) -> Vec<String> {
+    CronExpr::parse(expr)
+        .unwrap()
+        .upcoming(after)
+        .take(n)
+        .map(|t| t.format("%Y-%m-%d %a %H:%M:%S").to_string())
+        .collect()
+}
+
+/// Like [`runs_after`], from the start of 2024 (a Monday).
+fn runs(expr: &str, n: usize) -> Vec<String> {
+    runs_after(expr, at(2024, 1, 1, 0, 0, 0), n)
+}
+
+#[test]
+fn steps_ranges_and_lists() {
+    assert_eq!(runs("*/15 * * * *", 4), [
+        "2024-01-01 Mon 00:15:00", "2024-01-01 Mon 00:30:00", "2024-01-01 Mon 00:45:00", "2024-01-01 Mon 01:00:00",
+    ]);
+    // A single start with a step runs from there to the end of the range.
+    assert_eq!(runs("5/20 * * * *", 4), [
+        "2024-01-01 Mon 00:05:00", "2024-01-01 Mon 00:25:00", "2024-01-01 Mon 00:45:00", "2024-01-01 Mon 01:05:00",
+    ]);
+    assert_eq!(runs("0 0-12/6 * * *", 3), [
+        "2024-01-01 Mon 06:00:00", "2024-01-01 Mon 12:00:00", "2024-01-02 Tue 00:00:00",
+    ]);
+    assert_eq!(runs("0 8,17 * * 1-5", 4), [
+        "2024-01-01 Mon 08:00:00", "2024-01-01 Mon 17:00:00", "2024-01-02 Tue 08:00:00", "2024-01-02 Tue 17:00:00",
+    ]);
+    // Six fields put seconds first.
+    assert_eq!(runs("*/20 * * * * *", 3), [
+        "2024-01-01 Mon 00:00:20", "2024-01-01 Mon 00:00:40", "2024-01-01 Mon 00:01:00",
+    ]);
+}
+
+#[test]
+fn next_after_is_strictly_after() {
+    let cron = CronExpr::parse("30 9 * * *").unwrap();
+    assert_eq!(cron.next_after(at(2024, 1, 1, 9, 29, 59)), Some(at(2024, 1, 1, 9, 30, 0)));
+    assert_eq!(cron.next_after(at(2024, 1, 1, 9, 30, 0)), Some(at(2024, 1, 2, 9, 30, 0)));
+    // Sub-second parts don't make the same second count as later.
+    let just_after = at(2024, 1, 1, 9, 30, 0) + chrono::Duration::milliseconds(500);
+    assert_eq!(cron.next_after(just_after), Some(at(2024, 1, 2, 9, 30, 0)));
+}
+
+#[test]
+fn month_and_day_names() {
+    assert_eq!(runs("0 12 1 jun,DEC *", 3), [
+        "2024-06-01 Sat 12:00:00", "2024-12-01 Sun 12:00:00", "2025-06-01 Sun 12:00:00",
+    ]);
+    assert_eq!(runs("0 9 * JAN-FEB MON-WED", 4), [
+        "2024-01-01 Mon 09:00:00", "2024-01-02 Tue 09:00:00", "2024-01-03 Wed 09:00:00", "2024-01-08 Mon 09:00:00",
+    ]);
+    assert_eq!(runs("0 0 * Nov sat", 1), ["2024-11-02 Sat 00:00:00"]);
+    // 7 and SUN are both Sunday.
+    assert_eq!(runs("0 0 * * 7", 2), runs("0 0 * * SUN", 2));
+    assert_eq!(runs("0 0 * * 0", 1), ["2024-01-07 Sun 00:00:00"]);
+}
+
+#[test]
+fn either_day_field_matches_when_both_are_set() {
+    assert_eq!(runs("0 0 13 * FRI", 4), [
+        "2024-01-05 Fri 00:00:00", "2024-01-12 Fri 00:00:00", "2024-01-13 Sat 00:00:00", "2024-01-19 Fri 00:00:00",
+    ]);
+    // `?` leaves a field unrestricted, like `*`.
+    assert_eq!(runs("0 0 13 * ?", 2), ["2024-01-13 Sat 00:00:00", "2024-02-13 Tue 00:00:00"]);
+}
+
+#[test]
+fn a_day_field_covering_its_whole_range_is_unrestricted() {
+    // Every day of the month leaves only the weekday to match, rather than every day.
+    let mondays = ["2024-01-08 Mon 00:00:00", "2024-01-15 Mon 00:00:00", "2024-01-22 Mon 00:00:00"];
+    assert_eq!(runs("0 0 1-31 * MON", 3), mondays);
+    assert_eq!(runs("0 0 */1 * MON", 3), mondays);
+    assert_eq!(runs("0 0 13 * 0-6", 2), ["2024-01-13 Sat 00:00:00", "2024-02-13 Tue 00:00:00"]);
+    assert_eq!(runs("0 0 13 * 1-7", 2), ["2024-01-13 Sat 00:00:00", "2024-02-13 Tue 00:00:00"]);
+}
+
+#[test]
+fn last_day_and_weekday_of_the_month() {
+    assert_eq!(runs("0 0 L * *", 4), [
+        "2024-01-31 Wed 00:00:00", "2024-02-29 Thu 00:00:00", "2024-03-31 Sun 00:00:00", "2024-04-30 Tue 00:00:00",
+    ]);
+    // March and June end on a Sunday, so their last weekday is the Friday before.
+    assert_eq!(runs("0 0 LW * *", 6), [
+        "2024-01-31 Wed 00:00:00", "2024-02-29 Thu 00:00:00", "2024-03-29 Fri 00:00:00",
+        "2024-04-30 Tue 00:00:00", "2024-05-31 Fri 00:00:00", "2024-06-28 Fri 00:00:00",
+    ]);
+}
+
+#[test]
+fn nearest_weekday() {
+    // June 15th is a Saturday and September 15th a Sunday.
+    assert_eq!(runs_after("0 0 15W * *", at(2024, 5, 20, 0, 0, 0), 4), [
+        "2024-06-14 Fri 00:00:00", "2024-07-15 Mon 00:00:00", "2024-08-15 Thu 00:00:00", "2024-09-16 Mon 00:00:00",
+    ]);
+    // A Saturday 1st moves forward rather than into the previous month.
+    assert_eq!(runs_after("0 0 1W * *", at(2024, 5, 2, 0, 0, 0), 1), ["2024-06-03 Mon 00:00:00"]);
+    // And a Sunday 31st moves back.
+    assert_eq!(runs_after("0 0 31W * *", at(2024, 3, 1, 0, 0, 0), 1), ["2024-03-29 Fri 00:00:00"]);
+}
+
+#[test]
+fn last_and_nth_weekday_of_the_month() {
+    assert_eq!(runs("0 0 * * 5L", 4), [
+        "2024-01-26 Fri 00:00:00", "2024-02-23 Fri 00:00:00", "2024-03-29 Fri 00:00:00", "2024-04-26 Fri 00:00:00",
+    ]);
+    assert_eq!(runs("0 0 * * MON#2", 3), [
+        "2024-01-08 Mon 00:00:00", "2024-02-12 Mon 00:00:00", "2024-03-11 Mon 00:00:00",
+    ]);
+    // Months without a fifth Monday are skipped.
+    assert_eq!(runs("0 0 * * 1#5", 4), [
+        "2024-01-29 Mon 00:00:00", "2024-04-29 Mon 00:00:00", "2024-07-29 Mon 00:00:00", "2024-09-30 Mon 00:00:00",
+    ]);
+}
+
+#[test]
+fn macros() {
+    assert_eq!(runs("@yearly", 1), ["2025-01-01 Wed 00:00:00"]);
+    assert_eq!(runs("@annually", 1), runs("@yearly", 1));
+    assert_eq!(runs("@monthly", 1), ["2024-02-01 Thu 00:00:00"]);
+    assert_eq!(runs("@weekly", 1), ["2024-01-07 Sun 00:00:00"]);
+    assert_eq!(runs("@daily", 1), ["2024-01-02 Tue 00:00:00"]);
+    assert_eq!(runs("@MIDNIGHT", 1), runs("@daily", 1));
+    assert_eq!(runs("@hourly", 2), ["2024-01-01 Mon 01:00:00", "2024-01-01 Mon 02:00:00"]);
+}
+
+#[test]
+fn rare_and_impossible_dates() {
+    assert_eq!(runs_after("0 0 29 2 *", at(2024, 3, 1, 0, 0, 0), 1), ["2028-02-29 Tue 00:00:00"]);
+    assert_eq!(CronExpr::parse("0 0 30 2 *").unwrap().next_after(at(2024, 1, 1, 0, 0, 0)), None);
+    assert_eq!(CronExpr::parse("0 0 31 4,6,9,11 *").unwrap().next_after(at(2024, 1, 1, 0, 0, 0)), None);
+}
+
+#[test]
+fn invalid_expressions_say_what_is_wrong() {
+    let cases = [
+        ("", "expected 5 or 6 fields, found 0"),
+        ("* * * *", "expected 5 or 6 fields, found 4"),
+        ("* * * * * * *", "expected 5 or 6 fields, found 7"),
+        ("@reboot", "unknown macro '@reboot'"),
+        ("60 * * * * *", "seconds: value 60 out of range 0-59"),
+        ("60 * * * *", "minutes: value 60 out of range 0-59"),
+        ("* 24 * * *", "hours: value 24 out of range 0-23"),
+        ("* * 0 * *", "day of month: value 0 out of range 1-31"),
+        ("* * 32W * *", "day of month: value 32 out of range 1-31"),
+        ("* * * 13 *", "month: value 13 out of range 1-12"),
+        ("* * * FOO *", "month: invalid value 'FOO'"),
+        ("* * * * 8", "day of week: value 8 out of range 0-7"),
+        ("* * * * 1#6", "day of week: value 6 out of range 1-5"),
+        ("*/0 * * * *", "minutes: step must be greater than zero"),
+        ("*/x * * * *", "minutes: invalid step 'x'"),
+        ("5-1 * * * *", "minutes: range '5-1' is reversed"),
+        ("a * * * *", "minutes: invalid value 'a'"),
+    ];
+    for (expr, reason) in cases {
+        match CronExpr::parse(expr) {
+            Err(ChronoError::InvalidCron(message)) => {
+                assert_eq!(message, format!("'{}': {}", expr, reason));
+            }
+            other => panic!("{:?} should be invalid, got {:?}", expr, other),
+        }
+    }
+}
+
+#[test]
+fn an_expression_keeps_its_source() {
+    let cron: CronExpr = "  0 9 * * MON-FRI ".parse().unwrap();
+    assert_eq!(cron.as_str(), "0 9 * * MON-FRI");
+    assert_eq!(cron.to_string(), "0 9 * * MON-FRI");
+    assert_eq!(cron, CronExpr::parse("0 9 * * MON-FRI").unwrap());
+}
@@ -0,0 +1,321 @@
+mod common;
+
+use chronoflow::definitions::{self, definition_id, SyncReport};
+use chronoflow::{
+    ChronoError, PluginConfig, PluginManager, Result, Schedule, Scheduler, Snapshot, Storage, Task, TaskExecution,
+    TriggerCondition, WorkflowRun,
+};
+use common::Scratch;
+use serde_json::json;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+fn error(result: Result<impl std::fmt::Debug>) -> String {
+    match result {
+        Err(ChronoError::InvalidDefinition(message)) => message,
+        other => panic!("expected an invalid definition, got {:?}", other),
+    }
+}
+
+fn names(scheduler: &Scheduler) -> Vec<String> {
+    let mut names: Vec<String> = scheduler.list_tasks().into_iter().map(|t| t.name).collect();
+    names.sort();
+    names
+}
+
+const PIPELINE: &str = "\
+tasks:
+  - name: fetch
+    cron: \"*/15 * * * *\"
+    timezone: Europe/Berlin
+    plugin: logger
+    config:
+      message: fetching
+  - name: report
+    plugin: logger
+    depends_on:
+      - fetch
+      - task: fetch
+        condition: OnFailure
+";
+
+#[test]
+fn yaml_and_toml_files_are_parsed() {
+    let dir = Scratch::new("definitions");
+    let yaml = dir.write("a.yaml", PIPELINE);
+    dir.write("b.toml", "\
+[[tasks]]
+name = \"hook\"
+webhook = \"deploy\"
+plugin = \"logger\"
+enabled = false
+");
+    dir.write("notes.txt", "not a definition");
+
+    let loaded = definitions::load_dir(dir.path()).unwrap();
+    let found: Vec<(&str, usize)> = loaded.iter().map(|d| (d.task.name.as_str(), d.line)).collect();
+    assert_eq!(found, [("fetch", 2), ("report", 8), ("hook", 2)]);
+
+    let fetch = &loaded[0].task;
+    assert_eq!(fetch.id, definition_id("fetch"));
+    assert_eq!(fetch.schedule, Schedule::Cron("*/15 * * * *".into()));
+    assert_eq!(fetch.timezone, chrono_tz::Europe::Berlin);
+    assert_eq!(fetch.plugin.config, json!({ "message": "fetching" }));
+    assert_eq!(fetch.source.as_deref(), Some(yaml.display().to_string().as_str()));
+
+    let report = &loaded[1].task;
+    assert_eq!(report.schedule, Schedule::Manual);
+    let deps: Vec<_> = report.depends_on.iter().map(|d| (d.task_id, d.condition)).collect();
+    assert_eq!(deps, [(fetch.id, TriggerCondition::OnSuccess), (fetch.id, TriggerCondition::OnFailure)]);
+
+    let hook = &loaded[2].task;
+    assert_eq!(hook.schedule, Schedule::Webhook { path: "deploy".into() });
+    assert!(!hook.enabled);
+}
+
+#[test]
+fn errors_name_the_file_and_line() {
+    let dir = Scratch::new("definitions");
+    let broken = dir.write("broken.yaml", "tasks:\n  - name: a\n    plugin: logger\n   cron: bad indent\n");
+    let message = error(definitions::load_dir(dir.path()));
+    assert!(message.starts_with(&format!("{}:4:", broken.display())), "{}", message);
+    std::fs::remove_file(dir.join("broken.yaml")).unwrap();
+
+    let toml = dir.write("broken.toml", "[[tasks]]\nname = \"a\"\nplugin = \"logger\"\nevery = \"often\"\n");
+    let message = error(definitions::load_dir(dir.path()));
+    assert!(message.starts_with(&format!("{}:4:", toml.display())), "{}", message);
+    std::fs::remove_file(dir.join("broken.toml")).unwrap();
+
+    let tasks = dir.write("tasks.yaml", "\
+tasks:
+  - name: fine
+    plugin: logger
+  - name: bad-cron
+    cron: \"61 * * * *\"
+    plugin: logger
+  - name: typo
+    plugin: logger
+    retires: 3
+");
+    let message = error(definitions::load_dir(dir.path()));
+    // Unknown keys are caught while parsing, before any task is built.
+    assert!(message.starts_with(&format!("{}:", tasks.display())), "{}", message);
+    assert!(message.contains("unknown field `retires`"), "{}", message);
+    let fixed = std::fs::read_to_string(&tasks).unwrap().replace("retires", "timeout_seconds");
+    std::fs::write(&tasks, fixed).unwrap();
+
+    // Every file's first problem is reported at once.
+    let other = dir.write("zz.yaml", "tasks:\n  - name: two-triggers\n    every: 60\n    topic: deploys\n    plugin: logger\n");
+    let message = error(definitions::load_dir(dir.path()));
+    let lines: Vec<&str> = message.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", message);
+    assert!(lines[0].starts_with(&format!("{}:4: task 'bad-cron':", tasks.display())), "{}", message);
+    assert!(lines[0].contains("out of range"), "{}", message);
+    assert!(lines[1].starts_with(&format!("{}:2: task 'two-triggers':", other.display())), "{}", message);
+    assert!(lines[1].contains("only one of"), "{}", message);
+}
+
+#[test]
+fn duplicate_names_and_missing_dependencies_are_reported_together() {
+    let dir = Scratch::new("definitions");
+    let first = dir.write("a.yaml", "tasks:\n  - name: same\n    plugin: logger\n");
+    let second = dir.write("b.yaml", "\
+tasks:
+  - name: same
+    plugin: logger
+  - name: orphan
+    plugin: logger
+    depends_on: [missing]
+");
+    let message = error(definitions::load_dir(dir.path()));
+    assert!(
+        message.contains(&format!("{}:2: task 'same': already defined at {}:2", second.display(), first.display())),
+        "{}",
+        message
+    );
+    assert!(message.contains(&format!("{}:4: task 'orphan': depends on an undefined task", second.display())));
+    assert!(message.contains(&definition_id("missing").to_string()), "{}", message);
+}
+
+#[test]
+fn a_cycle_is_refused() {
+    let dir = Scratch::new("definitions");
+    dir.write("a.yaml", "\
+tasks:
+  - name: a
+    plugin: logger
+    depends_on: [b]
+  - name: b
+    plugin: logger
+    depends_on: [a]
+");
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    let message = error(definitions::load_and_sync(&scheduler, dir.path()));
+    assert!(message.contains("dependency cycle"), "{}", message);
+    assert!(scheduler.list_tasks().is_empty());
+}
+
+fn api_task(name: &str, depends_on: Option<Uuid>) -> Task {
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    let mut task = Task::new(name.into(), Schedule::Manual, plugin).unwrap();
+    if let Some(upstream) = depends_on {
+        task.depends_on = vec![chronoflow::Dependency { task_id: upstream, condition: TriggerCondition::OnSuccess }];
+    }
+    task
+}
+
+#[test]
+fn sync_adds_updates_and_removes_file_tasks_only() {
+    let dir = Scratch::new("definitions");
+    dir.write("a.yaml", PIPELINE);
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    scheduler.add_task(api_task("from-api", None)).unwrap();
+
+    let report = definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+    assert_eq!(report.added, ["fetch", "report"]);
+    assert_eq!(names(&scheduler), ["fetch", "from-api", "report"]);
+    assert!(definitions::load_and_sync(&scheduler, dir.path()).unwrap().is_empty());
+
+    let created = scheduler.get_task(&definition_id("fetch")).unwrap().created_at;
+    dir.write("a.yaml", &PIPELINE.replace("fetching", "fetching again").replace("      - fetch\n", ""));
+    let report = definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+    assert_eq!(report, SyncReport { updated: vec!["fetch".into(), "report".into()], ..SyncReport::default() });
+    let fetch = scheduler.get_task(&definition_id("fetch")).unwrap();
+    assert_eq!(fetch.plugin.config, json!({ "message": "fetching again" }));
+    assert_eq!(fetch.created_at, created, "an update keeps the task's history");
+
+    dir.write("a.yaml", "tasks: []\n");
+    let report = definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+    assert_eq!(report.removed.len(), 2);
+    assert_eq!(names(&scheduler), ["from-api"]);
+}
+
+#[test]
+fn an_invalid_definition_changes_nothing() {
+    let dir = Scratch::new("definitions");
+    dir.write("a.yaml", PIPELINE);
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+
+    // Parses, but names a plugin that isn't registered; only the scheduler can tell.
+    dir.write("a.yaml", &PIPELINE.replace("fetching", "changed"));
+    dir.write("b.yaml", "tasks:\n  - name: extra\n    plugin: logger\n  - name: ghost\n    plugin: nope\n");
+    let message = error(definitions::load_and_sync(&scheduler, dir.path()));
+    assert!(message.contains("b.yaml:4: task 'ghost'"), "{}", message);
+    assert_eq!(names(&scheduler), ["fetch", "report"]);
+    let fetch = scheduler.get_task(&definition_id("fetch")).unwrap();
+    assert_eq!(fetch.plugin.config, json!({ "message": "fetching" }));
+}
+
+#[test]
+fn a_removal_blocked_by_an_api_task_changes_nothing() {
+    let dir = Scratch::new("definitions");
+    dir.write("a.yaml", PIPELINE);
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+    scheduler.add_task(api_task("downstream", Some(definition_id("report")))).unwrap();
+
+    dir.write("a.yaml", "tasks:\n  - name: fetch\n    plugin: logger\n  - name: new\n    plugin: logger\n");
+    let message = error(definitions::load_and_sync(&scheduler, dir.path()));
+    assert!(message.contains("'report' (a dependency of 'downstream')"), "{}", message);
+    assert_eq!(names(&scheduler), ["downstream", "fetch", "report"]);
+    assert_eq!(scheduler.get_task(&definition_id("fetch")).unwrap().schedule, Schedule::Cron("*/15 * * * *".into()));
+}
+
+/// Storage that fails to save tasks while `broken` is set.
+#[derive(Default)]
+struct FlakyStorage {
+    broken: AtomicBool,
+}
+
+impl Storage for FlakyStorage {
+    fn save_task(&self, task: &Task) -> Result<()> {
+        if self.broken.load(Ordering::SeqCst) && task.name == "last" {
+            return Err(ChronoError::StorageError("disk full".into()));
+        }
+        Ok(())
+    }
+
+    fn delete_task(&self, _id: &Uuid) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_execution(&self, _execution: &TaskExecution) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_executions(&self, _ids: &[Uuid]) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_workflow_run(&self, _run: &WorkflowRun) -> Result<()> {
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Snapshot> {
+        Ok(Snapshot::default())
+    }
+}
+
+#[test]
+fn a_change_that_fails_to_apply_undoes_the_ones_before_it() {
+    let dir = Scratch::new("definitions");
+    dir.write("a.yaml", PIPELINE);
+    let storage = Arc::new(FlakyStorage::default());
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new())).with_storage(storage.clone()).unwrap();
+    definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+
+    // `fetch` is updated and `first` added before saving `last` fails.
+    dir.write("a.yaml", &format!(
+        "{}  - name: first\n    plugin: logger\n  - name: last\n    plugin: logger\n    depends_on: [first]\n",
+        PIPELINE.replace("fetching", "changed")
+    ));
+    storage.broken.store(true, Ordering::SeqCst);
+    let err = definitions::load_and_sync(&scheduler, dir.path()).unwrap_err();
+    assert!(err.to_string().contains("disk full"), "{}", err);
+    assert_eq!(names(&scheduler), ["fetch", "report"]);
+    let fetch = scheduler.get_task(&definition_id("fetch")).unwrap();
+    assert_eq!(fetch.plugin.config, json!({ "message": "fetching" }));
+
+    storage.broken.store(false, Ordering::SeqCst);
+    let report = definitions::load_and_sync(&scheduler, dir.path()).unwrap();
+    assert_eq!(report.added, ["first", "last"]);
+    assert_eq!(report.updated, ["fetch"]);
+}
+
+async fn wait_for(scheduler: &Scheduler, expected: &[&str]) {
+    for _ in 0..100 {
+        if names(scheduler) == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(names(scheduler), expected);
+}
+
+#[tokio::test]
+async fn the_watcher_reloads_changed_files() {
+    let dir = Scratch::new("definitions");
+    let scheduler = Arc::new(Scheduler::new(Arc::new(PluginManager::new())));
+    let _watcher = definitions::watch(scheduler.clone(), dir.path().to_path_buf()).unwrap();
+
+    dir.write("a.yaml", PIPELINE);
+    wait_for(&scheduler, &["fetch", "report"]).await;
+
+    // A broken edit is reported and leaves the tasks as they were.
+    dir.write("a.yaml", "tasks:\n  - name: fetch\n    plugin: [\n");
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    assert_eq!(names(&scheduler), ["fetch", "report"]);
+
+    dir.write("b.toml", "[[tasks]]\nname = \"later\"\nplugin = \"logger\"\n");
+    dir.write("a.yaml", "tasks: []\n");
+    wait_for(&scheduler, &["later"]).await;
+    assert_eq!(load(dir.path()), ["later"]);
+}
+
+fn load(dir: &Path) -> Vec<String> {
+    definitions::load_dir(dir).unwrap().into_iter().map(|d| d.task.name).collect()
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use chronoflow::{api, events, ExecutionStatus, PluginConfig, PluginManager, Schedule, Scheduler, Task, TaskExecution};
+use common::{finished, Scratch};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+async fn scheduler() -> Arc<Scheduler> {
+    let scheduler = Arc::new(Scheduler::new(Arc::new(PluginManager::new())));
+    scheduler.start().await;
+    scheduler
+}
+
+/// A logger task that logs `message`, rendered against the event.
+fn task(name: &str, schedule: Schedule, message: &str) -> Task {
+    let config = json!({ "message": message });
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config };
+    Task::new(name.into(), schedule, plugin).unwrap()
+}
+
+/// Serves the API for `scheduler`, returning its base URL.
+async fn serve(scheduler: &Arc<Scheduler>) -> String {
+    serve_with_token(scheduler, None).await
+}
+
+async fn serve_with_token(scheduler: &Arc<Scheduler>, token: Option<&str>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = api::router(scheduler.clone(), token.map(String::from));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn runs_of(scheduler: &Scheduler, task_id: &Uuid) -> Vec<TaskExecution> {
+    scheduler.list_executions(task_id)
+}
+
+#[tokio::test]
+async fn a_webhook_request_runs_the_tasks_on_its_path() {
+    let scheduler = scheduler().await;
+    let deploy = scheduler
+        .add_task(task("deploy", Schedule::Webhook { path: "/deploy/".into() }, "ref {{ event.body.ref }}"))
+        .unwrap();
+    let mut paused = task("paused", Schedule::Webhook { path: "deploy".into() }, "");
+    paused.enabled = false;
+    let paused = scheduler.add_task(paused).unwrap();
+    let other = scheduler.add_task(task("other", Schedule::Webhook { path: "other".into() }, "")).unwrap();
+
+    let url = format!("{}/hooks/deploy?dry_run=1", serve(&scheduler).await);
+    let response = reqwest::Client::new().post(url)
+        .header("x-source", "ci")
+        .json(&json!({ "ref": "v1.2" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let ids: Vec<Uuid> = serde_json::from_value(body["execution_ids"].clone()).unwrap();
+    assert_eq!(ids.len(), 1, "{}", body);
+
+    let execution = finished(&scheduler, &ids[0]).await;
+    assert_eq!(execution.task_id, deploy);
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(execution.output.unwrap().message, "Logged: ref v1.2");
+    let event = execution.event.unwrap();
+    assert_eq!(event["source"], "webhook");
+    assert_eq!(event["path"], "deploy");
+    assert_eq!(event["query"], json!({ "dry_run": "1" }));
+    assert_eq!(event["headers"]["x-source"], "ci");
+    assert!(runs_of(&scheduler, &paused).is_empty());
+    assert!(runs_of(&scheduler, &other).is_empty());
+}
+
+#[tokio::test]
+async fn a_webhook_body_that_is_not_json_is_kept_as_text() {
+    let scheduler = scheduler().await;
+    scheduler.add_task(task("raw", Schedule::Webhook { path: "raw".into() }, "")).unwrap();
+    let url = serve(&scheduler).await;
+    let http = reqwest::Client::new();
+
+    let mut bodies = Vec::new();
+    for body in ["plain text", ""] {
+        let response = http.post(format!("{}/hooks/raw", url)).body(body).send().await.unwrap();
+        let response: Value = response.json().await.unwrap();
+        let id: Uuid = serde_json::from_value(response["execution_ids"][0].clone()).unwrap();
+        bodies.push(finished(&scheduler, &id).await.event.unwrap()["body"].clone());
+    }
+    assert_eq!(bodies, [json!("plain text"), Value::Null]);
+
+    let response = http.post(format!("{}/hooks/nobody", url)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn credentials_are_kept_out_of_the_webhook_event() {
+    let scheduler = scheduler().await;
+    scheduler.add_task(task("guarded", Schedule::Webhook { path: "guarded".into() }, "")).unwrap();
+    let url = format!("{}/hooks/guarded", serve_with_token(&scheduler, Some("s3cret")).await);
+    let response = reqwest::Client::new().post(url)
+        .bearer_auth("s3cret")
+        .header("proxy-authorization", "Basic cHJveHk6czNjcmV0")
+        .header("cookie", "session=s3cret")
+        .header("x-source", "ci")
+        .send().await.unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let id: Uuid = serde_json::from_value(body["execution_ids"][0].clone()).unwrap();
+
+    finished(&scheduler, &id).await;
+    let event = scheduler.get_execution(&id).unwrap().event.unwrap();
+    let headers = event["headers"].as_object().unwrap();
+    for name in ["authorization", "proxy-authorization", "cookie"] {
+        assert!(!headers.contains_key(name), "{} leaked: {}", name, event);
+    }
+    assert_eq!(headers["x-source"], "ci");
+    assert!(!event.to_string().contains("s3cret"), "{}", event);
+}
+
+#[tokio::test]
+async fn publishing_runs_the_topic_subscribers() {
+    let scheduler = scheduler().await;
+    let orders = || Schedule::Topic { name: "orders".into() };
+    let first = scheduler.add_task(task("first", orders(), "order {{ event.payload.id }}")).unwrap();
+    let second = scheduler.add_task(task("second", orders(), "")).unwrap();
+    let elsewhere = scheduler.add_task(task("elsewhere", Schedule::Topic { name: "refunds".into() }, "")).unwrap();
+
+    let ids = scheduler.publish("orders", json!({ "id": 42 })).unwrap();
+    assert_eq!(ids.len(), 2);
+    for id in &ids {
+        let execution = finished(&scheduler, id).await;
+        assert_eq!(execution.status, ExecutionStatus::Success);
+        assert_eq!(execution.event.unwrap(), json!({ "source": "topic", "topic": "orders", "payload": { "id": 42 } }));
+    }
+    let mut started: Vec<Uuid> = ids.iter().map(|id| scheduler.get_execution(id).unwrap().task_id).collect();
+    started.sort();
+    let mut subscribers = vec![first, second];
+    subscribers.sort();
+    assert_eq!(started, subscribers);
+    assert_eq!(runs_of(&scheduler, &first)[0].output.as_ref().unwrap().message, "Logged: order 42");
+    assert!(runs_of(&scheduler, &elsewhere).is_empty());
+
+    assert!(scheduler.publish("nobody-listens", json!(null)).unwrap().is_empty());
+}
+
+/// The events of `task_id`'s runs, once there are `count` of them.
+async fn file_events(scheduler: &Scheduler, task_id: &Uuid, count: usize) -> Vec<Value> {
+    for _ in 0..150 {
+        let runs = runs_of(scheduler, task_id);
+        if runs.len() >= count {
+            return runs.into_iter().filter_map(|r| r.event).collect();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {} runs, got {:?}", count, runs_of(scheduler, task_id));
+}
+
+#[tokio::test]
+async fn file_changes_matching_the_glob_run_the_task() {
+    let dir = Scratch::new("events");
+    let scheduler = scheduler().await;
+    let schedule = Schedule::FileWatch { dir: dir.path().display().to_string(), glob: Some("**/*.csv".into()) };
+    let id = scheduler.add_task(task("import", schedule, "{{ event.relative_path }}")).unwrap();
+    std::fs::create_dir(dir.join("in")).unwrap();
+    let _triggers = events::watch_files(scheduler.clone()).unwrap();
+    // The watcher picks up the task's directory on its first sync.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    std::fs::write(dir.join("in/orders.csv"), "a,b\n").unwrap();
+    std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+    let events = file_events(&scheduler, &id, 1).await;
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(runs_of(&scheduler, &id).len(), 1, "one run for the burst of writes to one file");
+    let event = &events[0];
+    assert_eq!(event["source"], "file");
+    assert_eq!(event["relative_path"], "in/orders.csv");
+    assert_eq!(event["path"], json!(dir.join("in/orders.csv")));
+    assert_eq!(event["kind"], "created");
+    assert_eq!(event["dir"], json!(dir.path().display().to_string()));
+
+    std::fs::remove_file(dir.join("in/orders.csv")).unwrap();
+    let events = file_events(&scheduler, &id, 2).await;
+    assert!(events.iter().any(|e| e["kind"] == "removed"), "{:?}", events);
+}
+
+#[tokio::test]
+async fn a_directory_created_later_is_watched_once_it_exists() {
+    let parent = Scratch::new("events");
+    let dir = parent.join("later");
+    let scheduler = scheduler().await;
+    let _triggers = events::watch_files(scheduler.clone()).unwrap();
+    let schedule = Schedule::FileWatch { dir: dir.display().to_string(), glob: None };
+    let id = scheduler.add_task(task("late", schedule, "")).unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    std::fs::create_dir(&dir).unwrap();
+    // Give the once-a-second sync time to start watching it.
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    std::fs::write(dir.join("data.json"), "{}").unwrap();
+    let events = file_events(&scheduler, &id, 1).await;
+    assert_eq!(events[0]["relative_path"], "data.json");
+
+    // A disabled task stops firing.
+    scheduler.set_enabled(&id, false).unwrap();
+    std::fs::write(dir.join("more.json"), "{}").unwrap();
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    assert_eq!(runs_of(&scheduler, &id).len(), 1);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use chrono::{Duration as ChronoDuration, Utc};
+use chronoflow::{
+    ExecutionFilter, ExecutionStatus, FileStorage, PluginConfig, PluginManager, RetentionPolicy, Schedule, Scheduler,
+    SchedulerConfig, Storage, Task, TaskExecution,
+};
+use common::{finished, Scratch};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+fn task(name: &str) -> Task {
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    Task::new(name.into(), Schedule::Manual, plugin).unwrap()
+}
+
+/// A finished execution that started `minutes_ago` and ran for `duration_ms`.
+fn execution(task: &Task, status: ExecutionStatus, minutes_ago: i64, duration_ms: u64) -> TaskExecution {
+    let mut execution = TaskExecution::new(task.id, Utc::now(), false);
+    execution.started_at = Utc::now() - ChronoDuration::minutes(minutes_ago);
+    execution.finished_at = Some(execution.started_at + ChronoDuration::milliseconds(duration_ms as i64));
+    execution.duration_ms = Some(duration_ms);
+    execution.status = status;
+    execution
+}
+
+/// A started scheduler over `storage`, which has already been seeded.
+async fn scheduler(storage: Arc<FileStorage>, retention: RetentionPolicy) -> Scheduler {
+    let config = SchedulerConfig { retention, ..SchedulerConfig::default() };
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new())).with_config(config).with_storage(storage).unwrap();
+    scheduler.start().await;
+    scheduler
+}
+
+fn seed(storage: &FileStorage, tasks: &[&Task], executions: &[&TaskExecution]) {
+    storage.load().unwrap();
+    for task in tasks {
+        storage.save_task(task).unwrap();
+    }
+    for execution in executions {
+        storage.save_execution(execution).unwrap();
+    }
+}
+
+fn ids(executions: &[TaskExecution]) -> Vec<Uuid> {
+    executions.iter().map(|e| e.id).collect()
+}
+
+#[tokio::test]
+async fn old_executions_are_dropped_from_memory_and_storage() {
+    let scratch = Scratch::new("history");
+    let storage = scratch.storage();
+    let job = task("job");
+    let stale = execution(&job, ExecutionStatus::Success, 3 * 24 * 60, 10);
+    let older = execution(&job, ExecutionStatus::Failed, 2 * 24 * 60, 10);
+    let recent = execution(&job, ExecutionStatus::Success, 5, 10);
+    seed(&storage, &[&job], &[&stale, &older, &recent]);
+
+    let retention = RetentionPolicy { max_age: Some(Duration::from_secs(24 * 60 * 60)), ..RetentionPolicy::default() };
+    let scheduler = scheduler(storage.clone(), retention).await;
+    assert_eq!(ids(&scheduler.list_executions(&job.id)), [recent.id]);
+    assert_eq!(ids(&storage.load().unwrap().executions), [recent.id]);
+    assert_eq!(scheduler.prune_history(), 0);
+}
+
+#[tokio::test]
+async fn only_the_newest_runs_of_each_task_are_kept() {
+    let scratch = Scratch::new("history");
+    let storage = scratch.storage();
+    let (busy, quiet) = (task("busy"), task("quiet"));
+    let quiet_run = execution(&quiet, ExecutionStatus::Success, 60, 10);
+    seed(&storage, &[&busy, &quiet], &[&quiet_run]);
+    let scheduler = scheduler(storage, RetentionPolicy { max_per_task: Some(3), ..RetentionPolicy::default() }).await;
+
+    let mut started = Vec::new();
+    for _ in 0..5 {
+        let id = scheduler.trigger_now(&busy.id).unwrap();
+        finished(&scheduler, &id).await;
+        started.push(id);
+        // Distinct start times keep "newest" unambiguous.
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(ids(&scheduler.list_executions(&busy.id)), started[2..]);
+    assert!(scheduler.get_execution(&started[0]).is_none());
+    assert_eq!(ids(&scheduler.list_executions(&quiet.id)), [quiet_run.id], "other tasks keep theirs");
+}
+
+#[tokio::test]
+async fn history_can_be_filtered_and_paged() {
+    let scratch = Scratch::new("history");
+    let storage = scratch.storage();
+    let (a, b) = (task("a"), task("b"));
+    // Minutes ago: a ran at 50, 40, 30, 20 and 10, b at 35.
+    let runs: Vec<TaskExecution> = [50, 40, 30, 20, 10]
+        .iter()
+        .enumerate()
+        .map(|(i, ago)| {
+            let status = if i % 2 == 0 { ExecutionStatus::Success } else { ExecutionStatus::Failed };
+            execution(&a, status, *ago, 10)
+        })
+        .collect();
+    let other = execution(&b, ExecutionStatus::Failed, 35, 10);
+    let mut all: Vec<&TaskExecution> = runs.iter().collect();
+    all.push(&other);
+    seed(&storage, &[&a, &b], &all);
+    let scheduler = scheduler(storage, RetentionPolicy::default()).await;
+
+    let query = |filter: ExecutionFilter| {
+        let page = scheduler.query_executions(&filter);
+        (ids(&page.items), page.total)
+    };
+    assert_eq!(query(ExecutionFilter::default()).1, 6);
+    let failed = ExecutionFilter { status: Some(ExecutionStatus::Failed), ..ExecutionFilter::default() };
+    assert_eq!(query(failed.clone()), (vec![runs[3].id, other.id, runs[1].id], 3));
+    let failed_a = ExecutionFilter { task_id: Some(a.id), ..failed };
+    assert_eq!(query(failed_a), (vec![runs[3].id, runs[1].id], 2));
+
+    let window = ExecutionFilter {
+        since: Some(runs[1].started_at),
+        until: Some(runs[3].started_at),
+        ..ExecutionFilter::default()
+    };
+    // `since` is inclusive and `until` exclusive.
+    assert_eq!(query(window.clone()), (vec![runs[2].id, other.id, runs[1].id], 3));
+    let page = ExecutionFilter { offset: 1, limit: Some(1), ..window };
+    assert_eq!(query(page), (vec![other.id], 3));
+}
+
+#[tokio::test]
+async fn stats_summarize_the_matching_runs() {
+    let scratch = Scratch::new("history");
+    let storage = scratch.storage();
+    let (job, idle) = (task("job"), task("idle"));
+    let mut runs: Vec<TaskExecution> = (1..=10)
+        .map(|n| execution(&job, ExecutionStatus::Success, 100 - n, n as u64 * 100))
+        .collect();
+    runs.push(execution(&job, ExecutionStatus::Failed, 80, 50));
+    runs.push(execution(&job, ExecutionStatus::Timeout, 70, 3000));
+    let last_failure = execution(&job, ExecutionStatus::Failed, 60, 2000);
+    runs.push(last_failure.clone());
+    // Neither counts towards the rate or the durations.
+    runs.push(execution(&job, ExecutionStatus::Skipped, 50, 99_999));
+    runs.push(execution(&job, ExecutionStatus::Cancelled, 40, 99_999));
+    seed(&storage, &[&job, &idle], &runs.iter().collect::<Vec<_>>());
+    let scheduler = scheduler(storage, RetentionPolicy::default()).await;
+
+    let stats = scheduler.execution_stats(&ExecutionFilter { task_id: Some(job.id), ..ExecutionFilter::default() });
+    assert_eq!((stats.total, stats.succeeded, stats.failed), (15, 10, 3));
+    assert_eq!(stats.success_rate, Some(10.0 / 13.0));
+    // Sorted: 50, 100, 200, ..., 1000, 2000, 3000.
+    assert_eq!((stats.p50_duration_ms, stats.p95_duration_ms), (Some(600), Some(3000)));
+    assert_eq!(stats.last_failure.map(|e| e.id), Some(last_failure.id));
+
+    let stats = scheduler.execution_stats(&ExecutionFilter { task_id: Some(idle.id), ..ExecutionFilter::default() });
+    assert_eq!((stats.total, stats.success_rate, stats.p50_duration_ms), (0, None, None));
+    assert!(stats.last_failure.is_none());
+}
@@ -0,0 +1,186 @@
+use axum::body::Bytes;
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::routing::{any, get};
+use axum::{Json, Router};
+use chronoflow::{ChronoError, ExecutionLog, HttpRequestPlugin, Plugin, PluginConfig, PluginContext, PluginManager};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+async fn echo(
+    method: Method,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Json<Value> {
+    Json(json!({
+        "method": method.as_str(),
+        "header": headers.get("x-token").and_then(|v| v.to_str().ok()),
+        "query": query,
+        "body": String::from_utf8_lossy(&body),
+    }))
+}
+
+async fn status(Path(code): Path<u16>) -> (StatusCode, &'static str) {
+    (StatusCode::from_u16(code).unwrap(), "status body")
+}
+
+async fn slow() -> &'static str {
+    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    "late"
+}
+
+async fn stub_server() -> SocketAddr {
+    let app = Router::new()
+        .route("/echo", any(echo))
+        .route("/status/:code", get(status))
+        .route("/large", get(|| async { "x".repeat(10_000) }))
+        .route("/slow", get(slow));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+fn ctx() -> PluginContext {
+    PluginContext {
+        task_id: Uuid::new_v4(),
+        execution_id: Uuid::new_v4(),
+        attempt: 1,
+        scheduled_at: None,
+        cancel: CancellationToken::new(),
+        log: ExecutionLog::default(),
+    }
+}
+
+fn response_body(data: &Value) -> Value {
+    serde_json::from_str(data["body"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn get_sends_headers_and_query() {
+    let addr = stub_server().await;
+    let config = json!({
+        "url": format!("http://{}/echo", addr),
+        "headers": { "x-token": "secret" },
+        "query": { "page": 2, "q": "jobs" }
+    });
+    let output = HttpRequestPlugin::new().execute(ctx(), &config).await.unwrap();
+
+    assert_eq!(output.data["status"], 200);
+    assert_eq!(output.data["truncated"], false);
+    let echoed = response_body(&output.data);
+    assert_eq!(echoed["method"], "GET");
+    assert_eq!(echoed["header"], "secret");
+    assert_eq!(echoed["query"], json!({ "page": "2", "q": "jobs" }));
+}
+
+#[tokio::test]
+async fn post_sends_json_body() {
+    let addr = stub_server().await;
+    let config = json!({
+        "url": format!("http://{}/echo", addr),
+        "method": "post",
+        "body": { "id": 7 }
+    });
+    let output = HttpRequestPlugin::new().execute(ctx(), &config).await.unwrap();
+
+    let echoed = response_body(&output.data);
+    assert_eq!(echoed["method"], "POST");
+    assert_eq!(serde_json::from_str::<Value>(echoed["body"].as_str().unwrap()).unwrap(), json!({ "id": 7 }));
+}
+
+#[tokio::test]
+async fn unexpected_status_fails() {
+    let addr = stub_server().await;
+    let config = json!({ "url": format!("http://{}/status/503", addr) });
+    match HttpRequestPlugin::new().execute(ctx(), &config).await {
+        Err(ChronoError::PluginFailed(message, output)) => {
+            assert_eq!(message, format!("GET http://{}/status/503 returned unexpected status 503", addr));
+            assert_eq!(output.message, message);
+            assert_eq!(output.data["status"], 503);
+            assert_eq!(output.data["body"], "status body");
+            assert_eq!(output.data["headers"]["content-type"], "text/plain; charset=utf-8");
+        }
+        other => panic!("expected the run to fail with its response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn expected_status_overrides_default() {
+    let addr = stub_server().await;
+    let config = json!({ "url": format!("http://{}/status/404", addr), "expected_status": [404] });
+    let output = HttpRequestPlugin::new().execute(ctx(), &config).await.unwrap();
+    assert_eq!(output.data["status"], 404);
+
+    let config = json!({ "url": format!("http://{}/status/200", addr), "expected_status": [201] });
+    assert!(HttpRequestPlugin::new().execute(ctx(), &config).await.is_err());
+}
+
+#[tokio::test]
+async fn response_is_truncated() {
+    let addr = stub_server().await;
+    let config = json!({ "url": format!("http://{}/large", addr), "max_response_bytes": 16 });
+    let output = HttpRequestPlugin::new().execute(ctx(), &config).await.unwrap();
+
+    assert_eq!(output.data["body"], "x".repeat(16));
+    assert_eq!(output.data["truncated"], true);
+}
+
+#[tokio::test]
+async fn request_timeout_fails() {
+    let addr = stub_server().await;
+    let config = json!({ "url": format!("http://{}/slow", addr), "timeout_seconds": 0.2 });
+    let started = std::time::Instant::now();
+    assert!(HttpRequestPlugin::new().execute(ctx(), &config).await.is_err());
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn cancellation_aborts_request() {
+    let addr = stub_server().await;
+    let config = json!({ "url": format!("http://{}/slow", addr) });
+    let ctx = ctx();
+    let cancel = ctx.cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        cancel.cancel();
+    });
+    let err = HttpRequestPlugin::new().execute(ctx, &config).await.unwrap_err();
+    assert!(matches!(err, ChronoError::Cancelled(_)), "{:?}", err);
+    assert_eq!(err.to_string(), format!("Execution cancelled: request to http://{}/slow", addr));
+}
+
+#[tokio::test]
+async fn only_failed_requests_count_as_plugin_errors() {
+    let addr = stub_server().await;
+    let plugins = PluginManager::new();
+    let plugin = |path: &str| PluginConfig {
+        name: "http_request".into(),
+        wasm_path: String::new(),
+        config: json!({ "url": format!("http://{}{}", addr, path) }),
+    };
+    let ctx = ctx();
+    ctx.cancel.cancel();
+    assert!(matches!(plugins.execute(ctx, &plugin("/slow")).await, Err(ChronoError::Cancelled(_))));
+    assert!(!plugins.metrics().encode().contains("chronoflow_plugin_errors_total{plugin=\"http_request\"}"));
+
+    assert!(plugins.execute(self::ctx(), &plugin("/status/500")).await.is_err());
+    assert!(plugins.metrics().encode().contains("chronoflow_plugin_errors_total{plugin=\"http_request\"} 1"));
+}
+
+#[test]
+fn validate_rejects_bad_config() {
+    let plugin = HttpRequestPlugin::new();
+    assert!(plugin.validate(&json!({})).is_err());
+    assert!(plugin.validate(&json!({ "url": "not a url" })).is_err());
+    assert!(plugin.validate(&json!({ "url": "http://localhost", "method": "BAD METHOD" })).is_err());
+    for timeout in [json!(0), json!(-1), json!(1e20), json!(f64::MAX)] {
+        let config = json!({ "url": "http://localhost", "timeout_seconds": timeout });
+        assert!(plugin.validate(&config).is_err(), "{}", timeout);
+    }
+    assert!(plugin.validate(&json!({ "url": "http://localhost", "method": "DELETE" })).is_ok());
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionStatus, FileStorage, LogLevel, Plugin, PluginConfig, PluginContext, PluginManager,
+    PluginOutput, Result, RetryPolicy, Schedule, Scheduler, Storage, Task, TaskExecution,
+};
+use common::{finished, Scratch};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Writes `config.lines` log lines, then fails on the first attempt if
+/// `config.fail_first` is set.
+struct Chatty;
+
+#[async_trait]
+impl Plugin for Chatty {
+    fn name(&self) -> &str {
+        "chatty"
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        ctx.log.debug(format!("attempt {}", ctx.attempt));
+        for line in 0..config["lines"].as_u64().unwrap_or(0) {
+            ctx.log.info(format!("line {}", line));
+        }
+        if config["fail_first"].as_bool().unwrap_or(false) && ctx.attempt == 1 {
+            ctx.log.error("giving up on this attempt");
+            return Err(ChronoError::PluginError("first attempt fails".into()));
+        }
+        ctx.log.warn("done");
+        Ok(PluginOutput::new("chatted"))
+    }
+}
+
+async fn scheduler(storage: Option<Arc<FileStorage>>) -> Scheduler {
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(Arc::new(Chatty));
+    let mut scheduler = Scheduler::new(plugins);
+    if let Some(storage) = storage {
+        scheduler = scheduler.with_storage(storage).unwrap();
+    }
+    scheduler.start().await;
+    scheduler
+}
+
+fn chatty(config: Value) -> Task {
+    let plugin = PluginConfig { name: "chatty".into(), wasm_path: String::new(), config };
+    Task::new("chatty".into(), Schedule::Manual, plugin).unwrap()
+}
+
+fn retry_once() -> RetryPolicy {
+    RetryPolicy { max_attempts: 2, backoff_base_ms: 10, max_delay_ms: 10, jitter: 0.0, retry_on: Vec::new() }
+}
+
+fn messages(execution: &TaskExecution) -> Vec<(LogLevel, &str)> {
+    execution.logs.iter().map(|l| (l.level, l.message.as_str())).collect()
+}
+
+#[tokio::test]
+async fn plugin_log_lines_are_kept_with_the_execution() {
+    let scratch = Scratch::new("logging");
+    let storage = scratch.storage();
+    let scheduler = scheduler(Some(storage.clone())).await;
+    let id = scheduler.add_task(chatty(json!({ "lines": 2 }))).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    let expected = [
+        (LogLevel::Debug, "attempt 1"),
+        (LogLevel::Info, "line 0"),
+        (LogLevel::Info, "line 1"),
+        (LogLevel::Warn, "done"),
+    ];
+    assert_eq!(messages(&execution), expected);
+    assert!(execution.logs.windows(2).all(|pair| pair[0].at <= pair[1].at));
+    let saved = storage.load().unwrap().executions.into_iter().find(|e| e.id == execution.id).unwrap();
+    assert_eq!(messages(&saved), expected, "the logs are stored with the execution");
+}
+
+#[tokio::test]
+async fn each_attempt_keeps_its_own_log() {
+    let scheduler = scheduler(None).await;
+    let id = scheduler.add_task(chatty(json!({ "fail_first": true })).with_retry(retry_once()).unwrap()).unwrap();
+    let first = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert_eq!(first.status, ExecutionStatus::Failed);
+    assert_eq!(messages(&first), [(LogLevel::Debug, "attempt 1"), (LogLevel::Error, "giving up on this attempt")]);
+
+    let mut executions = scheduler.list_executions(&id);
+    for _ in 0..100 {
+        if executions.len() == 2 && executions[1].finished_at.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        executions = scheduler.list_executions(&id);
+    }
+    assert_eq!(messages(&executions[1]), [(LogLevel::Debug, "attempt 2"), (LogLevel::Warn, "done")]);
+}
+
+#[tokio::test]
+async fn a_run_that_logs_too_much_keeps_the_first_lines_and_a_note() {
+    let scheduler = scheduler(None).await;
+    let id = scheduler.add_task(chatty(json!({ "lines": 1200 }))).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(execution.logs.len(), 1001);
+    assert_eq!(execution.logs[999].message, "line 998");
+    // 1200 lines, the debug line before them and the warning after, less the 1000 kept.
+    assert_eq!(messages(&execution)[1000], (LogLevel::Warn, "202 more line(s) dropped"));
+}
+
+/// Fields recorded on a span or event, formatted.
+#[derive(Debug, Default, Clone)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// A plugin log event with the spans it was emitted in, outermost first.
+#[derive(Debug)]
+struct Captured {
+    message: String,
+    spans: Vec<(String, Fields)>,
+}
+
+/// Keeps the events logged through `ExecutionLog`.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<Captured>>>);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        ctx.span(id).unwrap().extensions_mut().insert(fields);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != "chronoflow::plugin" {
+            return;
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let spans = ctx.event_scope(event).into_iter().flat_map(|scope| scope.from_root()).map(|span| {
+            let fields = span.extensions().get::<Fields>().cloned().unwrap_or_default();
+            (span.name().to_string(), fields)
+        });
+        let message = fields.0.remove("message").unwrap_or_default();
+        self.0.lock().unwrap().push(Captured { message, spans: spans.collect() });
+    }
+}
+
+#[tokio::test]
+async fn plugin_lines_are_traced_in_spans_naming_the_task_and_execution() {
+    let capture = Capture::default();
+    // The test's runtime is single-threaded, so the scheduler's tasks run under it too.
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+    let scheduler = scheduler(None).await;
+    let id = scheduler.add_task(chatty(json!({ "fail_first": true })).with_retry(retry_once()).unwrap()).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+    for _ in 0..100 {
+        if scheduler.list_executions(&id).iter().filter(|e| e.finished_at.is_some()).count() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let retried = scheduler.list_executions(&id)[1].id;
+
+    let events = capture.0.lock().unwrap();
+    let event = |message: &str| events.iter().find(|e| e.message == message).unwrap_or_else(|| panic!("{}", message));
+    for (message, execution, attempt) in [("attempt 1", first, "1"), ("attempt 2", retried, "2")] {
+        let spans = &event(message).spans;
+        let names: Vec<&str> = spans.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["task_run", "attempt"], "{}", message);
+        let (run, this) = (&spans[0].1 .0, &spans[1].1 .0);
+        assert_eq!(run["task_id"], id.to_string());
+        assert_eq!(run["task"], "chatty");
+        // The run is named by its first execution, each attempt by its own.
+        assert_eq!(run["execution_id"], first.to_string());
+        assert_eq!(this["execution_id"], execution.to_string());
+        assert_eq!(this["attempt"], attempt);
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    api, ChronoError, Metrics, OverlapPolicy, Plugin, PluginConfig, PluginContext, PluginManager,
+    PluginOutput, Result, Schedule, Scheduler, Task,
+};
+use common::finished;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Sleeps for `config.ms`, then fails if `config.fail` is set.
+struct Job;
+
+#[async_trait]
+impl Plugin for Job {
+    fn name(&self) -> &str {
+        "job"
+    }
+
+    async fn execute(&self, _ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        tokio::time::sleep(Duration::from_millis(config["ms"].as_u64().unwrap_or(0))).await;
+        if config["fail"].as_bool().unwrap_or(false) {
+            return Err(ChronoError::PluginError("failed".into()));
+        }
+        Ok(PluginOutput::new("done"))
+    }
+}
+
+async fn scheduler() -> Arc<Scheduler> {
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(Arc::new(Job));
+    let scheduler = Arc::new(Scheduler::new(plugins));
+    scheduler.start().await;
+    scheduler
+}
+
+fn task(name: &str, schedule: Schedule, config: Value) -> Task {
+    let plugin = PluginConfig { name: "job".into(), wasm_path: String::new(), config };
+    Task::new(name.into(), schedule, plugin).unwrap()
+}
+
+/// The value of the sample of `name` carrying all of `labels`, from Prometheus text.
+fn sample(text: &str, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+    text.lines().filter(|line| !line.starts_with('#')).find_map(|line| {
+        let (series, value) = line.rsplit_once(' ')?;
+        let (metric, rest) = series.split_once('{').unwrap_or((series, "}"));
+        let has_labels = labels.iter().all(|(k, v)| rest.contains(&format!("{}=\"{}\"", k, v)));
+        (metric == name && has_labels).then(|| value.parse().unwrap())
+    })
+}
+
+#[tokio::test]
+async fn runs_are_counted_by_status_and_timed() {
+    let scheduler = scheduler().await;
+    let ok = scheduler.add_task(task("ok", Schedule::Manual, json!({}))).unwrap();
+    let bad = scheduler.add_task(task("bad", Schedule::Manual, json!({ "fail": true }))).unwrap();
+    for id in [ok, ok, bad] {
+        finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    }
+
+    let text = scheduler.gather_metrics();
+    let ok_id = ok.to_string();
+    let ok_labels = [("task_id", ok_id.as_str()), ("task", "ok")];
+    let count = |labels: &[(&str, &str)]| sample(&text, "chronoflow_executions_total", labels);
+    assert_eq!(count(&[ok_labels[0], ("status", "Success")]), Some(2.0));
+    assert_eq!(count(&[("task", "bad"), ("status", "Failed")]), Some(1.0));
+    assert_eq!(count(&[("task", "ok"), ("status", "Failed")]), None);
+    assert_eq!(sample(&text, "chronoflow_execution_duration_seconds_count", &ok_labels), Some(2.0));
+    assert_eq!(sample(&text, "chronoflow_execution_duration_seconds_count", &[("task", "bad")]), Some(1.0));
+    // Manual runs fire the moment they are triggered, so each records a lag.
+    assert_eq!(sample(&text, "chronoflow_schedule_lag_seconds_count", &ok_labels), Some(2.0));
+    assert_eq!(sample(&text, "chronoflow_plugin_errors_total", &[("plugin", "job")]), Some(1.0));
+}
+
+#[tokio::test]
+async fn the_gauges_show_what_is_scheduled_queued_and_running() {
+    let scheduler = scheduler().await;
+    scheduler.add_task(task("hourly", Schedule::Interval { seconds: 3600 }, json!({}))).unwrap();
+    let mut paused = task("paused", Schedule::Interval { seconds: 3600 }, json!({}));
+    paused.enabled = false;
+    scheduler.add_task(paused).unwrap();
+    let slow = task("slow", Schedule::Manual, json!({ "ms": 500 })).with_overlap_policy(OverlapPolicy::Queue);
+    let slow = scheduler.add_task(slow).unwrap();
+    let first = scheduler.trigger_now(&slow).unwrap();
+    let second = scheduler.trigger_now(&slow).unwrap();
+    // Let the interval task's first run finish.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let text = scheduler.gather_metrics();
+    assert_eq!(sample(&text, "chronoflow_scheduled_tasks", &[]), Some(1.0));
+    assert_eq!(sample(&text, "chronoflow_running_executions", &[]), Some(1.0));
+    assert_eq!(sample(&text, "chronoflow_queued_executions", &[]), Some(1.0));
+
+    finished(&scheduler, &first).await;
+    finished(&scheduler, &second).await;
+    let text = scheduler.gather_metrics();
+    assert_eq!(sample(&text, "chronoflow_running_executions", &[]), Some(0.0));
+    assert_eq!(sample(&text, "chronoflow_queued_executions", &[]), Some(0.0));
+}
+
+#[tokio::test]
+async fn a_removed_task_leaves_no_series_behind() {
+    let scheduler = scheduler().await;
+    let id = scheduler.add_task(task("gone", Schedule::Manual, json!({}))).unwrap();
+    finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert!(scheduler.gather_metrics().contains("task=\"gone\""));
+
+    scheduler.remove_task(&id).unwrap();
+    assert!(!scheduler.gather_metrics().contains("task=\"gone\""));
+}
+
+#[tokio::test]
+async fn the_api_serves_the_metrics() {
+    let scheduler = scheduler().await;
+    let id = scheduler.add_task(task("served", Schedule::Manual, json!({}))).unwrap();
+    finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/metrics", listener.local_addr().unwrap());
+    let app = api::router(scheduler.clone(), None);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let response = reqwest::get(url).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+    assert_eq!(content_type, Metrics::new().content_type());
+    let text = response.text().await.unwrap();
+    let labels = [("task", "served"), ("status", "Success")];
+    assert_eq!(sample(&text, "chronoflow_executions_total", &labels), Some(1.0));
+    assert!(text.contains("# TYPE chronoflow_execution_duration_seconds histogram"));
+}
+
+#[test]
+fn plugin_managers_can_share_a_registry() {
+    let metrics = Arc::new(Metrics::new());
+    let plugins = PluginManager::new().with_metrics(metrics.clone());
+    metrics.record_plugin_error("shared");
+    assert!(Arc::ptr_eq(&plugins.metrics(), &metrics));
+    let text = plugins.metrics().encode();
+    assert_eq!(sample(&text, "chronoflow_plugin_errors_total", &[("plugin", "shared")]), Some(1.0));
+}
@@ -0,0 +1,128 @@
+use chrono::{Duration, Utc};
+use chronoflow::{
+    ChronoError, PluginConfig, PluginManager, Result, Schedule, Scheduler, Snapshot, Storage, Task, TaskExecution,
+    WorkflowRun,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Storage that restores a fixed set of tasks and keeps nothing else.
+struct Restored(Vec<Task>);
+
+impl Storage for Restored {
+    fn save_task(&self, _task: &Task) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_task(&self, _id: &Uuid) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_execution(&self, _execution: &TaskExecution) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_executions(&self, _ids: &[Uuid]) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_workflow_run(&self, _run: &WorkflowRun) -> Result<()> {
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Snapshot> {
+        Ok(Snapshot { tasks: self.0.clone(), ..Snapshot::default() })
+    }
+}
+
+fn logger() -> PluginConfig {
+    PluginConfig { name: "logger".into(), wasm_path: String::new(), config: serde_json::json!({}) }
+}
+
+#[test]
+fn zero_interval_is_rejected() {
+    let err = Task::new("spin".into(), Schedule::Interval { seconds: 0 }, logger()).unwrap_err();
+    assert!(matches!(err, ChronoError::InvalidTask(_)), "{}", err);
+
+    let mut task = Task::new("spin".into(), Schedule::Interval { seconds: 1 }, logger()).unwrap();
+    task.schedule = Schedule::Interval { seconds: 0 };
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    assert!(scheduler.add_task(task).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn catch_up_after_long_downtime_is_bounded() {
+    let ten_years_ago = Utc::now() - Duration::days(3650);
+
+    // One fire per second for ten years would take minutes to step through.
+    let mut behind = Task::new("behind".into(), Schedule::Interval { seconds: 1 }, logger()).unwrap();
+    behind.next_run = Some(ten_years_ago);
+    // Restored from a store written before zero intervals were rejected.
+    let mut stuck = Task::new("stuck".into(), Schedule::Interval { seconds: 1 }, logger()).unwrap();
+    stuck.schedule = Schedule::Interval { seconds: 0 };
+    stuck.next_run = Some(ten_years_ago);
+    let (behind_id, stuck_id) = (behind.id, stuck.id);
+
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()))
+        .with_storage(Arc::new(Restored(vec![behind, stuck])))
+        .unwrap();
+    scheduler.start().await;
+
+    let planned = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        loop {
+            let behind = scheduler.get_task(&behind_id).unwrap();
+            let stuck = scheduler.get_task(&stuck_id).unwrap();
+            if behind.last_run.is_some() && stuck.last_run.is_some() {
+                return (behind, stuck);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("planning the missed runs did not finish");
+
+    let (behind, stuck) = planned;
+    assert!(behind.next_run.unwrap() > Utc::now() - Duration::seconds(5));
+    assert_eq!(stuck.next_run, None);
+    // FireOnceNow collapses the backlog into one catch-up run each.
+    let runs = scheduler.list_executions(&behind_id);
+    assert!(runs.iter().any(|e| e.catch_up), "{:?}", runs);
+    assert_eq!(scheduler.list_executions(&stuck_id).len(), 1);
+}
+
+#[test]
+fn oversized_interval_is_rejected() {
+    for seconds in [10_000_000_000_000, u64::MAX] {
+        let err = Task::new("far".into(), Schedule::Interval { seconds }, logger()).unwrap_err();
+        assert!(matches!(err, ChronoError::InvalidTask(_)), "{}", err);
+    }
+    assert!(Task::new("yearly".into(), Schedule::Interval { seconds: 366 * 24 * 3600 }, logger()).is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unrepresentable_next_fire_ends_the_schedule() {
+    // Restored from a store written before intervals were bounded.
+    let mut far = Task::new("far".into(), Schedule::Interval { seconds: 1 }, logger()).unwrap();
+    far.schedule = Schedule::Interval { seconds: 10_000_000_000_000 };
+    far.next_run = Some(Utc::now() - Duration::seconds(1));
+    let mut wrapping = far.clone();
+    wrapping.id = Uuid::new_v4();
+    wrapping.schedule = Schedule::Interval { seconds: u64::MAX };
+    let (far_id, wrapping_id) = (far.id, wrapping.id);
+
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()))
+        .with_storage(Arc::new(Restored(vec![far, wrapping])))
+        .unwrap();
+    scheduler.start().await;
+
+    tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        while scheduler.list_executions(&far_id).is_empty() || scheduler.list_executions(&wrapping_id).is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the timer loop stopped firing");
+    assert_eq!(scheduler.get_task(&far_id).unwrap().next_run, None);
+    assert_eq!(scheduler.get_task(&wrapping_id).unwrap().next_run, None);
+    assert!(scheduler.is_running());
+}
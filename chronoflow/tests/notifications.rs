@@ -0,0 +1,313 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use chronoflow::{
+    ChronoError, ExecutionStatus, Notification, NotificationRule, Notifier, NotifyOn, Plugin, PluginConfig,
+    PluginContext, PluginManager, PluginOutput, Result, Schedule, Scheduler, SmtpNotifier, Task, WebhookNotifier,
+};
+use common::{finished, Scratch};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+/// Succeeds or fails as told by `outcomes`, one per run, after `delay`.
+struct Scripted {
+    outcomes: Mutex<VecDeque<bool>>,
+    delay: Duration,
+}
+
+#[async_trait]
+impl Plugin for Scripted {
+    fn name(&self) -> &str {
+        "scripted"
+    }
+
+    async fn execute(&self, _ctx: PluginContext, _config: &Value) -> Result<PluginOutput> {
+        tokio::time::sleep(self.delay).await;
+        let succeed = self.outcomes.lock().unwrap().pop_front().unwrap_or(true);
+        if succeed {
+            Ok(PluginOutput::new("ok"))
+        } else {
+            Err(ChronoError::PluginError("scripted failure".into()))
+        }
+    }
+}
+
+struct Setup {
+    scheduler: Scheduler,
+    task_id: Uuid,
+    sent: PathBuf,
+    _scratch: Scratch,
+}
+
+/// A scheduler with one manual task notifying through `rules` into a file.
+async fn setup(outcomes: &[bool], delay: Duration, rules: Vec<NotificationRule>) -> Setup {
+    let scratch = Scratch::new("notifications");
+    let sent = scratch.join("sent.jsonl");
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(Arc::new(Scripted { outcomes: Mutex::new(outcomes.iter().copied().collect()), delay }));
+    let scheduler = Scheduler::new(plugins);
+    scheduler.start().await;
+
+    let plugin = PluginConfig { name: "scripted".into(), wasm_path: String::new(), config: json!({}) };
+    let mut task = Task::new("watched".into(), Schedule::Manual, plugin).unwrap();
+    for rule in rules {
+        task = task.with_notification(NotificationRule { config: json!({ "path": sent }), ..rule });
+    }
+    let task_id = scheduler.add_task(task).unwrap();
+    Setup { scheduler, task_id, sent, _scratch: scratch }
+}
+
+fn rule(on: NotifyOn) -> NotificationRule {
+    NotificationRule::new(on, "file", json!({})).with_dedup_seconds(0)
+}
+
+impl Setup {
+    /// Runs the task once and waits for the run to finish.
+    async fn run(&self) {
+        let id = self.scheduler.trigger_now(&self.task_id).unwrap();
+        finished(&self.scheduler, &id).await;
+    }
+
+    /// The notifications written so far, once `count` have arrived.
+    async fn sent(&self, count: usize) -> Vec<Value> {
+        for _ in 0..200 {
+            let lines = self.lines();
+            if lines.len() >= count {
+                return lines;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("expected {} notifications, got {:?}", count, self.lines());
+    }
+
+    fn lines(&self) -> Vec<Value> {
+        std::fs::read_to_string(&self.sent).unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+fn events(sent: &[Value]) -> Vec<Value> {
+    sent.iter().map(|n| n["event"].clone()).collect()
+}
+
+#[tokio::test]
+async fn failures_and_the_recovery_after_them_notify() {
+    let rules = vec![rule(NotifyOn::Failure), rule(NotifyOn::Recovery)];
+    let setup = setup(&[false, true, true], Duration::ZERO, rules).await;
+    setup.run().await;
+    setup.run().await;
+    setup.run().await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let sent = setup.sent(2).await;
+    assert_eq!(events(&sent), [json!("Failure"), json!("Recovery")]);
+    assert_eq!(sent[0]["status"], "Failed");
+    assert!(sent[0]["error"].as_str().unwrap().contains("scripted failure"));
+    assert_eq!(sent[0]["summary"], format!("Task watched failed: {}", sent[0]["error"].as_str().unwrap()));
+    assert_eq!(sent[1]["summary"], "Task watched recovered");
+    assert_eq!(sent[1]["task_id"], json!(setup.task_id));
+}
+
+#[tokio::test]
+async fn repeats_within_the_dedup_window_are_dropped() {
+    let rules = vec![rule(NotifyOn::Failure).with_dedup_seconds(300), rule(NotifyOn::Success)];
+    let setup = setup(&[false, false, false, true, true], Duration::ZERO, rules).await;
+    for _ in 0..5 {
+        setup.run().await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // One failure for the window; successes aren't deduplicated with a zero window.
+    let sent = setup.sent(3).await;
+    assert_eq!(events(&sent), [json!("Failure"), json!("Success"), json!("Success")]);
+}
+
+#[tokio::test]
+async fn a_run_past_its_sla_notifies_while_still_running() {
+    let sla = NotifyOn::SlaMiss { seconds: 1 };
+    let setup = setup(&[true, true], Duration::from_millis(1500), vec![rule(sla)]).await;
+    let id = setup.scheduler.trigger_now(&setup.task_id).unwrap();
+
+    let sent = setup.sent(1).await;
+    assert_eq!(sent[0]["event"], json!({ "SlaMiss": { "seconds": 1 } }));
+    assert_eq!(sent[0]["status"], "Running");
+    assert_eq!(sent[0]["execution_id"], json!(id));
+    assert_eq!(
+        setup.scheduler.get_execution(&id).unwrap().status,
+        ExecutionStatus::Running,
+        "the notification came before the run ended"
+    );
+    assert!(sent[0]["duration_ms"].as_u64().unwrap() >= 1000);
+}
+
+#[tokio::test]
+async fn a_run_within_its_sla_does_not_notify() {
+    let sla = NotifyOn::SlaMiss { seconds: 1 };
+    let setup = setup(&[true], Duration::from_millis(100), vec![rule(sla)]).await;
+    setup.run().await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(setup.lines().is_empty(), "{:?}", setup.lines());
+}
+
+fn notification(error: Option<&str>) -> Notification {
+    Notification {
+        event: NotifyOn::Failure,
+        task_id: Uuid::new_v4(),
+        task: "report".into(),
+        execution_id: Uuid::new_v4(),
+        status: ExecutionStatus::Failed,
+        error: error.map(str::to_string),
+        duration_ms: 42,
+        at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn webhook_posts_the_notification() {
+    type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+    let received: Received = Arc::default();
+    let log = received.clone();
+    let app = Router::new()
+        .route("/hook", post(move |headers: HeaderMap, Json(body): Json<Value>| async move {
+            let token = headers.get("x-token").and_then(|v| v.to_str().ok()).map(str::to_string);
+            log.lock().unwrap().push((token, body));
+            StatusCode::NO_CONTENT
+        }))
+        .route("/broken", post(|| async { StatusCode::INTERNAL_SERVER_ERROR }));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let sent = notification(Some("boom"));
+    let config = json!({ "url": format!("http://{}/hook", addr), "headers": { "x-token": "t0ken" } });
+    WebhookNotifier::new().notify(&sent, &config).await.unwrap();
+    let (token, body) = received.lock().unwrap().pop().unwrap();
+    assert_eq!(token.as_deref(), Some("t0ken"));
+    assert_eq!(body["summary"], "Task report failed: boom");
+    assert_eq!(body["execution_id"], json!(sent.execution_id));
+
+    let config = json!({ "url": format!("http://{}/broken", addr) });
+    let err = WebhookNotifier::new().notify(&sent, &config).await.unwrap_err();
+    assert!(err.to_string().contains("500"), "{}", err);
+}
+
+/// A one-message SMTP server. Returns the envelope commands and the message data, as
+/// sent on the wire.
+async fn smtp_stub(listener: TcpListener) -> (Vec<String>, String) {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"220 stub ready\r\n").await.unwrap();
+    let (mut commands, mut data) = (Vec::new(), String::new());
+    let mut in_data = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap() == 0 {
+            break;
+        }
+        if in_data {
+            if line == ".\r\n" {
+                in_data = false;
+                writer.write_all(b"250 queued\r\n").await.unwrap();
+            } else {
+                data.push_str(&line);
+            }
+            continue;
+        }
+        let command = line.trim_end().to_string();
+        let reply: &[u8] = match command.as_str() {
+            c if c.starts_with("EHLO") => b"250-stub\r\n250 8BITMIME\r\n",
+            "DATA" => {
+                in_data = true;
+                b"354 go ahead\r\n"
+            }
+            "QUIT" => {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                commands.push(command);
+                break;
+            }
+            _ => b"250 ok\r\n",
+        };
+        writer.write_all(reply).await.unwrap();
+        commands.push(command);
+    }
+    (commands, data)
+}
+
+#[tokio::test]
+async fn smtp_sends_a_dot_stuffed_message() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(smtp_stub(listener));
+
+    let sent = notification(Some("first line\n.hidden\n.\nlast"));
+    let config = json!({
+        "host": "127.0.0.1",
+        "port": port,
+        "from": "scheduler@example.com",
+        "to": ["ops@example.com", "oncall@example.com"],
+        "subject": "Alert",
+    });
+    SmtpNotifier.notify(&sent, &config).await.unwrap();
+    let (commands, data) = server.await.unwrap();
+
+    assert_eq!(commands, [
+        "EHLO chronoflow",
+        "MAIL FROM:<scheduler@example.com>",
+        "RCPT TO:<ops@example.com>",
+        "RCPT TO:<oncall@example.com>",
+        "DATA",
+        "QUIT",
+    ]);
+    assert!(data.contains("Subject: Alert\r\n"), "{}", data);
+    assert!(data.contains("To: <ops@example.com>, <oncall@example.com>\r\n"), "{}", data);
+    // Lines starting with a dot get another, including a lone dot that would
+    // otherwise end the message early.
+    assert!(data.contains("\r\n..hidden\r\n..\r\nlast\r\n"), "{}", data);
+    assert!(!data.contains("\r\n.hidden"), "{}", data);
+}
+
+#[tokio::test]
+async fn smtp_reports_a_rejected_recipient() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"220 stub\r\n").await.unwrap();
+        for reply in ["250 hi\r\n", "250 ok\r\n", "550 no such user\r\n"] {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            writer.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+
+    let config = json!({ "host": "127.0.0.1", "port": port, "from": "a@example.com", "to": ["nobody@example.com"] });
+    let err = SmtpNotifier.notify(&notification(None), &config).await.unwrap_err();
+    assert!(err.to_string().contains("550 no such user"), "{}", err);
+}
+
+#[test]
+fn notifiers_reject_unusable_timeouts() {
+    for timeout in [json!(0), json!(-1), json!(1e20), json!(f64::MAX)] {
+        let webhook = json!({ "url": "http://localhost/hook", "timeout_seconds": timeout });
+        assert!(WebhookNotifier::new().validate(&webhook).is_err(), "{}", timeout);
+        let smtp = json!({ "from": "a@example.com", "to": ["b@example.com"], "timeout_seconds": timeout });
+        assert!(SmtpNotifier.validate(&smtp).is_err(), "{}", timeout);
+    }
+    let smtp = json!({ "from": "a@example.com", "to": ["b@example.com"], "timeout_seconds": 5 });
+    assert!(SmtpNotifier.validate(&smtp).is_ok());
+}
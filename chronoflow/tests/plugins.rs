@@ -0,0 +1,146 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionLog, ExecutionStatus, FailureReason, LogLevel, Plugin, PluginConfig, PluginContext,
+    PluginManager, PluginOutput, Result, Schedule, Scheduler, Task,
+};
+use common::finished;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Needs a `mode` in its config. Keeps the context and config of each run.
+#[derive(Default)]
+struct Recorder {
+    seen: Mutex<Vec<(PluginContext, Value)>>,
+}
+
+#[async_trait]
+impl Plugin for Recorder {
+    fn name(&self) -> &str {
+        "recorder"
+    }
+
+    fn validate(&self, config: &Value) -> Result<()> {
+        match config["mode"].as_str() {
+            Some("ok" | "fail" | "fail-with-output") => Ok(()),
+            _ => Err(ChronoError::InvalidTask("recorder: mode must be ok, fail or fail-with-output".into())),
+        }
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        self.seen.lock().unwrap().push((ctx.clone(), config.clone()));
+        ctx.log.info("working");
+        ctx.log.debug(format!("mode {}", config["mode"]));
+        let output = PluginOutput::new("done").with_data(json!({ "rows": 3, "mode": config["mode"] }));
+        match config["mode"].as_str() {
+            Some("fail") => Err(ChronoError::PluginError("no rows".into())),
+            Some("fail-with-output") => Err(ChronoError::PluginFailed("exit 2".into(), Box::new(output))),
+            _ => Ok(output),
+        }
+    }
+}
+
+async fn setup() -> (Arc<Recorder>, Arc<PluginManager>, Scheduler) {
+    let recorder = Arc::new(Recorder::default());
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(recorder.clone());
+    let scheduler = Scheduler::new(plugins.clone());
+    scheduler.start().await;
+    (recorder, plugins, scheduler)
+}
+
+fn task(plugin: &str, config: Value) -> Task {
+    let plugin = PluginConfig { name: plugin.into(), wasm_path: String::new(), config };
+    Task::new("job".into(), Schedule::Manual, plugin).unwrap()
+}
+
+#[tokio::test]
+async fn a_plugin_gets_the_run_context_and_its_output_is_kept() {
+    let (recorder, _, scheduler) = setup().await;
+    let id = scheduler.add_task(task("recorder", json!({ "mode": "ok", "task": "{{ task.name }}" }))).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(execution.output, Some(PluginOutput::new("done").with_data(json!({ "rows": 3, "mode": "ok" }))));
+    let logged: Vec<(LogLevel, &str)> = execution.logs.iter().map(|l| (l.level, l.message.as_str())).collect();
+    assert_eq!(logged, [(LogLevel::Info, "working"), (LogLevel::Debug, "mode \"ok\"")]);
+
+    let seen = recorder.seen.lock().unwrap();
+    let (ctx, config) = &seen[0];
+    assert_eq!((ctx.task_id, ctx.execution_id, ctx.attempt), (id, execution.id, 1));
+    assert!(!ctx.cancel.is_cancelled());
+    assert_eq!(config["task"], "job", "the config is rendered before the plugin sees it");
+}
+
+#[tokio::test]
+async fn a_plugin_error_fails_the_run_and_is_counted() {
+    let (_, plugins, scheduler) = setup().await;
+    let id = scheduler.add_task(task("recorder", json!({ "mode": "fail" }))).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Failed);
+    assert_eq!(execution.failure_reason, Some(FailureReason::Plugin));
+    assert_eq!(execution.error.as_deref(), Some("Plugin error: no rows"));
+    assert_eq!(execution.output, None);
+    let metrics = plugins.metrics().encode();
+    assert!(metrics.contains("chronoflow_plugin_errors_total{plugin=\"recorder\"} 1"), "{}", metrics);
+}
+
+#[tokio::test]
+async fn a_failure_can_carry_output() {
+    let (_, _, scheduler) = setup().await;
+    let id = scheduler.add_task(task("recorder", json!({ "mode": "fail-with-output" }))).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Failed);
+    assert_eq!(execution.error.as_deref(), Some("Plugin error: exit 2"));
+    assert_eq!(execution.output.unwrap().data["rows"], 3);
+}
+
+#[tokio::test]
+async fn configs_are_validated_when_a_task_is_added() {
+    let (_, _, scheduler) = setup().await;
+    match scheduler.add_task(task("recorder", json!({ "mode": "sideways" }))) {
+        Err(ChronoError::InvalidTask(message)) => assert!(message.contains("mode must be"), "{}", message),
+        other => panic!("expected the plugin to refuse the config, got {:?}", other),
+    }
+    match scheduler.add_task(task("missing", json!({}))) {
+        Err(ChronoError::PluginError(message)) => assert_eq!(message, "Plugin missing not found"),
+        other => panic!("expected an unknown plugin error, got {:?}", other),
+    }
+    assert!(scheduler.list_tasks().is_empty());
+
+    // A templated config can't be judged until it is rendered at run time.
+    let id = scheduler.add_task(task("recorder", json!({ "mode": "{{ task.name }}" }))).unwrap();
+    assert_eq!(finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await.status, ExecutionStatus::Success);
+}
+
+#[test]
+fn registering_a_name_again_replaces_the_plugin() {
+    let plugins = PluginManager::new();
+    assert_eq!(plugins.plugin_names(), ["http_request", "logger", "shell"]);
+    let recorder = Arc::new(Recorder::default());
+    plugins.register(recorder.clone());
+    assert_eq!(plugins.plugin_names(), ["http_request", "logger", "recorder", "shell"]);
+
+    let replacement = Arc::new(Recorder::default());
+    plugins.register(replacement.clone());
+    assert_eq!(plugins.plugin_names().len(), 4);
+    let current = plugins.get("recorder").unwrap();
+    assert!(std::ptr::addr_eq(Arc::as_ptr(&current), Arc::as_ptr(&replacement)));
+    assert_eq!(current.config_schema(), json!({ "type": "object" }));
+}
+
+#[test]
+fn an_execution_log_keeps_a_bounded_number_of_lines() {
+    let log = ExecutionLog::default();
+    let clone = log.clone();
+    for line in 0..1005 {
+        clone.info(format!("line {}", line));
+    }
+    let lines = log.lines();
+    assert_eq!(lines.len(), 1001);
+    assert_eq!(lines[999].message, "line 999");
+    assert_eq!((lines[1000].level, lines[1000].message.as_str()), (LogLevel::Warn, "5 more line(s) dropped"));
+}
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionStatus, Plugin, PluginConfig, PluginContext, PluginManager, PluginOutput, Result,
+    RetryPolicy, Schedule, Scheduler, Task, TaskExecution,
+};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Fails with the queued errors in turn, then succeeds. Records the attempts it saw.
+#[derive(Default)]
+struct Flaky {
+    errors: Mutex<VecDeque<&'static str>>,
+    attempts: Mutex<Vec<u32>>,
+}
+
+#[async_trait]
+impl Plugin for Flaky {
+    fn name(&self) -> &str {
+        "flaky"
+    }
+
+    async fn execute(&self, ctx: PluginContext, _config: &Value) -> Result<PluginOutput> {
+        self.attempts.lock().unwrap().push(ctx.attempt);
+        match self.errors.lock().unwrap().pop_front() {
+            Some(error) => Err(ChronoError::PluginError(error.into())),
+            None => Ok(PluginOutput::new("ok")),
+        }
+    }
+}
+
+fn policy(max_attempts: u32, backoff_base_ms: u64) -> RetryPolicy {
+    RetryPolicy { max_attempts, backoff_base_ms, max_delay_ms: 10_000, jitter: 0.0, retry_on: Vec::new() }
+}
+
+async fn setup(errors: &[&'static str], config: Value, retry: RetryPolicy) -> (Arc<Flaky>, Scheduler, Uuid) {
+    let flaky = Arc::new(Flaky { errors: Mutex::new(errors.iter().copied().collect()), ..Flaky::default() });
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(flaky.clone());
+    let scheduler = Scheduler::new(plugins);
+    scheduler.start().await;
+    let plugin = PluginConfig { name: "flaky".into(), wasm_path: String::new(), config };
+    let task = Task::new("flaky".into(), Schedule::Manual, plugin).unwrap().with_retry(retry).unwrap();
+    let id = scheduler.add_task(task).unwrap();
+    (flaky, scheduler, id)
+}
+
+/// The task's executions once `count` of them have finished, oldest first.
+async fn executions(scheduler: &Scheduler, task_id: &Uuid, count: usize) -> Vec<TaskExecution> {
+    let finished = |e: &TaskExecution| !matches!(e.status, ExecutionStatus::Queued | ExecutionStatus::Running);
+    for _ in 0..250 {
+        let executions = scheduler.list_executions(task_id);
+        if executions.len() >= count && executions.iter().all(finished) {
+            return executions;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {} finished executions, got {:?}", count, scheduler.list_executions(task_id));
+}
+
+fn statuses(executions: &[TaskExecution]) -> Vec<ExecutionStatus> {
+    executions.iter().map(|e| e.status).collect()
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    let retry = RetryPolicy { max_delay_ms: 500, ..policy(10, 100) };
+    let delays: Vec<u64> = (1..=5).map(|attempt| retry.delay_for(attempt).as_millis() as u64).collect();
+    assert_eq!(delays, [100, 200, 400, 500, 500]);
+    // Far-off attempts stay at the cap instead of overflowing.
+    assert_eq!(retry.delay_for(u32::MAX), Duration::from_millis(500));
+    let huge = RetryPolicy { backoff_base_ms: u64::MAX, max_delay_ms: u64::MAX, ..retry };
+    assert_eq!(huge.delay_for(40), Duration::from_millis(u64::MAX));
+}
+
+#[test]
+fn jitter_only_shortens_the_delay() {
+    let retry = RetryPolicy { jitter: 0.5, ..policy(10, 1000) };
+    for _ in 0..200 {
+        let delay = retry.delay_for(2);
+        assert!(delay >= Duration::from_millis(1000) && delay <= Duration::from_millis(2000), "{:?}", delay);
+    }
+}
+
+#[test]
+fn retry_on_limits_which_errors_are_retried() {
+    let retry = RetryPolicy { retry_on: vec!["timeout".into(), "503".into()], ..policy(3, 10) };
+    assert!(retry.should_retry(1, "upstream returned 503"));
+    assert!(retry.should_retry(2, "connect timeout"));
+    assert!(!retry.should_retry(3, "connect timeout"), "the last attempt is not retried");
+    assert!(!retry.should_retry(1, "404 not found"));
+    assert!(policy(3, 10).should_retry(1, "anything"));
+}
+
+#[test]
+fn invalid_policies_are_refused() {
+    assert!(policy(0, 10).validate().is_err());
+    assert!(RetryPolicy { jitter: 1.5, ..policy(3, 10) }.validate().is_err());
+    assert!(RetryPolicy { jitter: -0.1, ..policy(3, 10) }.validate().is_err());
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    assert!(Task::new("t".into(), Schedule::Manual, plugin).unwrap().with_retry(policy(0, 10)).is_err());
+}
+
+#[tokio::test]
+async fn failed_attempts_are_retried_as_linked_executions() {
+    let (flaky, scheduler, id) = setup(&["first", "second"], json!({}), policy(3, 100)).await;
+    let first = scheduler.trigger_now(&id).unwrap();
+
+    let runs = executions(&scheduler, &id, 3).await;
+    assert_eq!(statuses(&runs), [ExecutionStatus::Failed, ExecutionStatus::Failed, ExecutionStatus::Success]);
+    assert_eq!(runs.iter().map(|e| e.attempt).collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(*flaky.attempts.lock().unwrap(), [1, 2, 3]);
+    assert_eq!(runs[0].id, first);
+    assert_eq!(runs[0].retry_of, None);
+    assert!(runs[1..].iter().all(|e| e.retry_of == Some(first)));
+    assert!(runs[0].error.as_deref().unwrap().contains("first"));
+
+    // Each retry waits out its backoff: 100ms, then 200ms.
+    let gap = |a: &TaskExecution, b: &TaskExecution| (b.started_at - a.finished_at.unwrap()).num_milliseconds();
+    assert!(gap(&runs[0], &runs[1]) >= 95, "{}ms", gap(&runs[0], &runs[1]));
+    assert!(gap(&runs[1], &runs[2]) >= 195, "{}ms", gap(&runs[1], &runs[2]));
+}
+
+#[tokio::test]
+async fn a_run_fails_once_its_attempts_are_used_up() {
+    let (_, scheduler, id) = setup(&["a", "b", "c"], json!({}), policy(2, 10)).await;
+    scheduler.trigger_now(&id).unwrap();
+    let runs = executions(&scheduler, &id, 2).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(scheduler.list_executions(&id).len(), 2);
+    assert_eq!(statuses(&runs), [ExecutionStatus::Failed, ExecutionStatus::Failed]);
+}
+
+#[tokio::test]
+async fn errors_outside_retry_on_are_not_retried() {
+    let retry = RetryPolicy { retry_on: vec!["transient".into()], ..policy(5, 10) };
+    let (_, scheduler, id) = setup(&["transient glitch", "permanent failure", "transient"], json!({}), retry).await;
+    scheduler.trigger_now(&id).unwrap();
+    let runs = executions(&scheduler, &id, 2).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(scheduler.list_executions(&id).len(), 2);
+    assert!(runs[1].error.as_deref().unwrap().contains("permanent failure"));
+}
+
+#[tokio::test]
+async fn a_config_that_fails_to_render_is_not_retried() {
+    let (flaky, scheduler, id) = setup(&[], json!({ "x": "{{ secrets.missing }}" }), policy(5, 10)).await;
+    scheduler.trigger_now(&id).unwrap();
+    let runs = executions(&scheduler, &id, 1).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(scheduler.list_executions(&id).len(), 1);
+    assert_eq!(runs[0].status, ExecutionStatus::Failed);
+    assert!(runs[0].error.as_deref().unwrap().contains("secrets.missing"), "{:?}", runs[0].error);
+    assert!(flaky.attempts.lock().unwrap().is_empty(), "the plugin never ran");
+}
+
+#[tokio::test]
+async fn a_task_disabled_during_backoff_is_not_retried() {
+    let (_, scheduler, id) = setup(&["a", "b"], json!({}), policy(3, 300)).await;
+    scheduler.trigger_now(&id).unwrap();
+    executions(&scheduler, &id, 1).await;
+    scheduler.set_enabled(&id, false).unwrap();
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(statuses(&scheduler.list_executions(&id)), [ExecutionStatus::Failed]);
+}
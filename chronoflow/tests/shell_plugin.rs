@@ -0,0 +1,166 @@
+#![cfg(unix)]
+
+mod common;
+
+use chronoflow::{ChronoError, ExecutionLog, Plugin, PluginContext, PluginOutput, ShellPlugin};
+#[cfg(target_os = "linux")]
+use common::alive;
+use common::Scratch;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+fn ctx() -> PluginContext {
+    PluginContext {
+        task_id: Uuid::new_v4(),
+        execution_id: Uuid::new_v4(),
+        attempt: 1,
+        scheduled_at: None,
+        cancel: CancellationToken::new(),
+        log: ExecutionLog::default(),
+    }
+}
+
+fn sh(script: &str) -> Value {
+    json!({ "argv": ["/bin/sh", "-c", script] })
+}
+
+/// The output of a run that failed.
+fn failed(result: chronoflow::Result<PluginOutput>) -> (String, PluginOutput) {
+    match result {
+        Err(ChronoError::PluginFailed(message, output)) => (message, *output),
+        other => panic!("expected the run to fail, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn captures_exit_code_and_output() {
+    let output = ShellPlugin.execute(ctx(), &sh("echo out; echo err >&2")).await.unwrap();
+    assert_eq!(output.data["exit_code"], 0);
+    assert_eq!(output.data["stdout"], "out\n");
+    assert_eq!(output.data["stderr"], "err\n");
+
+    let (message, output) = failed(ShellPlugin.execute(ctx(), &sh("exit 3")).await);
+    assert!(message.contains("status 3"), "{}", message);
+    assert_eq!(output.data["exit_code"], 3);
+}
+
+#[tokio::test]
+async fn timeout_kills_the_process_group() {
+    let mut config = sh("sleep 30 & sleep 30; wait");
+    config["timeout_seconds"] = json!(0.3);
+    let started = Instant::now();
+    let (message, output) = failed(ShellPlugin.execute(ctx(), &config).await);
+
+    assert!(message.contains("timed out"), "{}", message);
+    assert_eq!(output.data["exit_code"], Value::Null);
+    // The background sleep holds stdout too; killing only the shell would leave the
+    // run waiting on it.
+    assert!(started.elapsed() < Duration::from_secs(10), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn cancellation_kills_the_process_group() {
+    let ctx = ctx();
+    let cancel = ctx.cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        cancel.cancel();
+    });
+    let started = Instant::now();
+    let (message, _) = failed(ShellPlugin.execute(ctx, &sh("sleep 30 & sleep 30; wait")).await);
+    assert!(message.contains("cancelled"), "{}", message);
+    assert!(started.elapsed() < Duration::from_secs(10), "took {:?}", started.elapsed());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn dropping_the_run_kills_the_process_group() {
+    let scratch = Scratch::new("shell");
+    let pid_file = scratch.join("pid");
+    let config = sh(&format!("sleep 30 & echo $! > {}; sleep 30; wait", pid_file.display()));
+    let run = ShellPlugin.execute(ctx(), &config);
+    assert!(tokio::time::timeout(Duration::from_millis(300), run).await.is_err());
+
+    let pid: u32 = std::fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+    for _ in 0..50 {
+        if !alive(pid) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the backgrounded sleep ({}) outlived the dropped run", pid);
+}
+
+#[tokio::test]
+async fn output_left_open_after_exit_does_not_hold_up_the_run() {
+    // The shell exits at once; the sleep it leaves behind keeps the pipes open.
+    let mut config = sh("echo started; sleep 30 &");
+    config["timeout_seconds"] = json!(0.5);
+    let started = Instant::now();
+    let output = ShellPlugin.execute(ctx(), &config).await.unwrap();
+
+    assert_eq!(output.data["exit_code"], 0);
+    assert_eq!(output.data["stdout"], "started\n");
+    assert!(started.elapsed() < Duration::from_secs(10), "took {:?}", started.elapsed());
+
+    // Without a timeout the grace period bounds it.
+    let started = Instant::now();
+    ShellPlugin.execute(ctx(), &sh("sleep 30 &")).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn output_is_capped_per_stream() {
+    let mut config = sh("head -c 100000 /dev/zero | tr '\\0' x; echo short >&2");
+    config["max_output_bytes"] = json!(10);
+    let output = ShellPlugin.execute(ctx(), &config).await.unwrap();
+
+    assert_eq!(output.data["stdout"], "x".repeat(10));
+    assert_eq!(output.data["stdout_truncated"], true);
+    assert_eq!(output.data["stderr"], "short\n");
+    assert_eq!(output.data["stderr_truncated"], false);
+}
+
+#[tokio::test]
+async fn clear_env_passes_only_the_configured_variables() {
+    std::env::set_var("CHRONOFLOW_SHELL_TEST_SECRET", "leaked");
+    let config = json!({
+        "argv": ["/usr/bin/env"],
+        "clear_env": true,
+        "env": { "GREETING": "hello" },
+    });
+    let output = ShellPlugin.execute(ctx(), &config).await.unwrap();
+    assert_eq!(output.data["stdout"], "GREETING=hello\n");
+
+    // Without it the scheduler's environment is inherited.
+    let output = ShellPlugin.execute(ctx(), &json!({ "argv": ["/usr/bin/env"] })).await.unwrap();
+    assert!(output.data["stdout"].as_str().unwrap().contains("CHRONOFLOW_SHELL_TEST_SECRET=leaked"));
+}
+
+#[tokio::test]
+async fn rlimits_apply_to_the_child() {
+    let dir = Scratch::new("shell");
+    let mut config = sh("head -c 100000 /dev/zero > big");
+    config["cwd"] = json!(dir.path());
+    config["limits"] = json!({ "file_size_bytes": 1000 });
+    failed(ShellPlugin.execute(ctx(), &config).await);
+    assert_eq!(std::fs::metadata(dir.join("big")).unwrap().len(), 1000);
+
+    let mut config = sh("ulimit -n");
+    config["limits"] = json!({ "open_files": 17 });
+    let output = ShellPlugin.execute(ctx(), &config).await.unwrap();
+    assert_eq!(output.data["stdout"], "17\n");
+}
+
+#[test]
+fn validate_rejects_bad_config() {
+    assert!(ShellPlugin.validate(&json!({ "argv": [] })).is_err());
+    assert!(ShellPlugin.validate(&json!({ "argv": ["true"], "limits": { "stack": 1 } })).is_err());
+    for timeout in [json!(0), json!(-1), json!(1e20), json!(f64::MAX)] {
+        let config = json!({ "argv": ["true"], "timeout_seconds": timeout });
+        assert!(ShellPlugin.validate(&config).is_err(), "{}", timeout);
+    }
+    assert!(ShellPlugin.validate(&json!({ "argv": ["true"], "timeout_seconds": 1e9 })).is_ok());
+}
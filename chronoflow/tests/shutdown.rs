@@ -0,0 +1,178 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionStatus, OverlapPolicy, Plugin, PluginConfig, PluginContext, PluginManager,
+    PluginOutput, Result, RetryPolicy, Schedule, Scheduler, ShutdownReport, Storage, Task,
+};
+use common::Scratch;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Sleeps for `config.ms` unless cancelled, then fails if `config.fail` is set. Keeps
+/// each run's cancel token.
+#[derive(Default)]
+struct Sleeper {
+    runs: AtomicUsize,
+    tokens: Mutex<Vec<CancellationToken>>,
+}
+
+#[async_trait]
+impl Plugin for Sleeper {
+    fn name(&self) -> &str {
+        "sleeper"
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        self.tokens.lock().unwrap().push(ctx.cancel.clone());
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(config["ms"].as_u64().unwrap_or(0))) => {}
+            _ = ctx.cancel.cancelled() => return Err(ChronoError::Cancelled("stopped".into())),
+        }
+        if config["fail"].as_bool().unwrap_or(false) {
+            return Err(ChronoError::PluginError("failed".into()));
+        }
+        Ok(PluginOutput::new("slept"))
+    }
+}
+
+async fn scheduler() -> (Arc<Sleeper>, Scheduler) {
+    let sleeper = Arc::new(Sleeper::default());
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(sleeper.clone());
+    let scheduler = Scheduler::new(plugins);
+    scheduler.start().await;
+    (sleeper, scheduler)
+}
+
+fn task(ms: u64) -> Task {
+    let plugin = PluginConfig { name: "sleeper".into(), wasm_path: String::new(), config: json!({ "ms": ms }) };
+    Task::new(format!("sleep-{}", ms), Schedule::Manual, plugin).unwrap()
+}
+
+fn status(scheduler: &Scheduler, id: &Uuid) -> ExecutionStatus {
+    scheduler.get_execution(id).unwrap().status
+}
+
+/// Waits until the plugin has been called `count` times in all.
+async fn started(sleeper: &Sleeper, count: usize) {
+    for _ in 0..100 {
+        if sleeper.runs.load(Ordering::SeqCst) >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the plugin ran {} time(s), expected {}", sleeper.runs.load(Ordering::SeqCst), count);
+}
+
+#[tokio::test]
+async fn runs_that_finish_within_the_grace_period_are_drained() {
+    let (sleeper, scheduler) = scheduler().await;
+    let id = scheduler.add_task(task(300)).unwrap();
+    let run = scheduler.trigger_now(&id).unwrap();
+    started(&sleeper, 1).await;
+
+    let report = scheduler.shutdown_handle().shutdown(Duration::from_secs(5)).await;
+    assert_eq!(report, ShutdownReport { drained: 1, interrupted: 0 });
+    assert_eq!(status(&scheduler, &run), ExecutionStatus::Success);
+    assert!(scheduler.is_shutting_down());
+    assert!(!scheduler.is_running());
+}
+
+#[tokio::test]
+async fn runs_past_the_deadline_are_cancelled_and_interrupted() {
+    let (sleeper, scheduler) = scheduler().await;
+    let id = scheduler.add_task(task(30_000)).unwrap();
+    let run = scheduler.trigger_now(&id).unwrap();
+    started(&sleeper, 1).await;
+
+    let begun = Instant::now();
+    let report = scheduler.shutdown_handle().shutdown(Duration::from_millis(200)).await;
+    assert!(begun.elapsed() < Duration::from_secs(3), "{:?}", begun.elapsed());
+    assert_eq!(report, ShutdownReport { drained: 0, interrupted: 1 });
+    let execution = scheduler.get_execution(&run).unwrap();
+    assert_eq!(execution.status, ExecutionStatus::Interrupted);
+    assert_eq!(execution.error.as_deref(), Some("Execution interrupted: the scheduler shut down"));
+    assert!(execution.finished_at.is_some());
+    assert!(sleeper.tokens.lock().unwrap()[0].is_cancelled(), "the plugin was told to stop");
+}
+
+#[tokio::test]
+async fn runs_waiting_to_start_are_interrupted_at_once() {
+    let (sleeper, scheduler) = scheduler().await;
+    let id = scheduler.add_task(task(30_000).with_overlap_policy(OverlapPolicy::Queue)).unwrap();
+    let running = scheduler.trigger_now(&id).unwrap();
+    let waiting = scheduler.trigger_now(&id).unwrap();
+    started(&sleeper, 1).await;
+
+    let report = scheduler.shutdown_handle().shutdown(Duration::from_millis(100)).await;
+    assert_eq!(report, ShutdownReport { drained: 0, interrupted: 2 });
+    assert_eq!(status(&scheduler, &running), ExecutionStatus::Interrupted);
+    assert_eq!(status(&scheduler, &waiting), ExecutionStatus::Interrupted);
+    assert_eq!(sleeper.runs.load(Ordering::SeqCst), 1, "the queued run never started");
+}
+
+#[tokio::test]
+async fn nothing_new_starts_after_shutdown() {
+    let (sleeper, scheduler) = scheduler().await;
+    let id = scheduler.add_task(task(0)).unwrap();
+    scheduler.shutdown_handle().shutdown(Duration::from_secs(1)).await;
+
+    assert!(matches!(scheduler.trigger_now(&id), Err(ChronoError::ShuttingDown)));
+    let plugin = PluginConfig { name: "sleeper".into(), wasm_path: String::new(), config: json!({}) };
+    let every_second = Task::new("every-second".into(), Schedule::Interval { seconds: 1 }, plugin).unwrap();
+    let every_second = scheduler.add_task(every_second).unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(sleeper.runs.load(Ordering::SeqCst), 0);
+    assert!(scheduler.list_executions(&id).is_empty());
+    assert!(scheduler.list_executions(&every_second).is_empty());
+}
+
+#[tokio::test]
+async fn a_retry_waiting_out_its_backoff_is_not_started() {
+    let (sleeper, scheduler) = scheduler().await;
+    let retry = RetryPolicy {
+        max_attempts: 3,
+        backoff_base_ms: 60_000,
+        max_delay_ms: 60_000,
+        jitter: 0.0,
+        retry_on: Vec::new(),
+    };
+    let mut failing = task(0).with_retry(retry).unwrap();
+    failing.plugin.config = json!({ "fail": true });
+    let id = scheduler.add_task(failing).unwrap();
+    scheduler.trigger_now(&id).unwrap();
+    started(&sleeper, 1).await;
+
+    let begun = Instant::now();
+    scheduler.shutdown_handle().shutdown(Duration::from_millis(200)).await;
+    assert!(begun.elapsed() < Duration::from_secs(3), "{:?}", begun.elapsed());
+    let executions = scheduler.list_executions(&id);
+    assert_eq!(executions.len(), 1);
+    assert_eq!(executions[0].status, ExecutionStatus::Failed);
+    assert_eq!(sleeper.runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn interrupted_runs_are_persisted() {
+    let scratch = Scratch::new("shutdown");
+    let storage = scratch.storage();
+    let sleeper = Arc::new(Sleeper::default());
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(sleeper.clone());
+    let scheduler = Scheduler::new(plugins).with_storage(storage.clone()).unwrap();
+    scheduler.start().await;
+    let id = scheduler.add_task(task(30_000)).unwrap();
+    let run = scheduler.trigger_now(&id).unwrap();
+    started(&sleeper, 1).await;
+
+    scheduler.shutdown_handle().shutdown(Duration::from_millis(100)).await;
+    let saved = storage.load().unwrap().executions.into_iter().find(|e| e.id == run).unwrap();
+    assert_eq!(saved.status, ExecutionStatus::Interrupted);
+    assert!(saved.finished_at.is_some());
+}
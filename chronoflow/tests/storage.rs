@@ -0,0 +1,155 @@
+mod common;
+
+use chrono::Utc;
+use chronoflow::cluster::{Command, Entry, RaftRecord, StateMachine};
+use chronoflow::{ChronoError, ExecutionStatus, FileStorage, PluginConfig, Schedule, Storage, Task, TaskExecution};
+use common::Scratch;
+use serde_json::json;
+use std::io::Write;
+use uuid::Uuid;
+
+/// The number of records in `scratch`'s state log.
+fn lines(scratch: &Scratch) -> usize {
+    std::fs::read_to_string(scratch.state_file()).unwrap().lines().count()
+}
+
+fn task(name: &str) -> Task {
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config: json!({}) };
+    Task::new(name.into(), Schedule::Interval { seconds: 60 }, plugin).unwrap()
+}
+
+fn execution(task: &Task) -> TaskExecution {
+    let mut execution = TaskExecution::new(task.id, Utc::now(), false);
+    execution.status = ExecutionStatus::Success;
+    execution.finished_at = Some(Utc::now());
+    execution
+}
+
+fn names(tasks: &[Task]) -> Vec<String> {
+    let mut names: Vec<String> = tasks.iter().map(|t| t.name.clone()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn a_reopened_log_has_the_latest_state() {
+    let scratch = Scratch::new("storage");
+    let storage = FileStorage::open(scratch.state_file()).unwrap();
+    assert!(storage.load().unwrap().tasks.is_empty());
+
+    let (mut kept, dropped) = (task("kept"), task("dropped"));
+    storage.save_task(&kept).unwrap();
+    storage.save_task(&dropped).unwrap();
+    kept.enabled = false;
+    storage.save_task(&kept).unwrap();
+    storage.delete_task(&dropped.id).unwrap();
+    let (first, second) = (execution(&kept), execution(&kept));
+    storage.save_execution(&first).unwrap();
+    storage.save_execution(&second).unwrap();
+    storage.delete_executions(&[first.id]).unwrap();
+    drop(storage);
+
+    let snapshot = FileStorage::open(scratch.state_file()).unwrap().load().unwrap();
+    assert_eq!(names(&snapshot.tasks), ["kept"]);
+    assert!(!snapshot.tasks[0].enabled);
+    let executions: Vec<Uuid> = snapshot.executions.iter().map(|e| e.id).collect();
+    assert_eq!(executions, [second.id]);
+    // Loading rewrote the log down to what is left.
+    assert_eq!(lines(&scratch), 2);
+}
+
+#[test]
+fn a_torn_last_line_is_dropped() {
+    let scratch = Scratch::new("storage");
+    let storage = FileStorage::open(scratch.state_file()).unwrap();
+    storage.load().unwrap();
+    storage.save_task(&task("whole")).unwrap();
+    drop(storage);
+    let mut file = std::fs::OpenOptions::new().append(true).open(scratch.state_file()).unwrap();
+    file.write_all(br#"{"record":"task","id":"#).unwrap();
+    drop(file);
+
+    let storage = FileStorage::open(scratch.state_file()).unwrap();
+    assert_eq!(names(&storage.load().unwrap().tasks), ["whole"]);
+    // The torn line is gone, so appending after it doesn't corrupt the next record.
+    storage.save_task(&task("after")).unwrap();
+    drop(storage);
+    let snapshot = FileStorage::open(scratch.state_file()).unwrap().load().unwrap();
+    assert_eq!(names(&snapshot.tasks), ["after", "whole"]);
+}
+
+#[test]
+fn a_bad_line_before_the_last_is_reported() {
+    let scratch = Scratch::new("storage");
+    let storage = FileStorage::open(scratch.state_file()).unwrap();
+    storage.load().unwrap();
+    storage.save_task(&task("first")).unwrap();
+    drop(storage);
+    let mut file = std::fs::OpenOptions::new().append(true).open(scratch.state_file()).unwrap();
+    file.write_all(b"not json\n").unwrap();
+    drop(file);
+    FileStorage::open(scratch.state_file()).unwrap().save_task(&task("last")).unwrap();
+
+    match FileStorage::open(scratch.state_file()).unwrap().load() {
+        Err(ChronoError::StorageError(message)) => {
+            assert!(message.contains(&format!("{}:2:", scratch.state_file().display())), "{}", message);
+        }
+        other => panic!("expected a storage error, got {:?}", other.map(|s| s.tasks.len())),
+    }
+}
+
+#[test]
+fn a_growing_log_is_compacted_while_running() {
+    let scratch = Scratch::new("storage");
+    let storage = FileStorage::open(scratch.state_file()).unwrap().with_compaction_threshold(8 * 1024);
+    storage.load().unwrap();
+    let mut task = task("busy");
+    for run in 0..500 {
+        task.plugin.config = json!({ "run": run });
+        storage.save_task(&task).unwrap();
+    }
+    // 500 saves of a few hundred bytes each would be well over the threshold.
+    assert!(std::fs::metadata(scratch.state_file()).unwrap().len() < 16 * 1024);
+    assert!(lines(&scratch) < 100, "{} lines", lines(&scratch));
+    drop(storage);
+
+    let snapshot = FileStorage::open(scratch.state_file()).unwrap().load().unwrap();
+    assert_eq!(snapshot.tasks.len(), 1);
+    assert_eq!(snapshot.tasks[0].plugin.config, json!({ "run": 499 }));
+}
+
+#[test]
+fn compaction_keeps_the_raft_state() {
+    let scratch = Scratch::new("storage");
+    let storage = FileStorage::open(scratch.state_file()).unwrap().with_compaction_threshold(4 * 1024);
+    storage.load().unwrap();
+    let entry = |term, name: &str| Entry { term, origin: "a".into(), command: Command::PutTask(task(name)) };
+
+    storage.save_raft(&RaftRecord::Vote { term: 3, voted_for: Some("b".into()) }).unwrap();
+    let mut state = StateMachine::default();
+    let snapshotted = task("snapshotted");
+    state.tasks.insert(snapshotted.id, snapshotted);
+    storage.save_raft(&RaftRecord::Snapshot { index: 2, term: 2, state }).unwrap();
+    storage.save_raft(&RaftRecord::Entries { index: 3, entries: vec![entry(3, "x"), entry(3, "y")] }).unwrap();
+    // Replaces y.
+    storage.save_raft(&RaftRecord::Entries { index: 4, entries: vec![entry(3, "z")] }).unwrap();
+    // Enough unrelated writes to compact a few times over.
+    let filler = task("filler");
+    for _ in 0..50 {
+        storage.save_task(&filler).unwrap();
+    }
+    assert!(lines(&scratch) < 20, "{} lines", lines(&scratch));
+    drop(storage);
+
+    let raft = FileStorage::open(scratch.state_file()).unwrap().load().unwrap().raft.unwrap();
+    assert_eq!((raft.term, raft.voted_for.as_deref()), (3, Some("b")));
+    assert_eq!((raft.snapshot_index, raft.snapshot_term), (2, 2));
+    assert_eq!(names(&raft.snapshot.tasks.into_values().collect::<Vec<_>>()), ["snapshotted"]);
+    let logged: Vec<String> = raft.entries.iter()
+        .map(|e| match &e.command {
+            Command::PutTask(task) => task.name.clone(),
+            other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    assert_eq!(logged, ["x", "z"]);
+}
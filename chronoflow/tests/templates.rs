@@ -0,0 +1,151 @@
+mod common;
+
+use chronoflow::template::check;
+use chronoflow::{
+    ChronoError, DirSecrets, ExecutionStatus, FailureReason, NoSecrets, PluginConfig, PluginManager, Schedule,
+    Scheduler, SecretProvider, Task, TemplateContext,
+};
+use common::{finished, Scratch};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn values() -> Value {
+    json!({
+        "task": { "name": "report" },
+        "run": { "attempt": 2, "catch_up": false, "workflow_run_id": null },
+        "upstream": { "fetch": { "output": { "id": 7, "items": ["a", { "b": true }] } } },
+    })
+}
+
+fn render(config: Value) -> chronoflow::Result<Value> {
+    TemplateContext::new(values(), &NoSecrets).render(&config)
+}
+
+fn template_error(config: Value) -> String {
+    match render(config) {
+        Err(ChronoError::TemplateError(message)) => message,
+        other => panic!("expected a template error, got {:?}", other),
+    }
+}
+
+#[test]
+fn a_lone_placeholder_keeps_its_type() {
+    assert_eq!(render(json!("{{ run.attempt }}")).unwrap(), json!(2));
+    assert_eq!(render(json!("{{run.catch_up}}")).unwrap(), json!(false));
+    let output = json!({ "id": 7, "items": ["a", { "b": true }] });
+    assert_eq!(render(json!("{{ upstream.fetch.output }}")).unwrap(), output);
+    assert_eq!(render(json!("{{ run.workflow_run_id }}")).unwrap(), Value::Null);
+}
+
+#[test]
+fn placeholders_in_text_are_substituted() {
+    let rendered = render(json!("{{ task.name }} #{{ upstream.fetch.output.id }} [{{ run.workflow_run_id }}]"));
+    assert_eq!(rendered.unwrap(), json!("report #7 []"));
+    assert_eq!(render(json!("items: {{ upstream.fetch.output.items.1 }}")).unwrap(), json!(r#"items: {"b":true}"#));
+    assert_eq!(render(json!("{{ upstream.fetch.output.items.0 }}")).unwrap(), json!("a"));
+}
+
+#[test]
+fn values_are_rendered_throughout_the_config_but_keys_are_not() {
+    let config = json!({
+        "{{ task.name }}": ["{{ run.attempt }}", 1.5, null, { "nested": "x{{ run.attempt }}" }],
+        "flag": true,
+    });
+    assert_eq!(render(config).unwrap(), json!({
+        "{{ task.name }}": [2, 1.5, null, { "nested": "x2" }],
+        "flag": true,
+    }));
+}
+
+#[test]
+fn unknown_paths_name_the_missing_part() {
+    assert_eq!(template_error(json!("{{ run.nope }}")), "{{ run.nope }}: 'run.nope' not found");
+    assert_eq!(
+        template_error(json!({ "a": ["ok", "id {{ upstream.missing.output }}"] })),
+        "{{ upstream.missing.output }}: 'upstream.missing' not found",
+    );
+    assert_eq!(
+        template_error(json!("{{ upstream.fetch.output.items.9 }}")),
+        "{{ upstream.fetch.output.items.9 }}: 'upstream.fetch.output.items.9' not found",
+    );
+    assert_eq!(template_error(json!("{{ secrets.token }}")), "{{ secrets.token }}: unknown secret");
+}
+
+#[test]
+fn bad_syntax_is_reported_by_check() {
+    assert!(!check(&json!({ "url": "https://example.com", "n": 1 })).unwrap());
+    assert!(check(&json!({ "deep": [{ "x": "{{ run.attempt }}" }] })).unwrap());
+    let cases = [
+        ("{{ run.attempt", "'{{ run.attempt': unterminated placeholder"),
+        ("a {{ }} b", "'a {{ }} b': invalid placeholder ''"),
+        ("{{ run..attempt }}", "'{{ run..attempt }}': invalid placeholder 'run..attempt'"),
+        ("{{ run attempt }}", "'{{ run attempt }}': invalid placeholder 'run attempt'"),
+    ];
+    for (text, expected) in cases {
+        match check(&json!({ "x": text })) {
+            Err(ChronoError::TemplateError(message)) => assert_eq!(message, expected),
+            other => panic!("{:?} should be invalid, got {:?}", text, other),
+        }
+    }
+}
+
+#[test]
+fn environment_variables_are_read_when_rendered() {
+    let name = format!("CHRONOFLOW_TEMPLATE_TEST_{}", Uuid::new_v4().simple());
+    let placeholder = format!("{{{{ env.{} }}}}", name);
+    assert_eq!(template_error(json!(placeholder)), format!("{}: environment variable is not set", placeholder));
+    std::env::set_var(&name, "42");
+    // Always text, even when it looks like a number.
+    assert_eq!(render(json!(placeholder)).unwrap(), json!("42"));
+    std::env::remove_var(&name);
+}
+
+#[test]
+fn secrets_come_from_files_in_a_directory() {
+    let dir = Scratch::new("secrets");
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    dir.write("token", "s3cret\n");
+    dir.write("windows", "crlf\r\n");
+    dir.write("lines", "a\nb\n\n");
+    dir.write("nested/key", "hidden");
+    dir.write(".hidden", "hidden");
+
+    let secrets = DirSecrets::new(dir.path());
+    assert_eq!(secrets.get("token").as_deref(), Some("s3cret"));
+    assert_eq!(secrets.get("windows").as_deref(), Some("crlf"));
+    assert_eq!(secrets.get("lines").as_deref(), Some("a\nb\n"), "only one newline is dropped");
+    for name in ["missing", "nested/key", "../token", ".hidden", ""] {
+        assert_eq!(secrets.get(name), None, "{:?}", name);
+    }
+    let rendered = TemplateContext::new(values(), &secrets).render(&json!("Bearer {{ secrets.token }}"));
+    assert_eq!(rendered.unwrap(), json!("Bearer s3cret"));
+}
+
+fn logger(message: &str) -> Task {
+    let config = json!({ "message": message });
+    let plugin = PluginConfig { name: "logger".into(), wasm_path: String::new(), config };
+    Task::new("templated".into(), Schedule::Manual, plugin).unwrap()
+}
+
+#[tokio::test]
+async fn a_run_renders_its_config_and_reports_render_failures() {
+    let secrets: HashMap<String, String> = [("token".to_string(), "abc".to_string())].into();
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new())).with_secrets(Arc::new(secrets));
+    scheduler.start().await;
+
+    let id = scheduler.add_task(logger("{{ task.name }} attempt {{ run.attempt }} with {{ secrets.token }}")).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(execution.output.unwrap().message, "Logged: templated attempt 1 with abc");
+
+    let id = scheduler.add_task(logger("{{ secrets.other }}")).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Failed);
+    assert_eq!(execution.failure_reason, Some(FailureReason::Template));
+    assert_eq!(execution.error.as_deref(), Some("Template error: {{ secrets.other }}: unknown secret"));
+
+    // Bad syntax is caught when the task is added.
+    assert!(matches!(scheduler.add_task(logger("{{ run.attempt")), Err(ChronoError::TemplateError(_))));
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, ExecutionStatus, FailureReason, Plugin, PluginConfig, PluginContext, PluginManager, PluginOutput,
+    Result, RetryPolicy, Schedule, Scheduler, SchedulerConfig, Task,
+};
+#[cfg(target_os = "linux")]
+use common::alive;
+use common::{finished, Scratch};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Sleeps for `config.ms` unless cancelled, keeping each run's cancel token.
+#[derive(Default)]
+struct Sleeper {
+    tokens: Mutex<Vec<CancellationToken>>,
+}
+
+impl Sleeper {
+    fn cancelled(&self) -> Vec<bool> {
+        self.tokens.lock().unwrap().iter().map(|t| t.is_cancelled()).collect()
+    }
+}
+
+#[async_trait]
+impl Plugin for Sleeper {
+    fn name(&self) -> &str {
+        "sleeper"
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        self.tokens.lock().unwrap().push(ctx.cancel.clone());
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(config["ms"].as_u64().unwrap_or(0))) => {}
+            _ = ctx.cancel.cancelled() => return Err(ChronoError::Cancelled("stopped".into())),
+        }
+        Ok(PluginOutput::new("slept"))
+    }
+}
+
+async fn scheduler(config: SchedulerConfig) -> (Arc<Sleeper>, Scheduler) {
+    let sleeper = Arc::new(Sleeper::default());
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(sleeper.clone());
+    let scheduler = Scheduler::new(plugins).with_config(config);
+    scheduler.start().await;
+    (sleeper, scheduler)
+}
+
+fn task(ms: u64) -> Task {
+    let plugin = PluginConfig { name: "sleeper".into(), wasm_path: String::new(), config: json!({ "ms": ms }) };
+    Task::new(format!("sleep-{}", ms), Schedule::Manual, plugin).unwrap()
+}
+
+#[tokio::test]
+async fn a_run_over_its_timeout_is_stopped() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(task(10_000).with_timeout(1)).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Timeout);
+    assert_eq!(execution.failure_reason, Some(FailureReason::Timeout));
+    assert_eq!(execution.error.as_deref(), Some("Execution timed out after 1s"));
+    let duration = execution.duration_ms.unwrap();
+    assert!((1000..3000).contains(&duration), "{}ms", duration);
+    assert!(execution.logs.iter().any(|l| l.message == "timed out after 1s"), "{:?}", execution.logs);
+    // The plugin is told to stop, so it can clean up after itself.
+    assert_eq!(sleeper.cancelled(), [true]);
+}
+
+#[tokio::test]
+async fn a_run_within_its_timeout_succeeds() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(task(200).with_timeout(1)).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Success);
+    assert_eq!(sleeper.cancelled(), [false]);
+}
+
+#[tokio::test]
+async fn the_default_timeout_applies_to_tasks_without_their_own() {
+    let config = SchedulerConfig { default_timeout: Some(Duration::from_millis(300)), ..SchedulerConfig::default() };
+    let (_, scheduler) = scheduler(config).await;
+    let unbounded = scheduler.add_task(task(800)).unwrap();
+    let own = scheduler.add_task(task(801).with_timeout(5)).unwrap();
+
+    let execution = finished(&scheduler, &scheduler.trigger_now(&unbounded).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Timeout);
+    assert_eq!(execution.error.as_deref(), Some("Execution timed out after 300ms"));
+    let execution = finished(&scheduler, &scheduler.trigger_now(&own).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Success, "the task's own limit wins");
+}
+
+#[tokio::test]
+async fn time_spent_queued_does_not_count() {
+    let config = SchedulerConfig { max_concurrent_runs: Some(1), ..SchedulerConfig::default() };
+    let (_, scheduler) = scheduler(config).await;
+    let blocker = scheduler.add_task(task(700)).unwrap();
+    let limited = scheduler.add_task(task(500).with_timeout(1)).unwrap();
+
+    let first = scheduler.trigger_now(&blocker).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let triggered = Instant::now();
+    let second = scheduler.trigger_now(&limited).unwrap();
+    assert_eq!(finished(&scheduler, &first).await.status, ExecutionStatus::Success);
+    let execution = finished(&scheduler, &second).await;
+    // Waiting for the blocker took it past its 1s limit, but only the run itself counts.
+    assert!(triggered.elapsed() >= Duration::from_millis(1100), "{:?}", triggered.elapsed());
+    assert_eq!(execution.status, ExecutionStatus::Success);
+}
+
+#[tokio::test]
+async fn timeouts_are_retried_like_failures() {
+    let (sleeper, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let retry = RetryPolicy {
+        max_attempts: 2,
+        backoff_base_ms: 10,
+        max_delay_ms: 10,
+        jitter: 0.0,
+        retry_on: vec!["timed out".into()],
+    };
+    let id = scheduler.add_task(task(10_000).with_timeout(1).with_retry(retry).unwrap()).unwrap();
+    let first = scheduler.trigger_now(&id).unwrap();
+
+    for _ in 0..200 {
+        let executions = scheduler.list_executions(&id);
+        if executions.len() == 2 && executions[1].status == ExecutionStatus::Timeout {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let executions = scheduler.list_executions(&id);
+    let statuses: Vec<ExecutionStatus> = executions.iter().map(|e| e.status).collect();
+    assert_eq!(statuses, [ExecutionStatus::Timeout, ExecutionStatus::Timeout]);
+    assert_eq!(executions[1].retry_of, Some(first));
+    assert_eq!(sleeper.cancelled(), [true, true]);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn a_timed_out_shell_task_takes_its_whole_process_group_down() {
+    let scratch = Scratch::new("timeout");
+    let pid_file = scratch.join("pid");
+    let script = format!("sleep 30 & echo $! > {}; sleep 30; wait", pid_file.display());
+    let plugin = PluginConfig {
+        name: "shell".into(),
+        wasm_path: String::new(),
+        config: json!({ "argv": ["/bin/sh", "-c", script] }),
+    };
+    let (_, scheduler) = scheduler(SchedulerConfig::default()).await;
+    let id = scheduler.add_task(Task::new("shell".into(), Schedule::Manual, plugin).unwrap().with_timeout(1)).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+    assert_eq!(execution.status, ExecutionStatus::Timeout);
+
+    let pid: u32 = std::fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+    for _ in 0..50 {
+        if !alive(pid) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the backgrounded sleep ({}) outlived the task", pid);
+}
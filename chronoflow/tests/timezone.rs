@@ -0,0 +1,89 @@
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::{America::New_York, Europe::Berlin, Tz};
+use chronoflow::{CronExpr, PluginConfig, Schedule, Task};
+
+fn next(expr: &str, after: DateTime<Tz>) -> DateTime<Utc> {
+    CronExpr::parse(expr)
+        .unwrap()
+        .next_after_in(&after)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+}
+
+#[test]
+fn nightly_job_follows_local_wall_clock() {
+    let after = New_York.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    assert_eq!(next("0 2 * * *", after), utc(2024, 1, 16, 7, 0));
+
+    let after = New_York.with_ymd_and_hms(2024, 7, 15, 12, 0, 0).unwrap();
+    assert_eq!(next("0 2 * * *", after), utc(2024, 7, 16, 6, 0));
+}
+
+#[test]
+fn spring_forward_gap_fires_at_end_of_gap() {
+    // 2024-03-10 02:00 EST jumps to 03:00 EDT in New York.
+    let after = New_York.with_ymd_and_hms(2024, 3, 10, 1, 0, 0).unwrap();
+    assert_eq!(next("30 2 * * *", after), utc(2024, 3, 10, 7, 0));
+
+    // The following day runs at the normal wall-clock time again.
+    let fired = New_York.from_utc_datetime(&utc(2024, 3, 10, 7, 0).naive_utc());
+    assert_eq!(next("30 2 * * *", fired), utc(2024, 3, 11, 6, 30));
+}
+
+#[test]
+fn spring_forward_gap_collapses_into_one_fire() {
+    let cron = CronExpr::parse("*/15 * * * *").unwrap();
+    let after = New_York.with_ymd_and_hms(2024, 3, 10, 1, 50, 0).unwrap();
+    let fires: Vec<_> = std::iter::successors(cron.next_after_in(&after), |t| cron.next_after_in(t))
+        .take(3)
+        .map(|t| t.with_timezone(&Utc))
+        .collect();
+    assert_eq!(fires, vec![utc(2024, 3, 10, 7, 0), utc(2024, 3, 10, 7, 15), utc(2024, 3, 10, 7, 30)]);
+}
+
+#[test]
+fn fall_back_repeated_hour_fires_once() {
+    // 2024-11-03 02:00 EDT falls back to 01:00 EST in New York.
+    let after = New_York.with_ymd_and_hms(2024, 11, 3, 0, 0, 0).unwrap();
+    let first = next("30 1 * * *", after);
+    assert_eq!(first, utc(2024, 11, 3, 5, 30));
+
+    let fired = New_York.from_utc_datetime(&first.naive_utc());
+    assert_eq!(next("30 1 * * *", fired), utc(2024, 11, 4, 6, 30));
+}
+
+#[test]
+fn fall_back_does_not_refire_from_second_occurrence() {
+    // 01:10 EST is the second pass through 01:xx; 01:30 already fired as EDT.
+    let after = New_York.from_utc_datetime(&utc(2024, 11, 3, 6, 10).naive_utc());
+    assert_eq!(next("30 1 * * *", after), utc(2024, 11, 4, 6, 30));
+    assert_eq!(next("0 * * * *", after), utc(2024, 11, 3, 7, 0));
+}
+
+#[test]
+fn europe_transitions() {
+    // 2024-03-31 02:00 CET jumps to 03:00 CEST.
+    let after = Berlin.with_ymd_and_hms(2024, 3, 30, 12, 0, 0).unwrap();
+    assert_eq!(next("15 2 * * *", after), utc(2024, 3, 31, 1, 0));
+
+    // 2024-10-27 03:00 CEST falls back to 02:00 CET.
+    let after = Berlin.with_ymd_and_hms(2024, 10, 26, 12, 0, 0).unwrap();
+    assert_eq!(next("15 2 * * *", after), utc(2024, 10, 27, 0, 15));
+}
+
+#[test]
+fn task_timezone_is_validated() {
+    let plugin = PluginConfig {
+        name: "logger".to_string(),
+        wasm_path: String::new(),
+        config: serde_json::json!({}),
+    };
+    let task = Task::new("nightly".to_string(), Schedule::Cron("0 2 * * *".to_string()), plugin).unwrap();
+    assert_eq!(task.timezone, Tz::UTC);
+    assert_eq!(task.clone().with_timezone("America/New_York").unwrap().timezone, New_York);
+    assert!(task.with_timezone("Mars/Olympus_Mons").is_err());
+}
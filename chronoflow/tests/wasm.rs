@@ -0,0 +1,193 @@
+mod common;
+
+use chronoflow::{
+    ChronoError, ExecutionLog, ExecutionStatus, PluginConfig, PluginContext, PluginManager, Schedule, Scheduler,
+    Task, WasmLimits, WasmRuntime,
+};
+use common::{finished, Scratch};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// A plugin module whose `run` executes `body` and then answers with `response`. The
+/// request is placed at offset 1024, the response lives at offset 0.
+fn plugin(body: &str, response: &str) -> String {
+    let data: String = response.bytes().map(|b| format!("\\{:02x}", b)).collect();
+    format!(
+        r#"(module
+            (import "chronoflow" "log" (func $log (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{data}")
+            (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "run") (param $ptr i32) (param $len i32) (result i64)
+                {body}
+                (i64.const {len})))"#,
+        data = data,
+        body = body,
+        len = response.len(),
+    )
+}
+
+fn ok(message: &str) -> String {
+    json!({ "ok": { "message": message, "data": { "from": "wasm" } } }).to_string()
+}
+
+fn context(cancel: CancellationToken) -> PluginContext {
+    PluginContext {
+        task_id: Uuid::new_v4(),
+        execution_id: Uuid::new_v4(),
+        attempt: 1,
+        scheduled_at: None,
+        cancel,
+        log: ExecutionLog::default(),
+    }
+}
+
+async fn run(runtime: &WasmRuntime, path: &Path) -> chronoflow::Result<String> {
+    let output = runtime.execute(path, context(CancellationToken::new()), &json!({})).await?;
+    Ok(output.message)
+}
+
+fn runtime(limits: WasmLimits) -> WasmRuntime {
+    WasmRuntime::new(limits).unwrap()
+}
+
+fn error(result: chronoflow::Result<String>) -> String {
+    match result {
+        Err(ChronoError::PluginError(message)) => message,
+        other => panic!("expected a plugin error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn a_task_runs_a_module_with_its_config_and_context() {
+    let dir = Scratch::new("wasm");
+    // Logs the request it was given.
+    let path = dir.write("echo.wat", &plugin("(call $log (local.get $ptr) (local.get $len))", &ok("hello")));
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    scheduler.start().await;
+    let plugin = PluginConfig {
+        name: "echo".into(),
+        wasm_path: path.display().to_string(),
+        config: json!({ "greeting": "{{ task.name }}" }),
+    };
+    let id = scheduler.add_task(Task::new("wasm-task".into(), Schedule::Manual, plugin).unwrap()).unwrap();
+    let execution = finished(&scheduler, &scheduler.trigger_now(&id).unwrap()).await;
+
+    assert_eq!(execution.status, ExecutionStatus::Success, "{:?}", execution.error);
+    let output = execution.output.unwrap();
+    assert_eq!((output.message.as_str(), &output.data), ("hello", &json!({ "from": "wasm" })));
+    let request: serde_json::Value = serde_json::from_str(&execution.logs[0].message).unwrap();
+    assert_eq!(request["config"], json!({ "greeting": "wasm-task" }));
+    assert_eq!(request["context"]["task_id"], json!(id));
+    assert_eq!(request["context"]["execution_id"], json!(execution.id));
+    assert_eq!(request["context"]["attempt"], 1);
+}
+
+#[tokio::test]
+async fn an_error_response_fails_the_run() {
+    let dir = Scratch::new("wasm");
+    let path = dir.write("fail.wat", &plugin("", r#"{"error":"bad input"}"#));
+    assert_eq!(error(run(&runtime(WasmLimits::default()), &path).await), "bad input");
+    let path = dir.write("neither.wat", &plugin("", r#"{"result":1}"#));
+    assert!(error(run(&runtime(WasmLimits::default()), &path).await).contains("neither 'ok' nor 'error'"));
+}
+
+#[tokio::test]
+async fn a_run_stops_when_its_fuel_runs_out() {
+    let dir = Scratch::new("wasm");
+    let path = dir.write("spin.wat", &plugin("(loop $forever (br $forever))", &ok("unreachable")));
+    let limits = WasmLimits { fuel: 100_000, ..WasmLimits::default() };
+    let started = Instant::now();
+    let message = error(run(&runtime(limits), &path).await);
+    assert!(message.contains("fuel"), "{}", message);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn memory_cannot_grow_past_the_cap() {
+    let dir = Scratch::new("wasm");
+    // Grows by 32 pages (2 MiB) and traps if that was refused.
+    let grow = "(if (i32.eq (memory.grow (i32.const 32)) (i32.const -1)) (then unreachable))";
+    let path = dir.write("grow.wat", &plugin(grow, &ok("grown")));
+
+    assert_eq!(run(&runtime(WasmLimits::default()), &path).await.unwrap(), "grown");
+    let limits = WasmLimits { max_memory_bytes: 1024 * 1024, ..WasmLimits::default() };
+    let message = error(run(&runtime(limits), &path).await);
+    assert!(message.contains("unreachable"), "{}", message);
+}
+
+#[tokio::test]
+async fn cancelling_the_run_interrupts_the_module() {
+    let dir = Scratch::new("wasm");
+    let path = dir.write("spin.wat", &plugin("(loop $forever (br $forever))", &ok("unreachable")));
+    let runtime = runtime(WasmLimits { fuel: u64::MAX, ..WasmLimits::default() });
+    let cancel = CancellationToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        canceller.cancel();
+    });
+
+    let started = Instant::now();
+    let result = runtime.execute(&path, context(cancel), &json!({})).await.map(|o| o.message);
+    assert!(error(result).contains("cancelled"));
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn modules_without_the_plugin_abi_are_refused() {
+    let dir = Scratch::new("wasm");
+    let runtime = runtime(WasmLimits::default());
+    let path = dir.write("bare.wat", r#"(module (memory (export "memory") 1))"#);
+    assert!(error(runtime.load(&path).map(|_| String::new())).ends_with("missing export 'alloc'"));
+    let path = dir.write("broken.wat", "(module (func");
+    assert!(error(runtime.load(&path).map(|_| String::new())).starts_with(&path.display().to_string()));
+    assert!(runtime.load(&dir.join("absent.wasm")).is_err());
+
+    let scheduler = Scheduler::new(Arc::new(PluginManager::new()));
+    let plugin = PluginConfig { name: "bare".into(), wasm_path: path.display().to_string(), config: json!({}) };
+    assert!(scheduler.add_task(Task::new("bare".into(), Schedule::Manual, plugin).unwrap()).is_err());
+}
+
+#[tokio::test]
+async fn a_changed_module_is_recompiled() {
+    let dir = Scratch::new("wasm");
+    let runtime = runtime(WasmLimits::default());
+    let path = dir.write("plugin.wat", &plugin("", &ok("one")));
+    assert_eq!(run(&runtime, &path).await.unwrap(), "one");
+    assert_eq!(run(&runtime, &path).await.unwrap(), "one");
+
+    dir.write("plugin.wat", &plugin("", &ok("version two")));
+    assert_eq!(run(&runtime, &path).await.unwrap(), "version two");
+
+    // Same size and modification time; only the contents tell them apart.
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    dir.write("plugin.wat", &plugin("", &ok("version 003")));
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    assert_eq!(run(&runtime, &path).await.unwrap(), "version 003");
+}
+
+#[tokio::test]
+async fn the_module_cache_is_bounded() {
+    let dir = Scratch::new("wasm");
+    let small = runtime(WasmLimits::default()).with_cache_capacity(2);
+    let paths: Vec<PathBuf> =
+        (0..3).map(|n| dir.write(&format!("{}.wat", n), &plugin("", &ok(&n.to_string())))).collect();
+    for path in &paths {
+        run(&small, path).await.unwrap();
+    }
+    assert_eq!(small.cached_modules(), 2);
+
+    // Files with the same contents share a module.
+    let copy = dir.write("copy.wat", &plugin("", &ok("2")));
+    assert_eq!(run(&small, &copy).await.unwrap(), "2");
+    assert_eq!(small.cached_modules(), 2);
+
+    let uncached = runtime(WasmLimits::default()).with_cache_capacity(0);
+    assert_eq!(run(&uncached, &paths[0]).await.unwrap(), "0");
+    assert_eq!(uncached.cached_modules(), 0);
+}
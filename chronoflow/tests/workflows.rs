@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use chronoflow::{
+    ChronoError, Plugin, PluginConfig, PluginContext, PluginManager, PluginOutput, Result,
+    RetryPolicy, Schedule, Scheduler, StepStatus, Task, TaskExecution, TriggerCondition, WorkflowRun,
+    WorkflowStatus,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Fails a task's first `fail_times` runs, then answers with `message`.
+#[derive(Default)]
+struct Outcome {
+    runs: Mutex<HashMap<Uuid, u64>>,
+}
+
+#[async_trait]
+impl Plugin for Outcome {
+    fn name(&self) -> &str {
+        "outcome"
+    }
+
+    async fn execute(&self, ctx: PluginContext, config: &Value) -> Result<PluginOutput> {
+        let runs = {
+            let mut runs = self.runs.lock().unwrap();
+            let count = runs.entry(ctx.task_id).or_default();
+            *count += 1;
+            *count
+        };
+        if runs <= config["fail_times"].as_u64().unwrap_or(0) {
+            return Err(ChronoError::PluginError(format!("run {} failed", runs)));
+        }
+        Ok(PluginOutput::new(config["message"].as_str().unwrap_or("ok")).with_data(json!({ "runs": runs })))
+    }
+}
+
+async fn scheduler() -> Scheduler {
+    let plugins = Arc::new(PluginManager::new());
+    plugins.register(Arc::new(Outcome::default()));
+    let scheduler = Scheduler::new(plugins);
+    scheduler.start().await;
+    scheduler
+}
+
+fn task(name: &str, config: Value) -> Task {
+    let plugin = PluginConfig { name: "outcome".into(), wasm_path: String::new(), config };
+    Task::new(name.into(), Schedule::Manual, plugin).unwrap()
+}
+
+fn succeeds(name: &str) -> Task {
+    task(name, json!({}))
+}
+
+fn fails(name: &str) -> Task {
+    task(name, json!({ "fail_times": 100 }))
+}
+
+/// A task that succeeds, run after `upstream` if `condition` holds.
+fn after(name: &str, upstream: Uuid, condition: TriggerCondition) -> Task {
+    succeeds(name).with_dependency(upstream, condition)
+}
+
+/// The workflow run started by `root`'s first firing, once it has finished.
+async fn finished_run(scheduler: &Scheduler, root: &Uuid) -> WorkflowRun {
+    for _ in 0..200 {
+        if let Some(run) = scheduler.list_workflow_runs(root).into_iter().next() {
+            if run.is_finished() {
+                return run;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("workflow run of {} didn't finish: {:?}", root, scheduler.list_workflow_runs(root));
+}
+
+fn only_run(scheduler: &Scheduler, task_id: &Uuid) -> TaskExecution {
+    let executions = scheduler.list_executions(task_id);
+    assert_eq!(executions.len(), 1, "{:?}", executions);
+    executions.into_iter().next().unwrap()
+}
+
+#[tokio::test]
+async fn dependencies_must_exist_and_not_form_a_cycle() {
+    let scheduler = scheduler().await;
+    let a = scheduler.add_task(succeeds("a")).unwrap();
+    let b = scheduler.add_task(after("b", a, TriggerCondition::OnSuccess)).unwrap();
+    let c = scheduler.add_task(after("c", b, TriggerCondition::Always)).unwrap();
+
+    let mut looped = scheduler.get_task(&a).unwrap();
+    looped.depends_on.clear();
+    let looped = looped.with_dependency(c, TriggerCondition::OnFailure);
+    match scheduler.update_task(looped) {
+        Err(ChronoError::InvalidTask(message)) => assert_eq!(message, "dependency cycle: a -> b -> c -> a"),
+        other => panic!("expected a cycle error, got {:?}", other.map(|t| t.name)),
+    }
+    let own = scheduler.get_task(&b).unwrap().with_dependency(b, TriggerCondition::Always);
+    match scheduler.update_task(own) {
+        Err(ChronoError::InvalidTask(message)) => assert_eq!(message, "dependency cycle: b -> b"),
+        other => panic!("expected a cycle error, got {:?}", other.map(|t| t.name)),
+    }
+
+    let unknown = Uuid::new_v4();
+    match scheduler.add_task(after("d", unknown, TriggerCondition::OnSuccess)) {
+        Err(ChronoError::InvalidTask(message)) => {
+            assert_eq!(message, format!("'d' depends on unknown task {}", unknown));
+        }
+        other => panic!("expected an unknown dependency error, got {:?}", other),
+    }
+    match scheduler.remove_task(&b) {
+        Err(ChronoError::InvalidTask(message)) => assert!(message.ends_with("is a dependency of 'c'"), "{}", message),
+        other => panic!("expected b to be kept, got {:?}", other),
+    }
+    assert!(scheduler.get_task(&a).unwrap().depends_on.is_empty());
+}
+
+#[tokio::test]
+async fn a_diamond_fans_out_and_joins() {
+    let scheduler = scheduler().await;
+    let root = scheduler.add_task(succeeds("root")).unwrap();
+    let left = scheduler.add_task(after("left", root, TriggerCondition::OnSuccess)).unwrap();
+    let right = scheduler.add_task(after("right", root, TriggerCondition::OnSuccess)).unwrap();
+    let join = scheduler
+        .add_task(
+            task("join", json!({ "message": "{{ upstream.left.status }} {{ upstream.right.output.runs }}" }))
+                .with_dependency(left, TriggerCondition::OnSuccess)
+                .with_dependency(right, TriggerCondition::OnSuccess),
+        )
+        .unwrap();
+
+    let first = scheduler.trigger_now(&root).unwrap();
+    let run = finished_run(&scheduler, &root).await;
+    assert_eq!(run.status, WorkflowStatus::Success);
+    assert_eq!(run.steps.len(), 4);
+    assert!(run.steps.values().all(|s| *s == StepStatus::Success), "{:?}", run.steps);
+    assert_eq!(run.executions.len(), 4);
+    assert_eq!(run.executions[0], first);
+
+    let joined = only_run(&scheduler, &join);
+    assert_eq!(joined.workflow_run_id, Some(run.id));
+    for upstream in [left, right] {
+        let upstream = only_run(&scheduler, &upstream);
+        assert!(upstream.finished_at.unwrap() <= joined.started_at, "the join waits for both sides");
+    }
+    assert_eq!(joined.output.unwrap().message, "Success 1");
+}
+
+#[tokio::test]
+async fn a_join_across_separate_roots_is_rejected() {
+    let scheduler = scheduler().await;
+    let a = scheduler.add_task(succeeds("a")).unwrap();
+    let b = scheduler.add_task(succeeds("b")).unwrap();
+    let fan_in = || succeeds("c").with_dependency(a, TriggerCondition::OnSuccess)
+        .with_dependency(b, TriggerCondition::OnSuccess);
+    match scheduler.add_task(fan_in()) {
+        Err(ChronoError::InvalidTask(message)) => assert_eq!(
+            message,
+            "'c' joins tasks started by separate roots ('a', 'b'); give them a common upstream task"
+        ),
+        other => panic!("expected a separate roots error, got {:?}", other),
+    }
+
+    // Moving an existing task under another root is caught at its join downstream too.
+    let left = scheduler.add_task(after("left", a, TriggerCondition::OnSuccess)).unwrap();
+    let right = scheduler.add_task(after("right", a, TriggerCondition::OnSuccess)).unwrap();
+    let join = succeeds("join").with_dependency(left, TriggerCondition::OnSuccess);
+    scheduler.add_task(join.with_dependency(right, TriggerCondition::OnSuccess)).unwrap();
+    let mut moved = scheduler.get_task(&right).unwrap();
+    moved.depends_on.clear();
+    match scheduler.update_task(moved.with_dependency(b, TriggerCondition::OnSuccess)) {
+        Err(ChronoError::InvalidTask(message)) => assert!(message.starts_with("'join' joins"), "{}", message),
+        other => panic!("expected a separate roots error, got {:?}", other.map(|t| t.name)),
+    }
+
+    // Once the roots are joined under one, the fan-in runs after both of them.
+    let start = scheduler.add_task(succeeds("start")).unwrap();
+    for root in [a, b] {
+        let root = scheduler.get_task(&root).unwrap().with_dependency(start, TriggerCondition::OnSuccess);
+        scheduler.update_task(root).unwrap();
+    }
+    let c = scheduler.add_task(fan_in()).unwrap();
+    scheduler.trigger_now(&start).unwrap();
+    let run = finished_run(&scheduler, &start).await;
+    assert_eq!(run.status, WorkflowStatus::Success);
+    let joined = only_run(&scheduler, &c);
+    for upstream in [a, b] {
+        assert!(only_run(&scheduler, &upstream).finished_at.unwrap() <= joined.started_at);
+    }
+}
+
+#[tokio::test]
+async fn trigger_conditions_pick_the_branches_that_run() {
+    let scheduler = scheduler().await;
+    let root = scheduler.add_task(fails("root")).unwrap();
+    let on_success = scheduler.add_task(after("on-success", root, TriggerCondition::OnSuccess)).unwrap();
+    let on_failure = scheduler.add_task(after("on-failure", root, TriggerCondition::OnFailure)).unwrap();
+    let always = scheduler.add_task(after("always", root, TriggerCondition::Always)).unwrap();
+    // Downstream of a skipped step: only `Always` still runs.
+    let after_skip = scheduler.add_task(after("after-skip", on_success, TriggerCondition::OnSuccess)).unwrap();
+    let cleanup = scheduler.add_task(after("cleanup", on_success, TriggerCondition::Always)).unwrap();
+
+    scheduler.trigger_now(&root).unwrap();
+    let run = finished_run(&scheduler, &root).await;
+    assert_eq!(run.status, WorkflowStatus::Failed);
+    let expected = [
+        (root, StepStatus::Failed),
+        (on_success, StepStatus::Skipped),
+        (on_failure, StepStatus::Success),
+        (always, StepStatus::Success),
+        (after_skip, StepStatus::Skipped),
+        (cleanup, StepStatus::Success),
+    ];
+    for (id, status) in expected {
+        assert_eq!(run.steps[&id], status, "{}", scheduler.get_task(&id).unwrap().name);
+    }
+    assert!(scheduler.list_executions(&on_success).is_empty());
+    assert!(scheduler.list_executions(&after_skip).is_empty());
+    assert_eq!(run.executions.len(), 4);
+}
+
+#[tokio::test]
+async fn a_step_that_succeeds_on_retry_lets_the_run_continue() {
+    let scheduler = scheduler().await;
+    let retry = RetryPolicy {
+        max_attempts: 3,
+        backoff_base_ms: 10,
+        max_delay_ms: 10,
+        jitter: 0.0,
+        retry_on: Vec::new(),
+    };
+    let root = scheduler.add_task(task("root", json!({ "fail_times": 1 })).with_retry(retry).unwrap()).unwrap();
+    let next = scheduler.add_task(after("next", root, TriggerCondition::OnSuccess)).unwrap();
+    let fallback = scheduler.add_task(after("fallback", root, TriggerCondition::OnFailure)).unwrap();
+
+    scheduler.trigger_now(&root).unwrap();
+    let run = finished_run(&scheduler, &root).await;
+    assert_eq!(run.status, WorkflowStatus::Success);
+    assert_eq!(run.steps[&root], StepStatus::Success);
+    assert_eq!(run.steps[&fallback], StepStatus::Skipped, "the failed attempt alone doesn't trigger it");
+    let attempts = scheduler.list_executions(&root);
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(run.executions, [attempts[0].id, attempts[1].id, only_run(&scheduler, &next).id]);
+}
+
+#[tokio::test]
+async fn a_disabled_step_is_skipped() {
+    let scheduler = scheduler().await;
+    let root = scheduler.add_task(succeeds("root")).unwrap();
+    let mut paused = after("paused", root, TriggerCondition::OnSuccess);
+    paused.enabled = false;
+    let paused = scheduler.add_task(paused).unwrap();
+    let downstream = scheduler.add_task(after("downstream", paused, TriggerCondition::Always)).unwrap();
+
+    scheduler.trigger_now(&root).unwrap();
+    let run = finished_run(&scheduler, &root).await;
+    assert_eq!(run.status, WorkflowStatus::Success);
+    assert_eq!(run.steps[&paused], StepStatus::Skipped);
+    assert_eq!(run.steps[&downstream], StepStatus::Success);
+}
+
+#[tokio::test]
+async fn a_task_without_dependents_starts_no_workflow_run() {
+    let scheduler = scheduler().await;
+    let alone = scheduler.add_task(succeeds("alone")).unwrap();
+    scheduler.trigger_now(&alone).unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(only_run(&scheduler, &alone).workflow_run_id, None);
+    assert!(scheduler.list_workflow_runs(&alone).is_empty());
+}